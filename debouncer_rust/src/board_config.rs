@@ -0,0 +1,134 @@
+//! Pins, rates and timings for each prototype board, so they are all in one place.
+//! The first prototype is used unless the `prototype-2` feature is turned on.
+
+use hand_core::{
+    Finger, FingerRange, PwmTimebase, ResponseCurve, Synergy, ThermalLimits, Thresholds,
+};
+
+use crate::smoothing::Smoothing;
+
+/// Everything about a board that the firmware needs to know
+pub struct BoardConfig {
+    /// Printed at boot so the serial log shows which build is running.
+    /// The servo's pin itself is picked by `servo_pin!`, it is part of the pin's type.
+    pub name: &'static str,
+    pub clock_hz: u32,
+    pub baud_rate: u32,
+    /// Timer2 with the 1024 prescaler, counting to 255.
+    /// `HandIo::init` sets the timer's prescaler from this.
+    pub servo_timebase: PwmTimebase,
+    /// The servo on the board's servo pin
+    pub servo: ServoConfig,
+    /// The finger the servo closes, for picking its line of the grip
+    pub finger: Finger,
+    /// How the fingers close together as the effort goes up, at boot.
+    /// The `GRIP` command picks another
+    pub grip: Synergy,
+    /// The servo's angles for its finger open and closed, and how it closes with the effort.
+    /// Every finger's tendon is routed differently, so each needs its own
+    pub finger_range: FingerRange,
+    /// How long the servo is held at 0 at boot, for calibration
+    pub calibration_ms: u32,
+    /// Bits the EMG samples are oversampled to past the ADC's 10. Each one takes
+    /// four times the conversions a sample, at about 104 us a conversion with the
+    /// ADC's default clock, so 2 bits is about 1.7 ms a sample
+    pub oversample_bits: u8,
+    /// Time between steps of the test sweep
+    pub sweep_step_ms: u32,
+    /// How the EMG is smoothed at boot, `EMA` and `KALMAN` commands change it after
+    pub smoothing: Smoothing,
+    /// Where the smoothed EMG changes state, in ADC counts. Keep the graph's
+    /// thresholds the same so it agrees with the state the firmware sends
+    pub thresholds: Thresholds,
+}
+
+/// How far one servo turns and the pulses for the ends of its range,
+/// every servo model wants its own
+#[derive(Clone, Copy)]
+pub struct ServoConfig {
+    /// 90 for the fingers. A 180 degree wrist rotator would only get about 29 duty
+    /// steps out of Timer2 at /1024, so it needs a 16 bit timer before it is added
+    pub max_angle: u8,
+    /// Pulse width for 0 degrees
+    pub min_us: u32,
+    /// Pulse width for `max_angle` degrees
+    pub max_us: u32,
+    /// How fast it heats holding a grip and cools off, for backing off before it cooks.
+    /// Tuned for the angle being set about 100 times a second
+    pub thermal: ThermalLimits,
+}
+
+/// The finger servos, what duties 23 and 31 gave on Timer2
+pub const FINGER_SERVO: ServoConfig = ServoConfig {
+    max_angle: 90,
+    min_us: 1536,
+    max_us: 2048,
+    thermal: ThermalLimits::DEFAULT,
+};
+
+/// The first prototype, a Nano with the servo on D3
+const PROTOTYPE_1: BoardConfig = BoardConfig {
+    name: "prototype 1 (Nano, servo on D3)",
+    clock_hz: 16_000_000,
+    baud_rate: 57600,
+    servo_timebase: PwmTimebase::new(16_000_000, 1024, 255),
+    servo: FINGER_SERVO,
+    finger: Finger::Index,
+    grip: Synergy::POWER_GRASP,
+    finger_range: FingerRange {
+        open: 0,
+        closed: 90,
+        curve: ResponseCurve::Linear,
+    },
+    calibration_ms: 5000,
+    oversample_bits: 2,
+    sweep_step_ms: 500,
+    smoothing: Smoothing::Ema { alpha: 0.15 },
+    thresholds: Thresholds::DEFAULT,
+};
+
+#[cfg(not(feature = "prototype-2"))]
+pub const BOARD: BoardConfig = PROTOTYPE_1;
+
+#[cfg(not(feature = "prototype-2"))]
+pub type ServoPinId = arduino_hal::hal::port::PD3;
+
+/// Moves the board's servo pin out of `arduino_hal::pins!`.
+/// It is a macro so it can take it after the serial port has taken D0 and D1.
+#[cfg(not(feature = "prototype-2"))]
+macro_rules! servo_pin {
+    ($pins:ident) => {
+        $pins.d3
+    };
+}
+
+/// The second prototype, the same as the first but with the servo moved to D11,
+/// the other Timer2 output
+#[cfg(feature = "prototype-2")]
+pub const BOARD: BoardConfig = BoardConfig {
+    name: "prototype 2 (Nano, servo on D11)",
+    ..PROTOTYPE_1
+};
+
+#[cfg(feature = "prototype-2")]
+pub type ServoPinId = arduino_hal::hal::port::PB3;
+
+#[cfg(feature = "prototype-2")]
+macro_rules! servo_pin {
+    ($pins:ident) => {
+        $pins.d11
+    };
+}
+
+pub(crate) use servo_pin;
+
+// the build fails if the timer can't make a servo's pulses
+const _: () = assert!(BOARD
+    .servo_timebase
+    .can_represent(BOARD.servo.min_us, BOARD.servo.max_us));
+const _: () = assert!(BOARD.servo_timebase.clock_hz == BOARD.clock_hz);
+// the finger's angles have to be ones the servo can turn to
+const _: () = assert!(
+    BOARD.finger_range.open <= BOARD.servo.max_angle
+        && BOARD.finger_range.closed <= BOARD.servo.max_angle
+);
@@ -0,0 +1,74 @@
+//! Commands sent over serial, read a line at a time without waiting for them
+
+use arduino_hal::prelude::*;
+use hand_core::Synergy;
+
+use crate::smoothing::Smoothing;
+
+/// Longest command kept, anything past it is dropped and the command won't parse
+const MAX_LINE: usize = 32;
+
+/// The grips `GRIP` can pick, by the name it takes
+const GRIPS: [(&str, Synergy); 3] = [
+    ("POWER", Synergy::POWER_GRASP),
+    ("PINCH", Synergy::PINCH),
+    ("LOCKSTEP", Synergy::LOCKSTEP),
+];
+
+/// What the firmware can be told to do over serial
+pub enum Command {
+    /// `EMA` or `KALMAN`, smooth the EMG with other settings
+    Smooth(Smoothing),
+    /// `GRIP <name>`, close the fingers in another pattern
+    Grip(Synergy),
+}
+
+impl Command {
+    /// The command in a line, `None` if it isn't one
+    pub fn parse(line: &str) -> Option<Command> {
+        if let Some(smoothing) = Smoothing::parse(line) {
+            return Some(Command::Smooth(smoothing));
+        }
+        let mut words = line.split_ascii_whitespace();
+        match (words.next()?, words.next()?, words.next()) {
+            ("GRIP", name, None) => GRIPS
+                .iter()
+                .find(|(grip, _)| *grip == name)
+                .map(|&(_, synergy)| Command::Grip(synergy)),
+            _ => None,
+        }
+    }
+}
+
+/// Collects the bytes of a command until its newline comes in.
+/// The USART only holds a couple of bytes, so ones that arrive while a line is being
+/// printed can be lost. A garbled command just gets an error back and can be sent again.
+pub struct CommandReader {
+    line: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl CommandReader {
+    pub fn new() -> CommandReader {
+        CommandReader {
+            line: [0; MAX_LINE],
+            len: 0,
+        }
+    }
+
+    /// Read whatever has arrived, giving back a command once a whole line is in
+    pub fn poll(&mut self, serial: &mut arduino_hal::DefaultSerial) -> Option<&str> {
+        while let Ok(byte) = serial.read() {
+            if byte == b'\n' || byte == b'\r' {
+                let len = core::mem::take(&mut self.len);
+                if len > 0 {
+                    return core::str::from_utf8(&self.line[..len]).ok();
+                }
+            } else if self.len < MAX_LINE {
+                self.line[self.len] = byte;
+                self.len += 1;
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,46 @@
+//! All the pin setup in one place, the pins themselves are picked in `board_config`
+
+use arduino_hal::hal::clock::Clock;
+use arduino_hal::simple_pwm::{IntoPwmPin, Prescaler, Timer2Pwm};
+
+use crate::board_config::{servo_pin, BOARD};
+use crate::Servo;
+
+/// Timer2's prescaler setting for the divider in `BOARD.servo_timebase`,
+/// so the timer is always set up the way the duty math expects
+const SERVO_PRESCALER: Prescaler = match BOARD.servo_timebase.prescaler {
+    1 => Prescaler::Direct,
+    8 => Prescaler::Prescale8,
+    64 => Prescaler::Prescale64,
+    256 => Prescaler::Prescale256,
+    1024 => Prescaler::Prescale1024,
+    _ => panic!("Timer2 can't divide the clock by the servo timebase's prescaler"),
+};
+
+// Timer2 is 8 bit and its fast PWM always counts to the top
+const _: () = assert!(BOARD.servo_timebase.top == 255);
+// the duty math has to use the clock the board really runs at
+const _: () = assert!(BOARD.clock_hz == <arduino_hal::DefaultClock as Clock>::FREQ);
+
+/// The pins and peripherals the hand uses, set up and ready to hand out
+pub struct HandIo {
+    pub serial: arduino_hal::DefaultSerial,
+    pub servo: Servo,
+}
+
+impl HandIo {
+    /// Set up every pin the board profile uses
+    pub fn init(dp: arduino_hal::Peripherals) -> HandIo {
+        let pins = arduino_hal::pins!(dp);
+        let serial = arduino_hal::default_serial!(dp, pins, BOARD.baud_rate);
+
+        let mut timer = Timer2Pwm::new(dp.TC2, SERVO_PRESCALER);
+        let mut servo_pin = servo_pin!(pins).into_output().into_pwm(&mut timer);
+        servo_pin.enable();
+
+        HandIo {
+            serial,
+            servo: Servo::new(servo_pin, BOARD.servo),
+        }
+    }
+}
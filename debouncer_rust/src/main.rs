@@ -33,218 +33,181 @@ use arduino_hal::port::Pin;
 //     loop {}
 // }
 
-use arduino_hal::simple_pwm::IntoPwmPin;
 use arduino_hal::simple_pwm::Timer2Pwm;
 use panic_halt as _;
 
-// ================== Testing =====================
-/// This is a simulator for when we don't have an EMG to test with, it uses random walks to get a seemingly resable graph for and EMG
-
-pub enum EmgState {
-    Relaxed,
-    Intermediate,
-    Clenched,
+use hand_core::{Classifier, EmgSimulator, LcgRng, Oversampler, ThermalModel};
+
+mod board_config;
+mod commands;
+mod hand_io;
+mod smoothing;
+use board_config::{ServoConfig, ServoPinId, BOARD};
+use commands::{Command, CommandReader};
+use hand_io::HandIo;
+use smoothing::Smoother;
+
+/// How far a sample from 0 to `full_scale` is up its range, in percent,
+/// `full_scale` is 1023 for a single ADC conversion
+pub fn to_percent(sample: u16, full_scale: u16) -> u8 {
+    let full_scale = full_scale.max(1) as u32;
+    ((sample as u32).min(full_scale) * 100 / full_scale) as u8
 }
 
-pub struct EmgSimulator {
-    step_count: u32,
-    state: EmgState,
-    phase: u16,
-    spike_remaining: u8, // Counts how many steps left in spike
+pub struct Servo {
+    pin: Pin<PwmOutput<Timer2Pwm>, ServoPinId>,
+    /// How far the servo turns, 90 for the fingers
+    max_angle: u8,
+    /// Pulse widths for 0 and `max_angle` degrees
+    min_us: u32,
+    max_us: u32,
+    /// How hot holding the angles it has been set to is making it
+    thermal: ThermalModel,
 }
 
-impl EmgSimulator {
-    pub fn new() -> Self {
-        Self {
-            step_count: 0,
-            state: EmgState::Relaxed,
-            phase: 0,
-            spike_remaining: 0,
+impl Servo {
+    pub fn new(pin: Pin<PwmOutput<Timer2Pwm>, ServoPinId>, config: ServoConfig) -> Servo {
+        Servo {
+            pin,
+            max_angle: config.max_angle.max(1),
+            min_us: config.min_us,
+            max_us: config.max_us.max(config.min_us),
+            thermal: ThermalModel::new(config.thermal),
         }
     }
 
-    pub fn next(&mut self, noise: u16) -> u16 {
-        self.step_count = self.step_count.wrapping_add(1);
-        self.phase = self.phase.wrapping_add(17);
-
-        // Change state every 1000 samples based on noise
-        if self.step_count % 1000 == 0 {
-            let r = noise % 100;
-            self.state = if r < 50 {
-                EmgState::Relaxed
-            } else if r < 80 {
-                EmgState::Intermediate
-            } else {
-                EmgState::Clenched
-            };
-        }
-
-        // Trigger spike if none active and noise meets condition
-        if self.spike_remaining == 0 && (noise % 200 == 0) {
-            // spike length pseudo-random from 1 to 5 inclusive
-            self.spike_remaining = (noise % 5 + 1) as u8;
-        }
-
-        // If in spike, output max value and decrement spike timer
-        if self.spike_remaining > 0 {
-            self.spike_remaining -= 1;
-            return 1023;
-        }
-
-        // Normal signal calculation
-        let (baseline, amplitude): (u16, u16) = match self.state {
-            EmgState::Relaxed => (200, 50),
-            EmgState::Intermediate => (620, 30),
-            EmgState::Clenched => (940, 10),
+    /// Turn to `angle` degrees, from 0 to `max_angle`. The further it turns the harder it
+    /// works, so once it has held a lot for long it is backed off a little while it cools.
+    pub fn set_angle(&mut self, angle: u8) {
+        let angle = angle.min(self.max_angle) as u32;
+        let load = angle * 100 / self.max_angle as u32;
+        let allowed = self.thermal.update(load as u8) as u32;
+        let angle = if allowed < load {
+            allowed * self.max_angle as u32 / 100
+        } else {
+            angle
         };
-
-        let jitter = ((noise % (2 * amplitude)) as i16) - (amplitude as i16);
-
-        let artifact = if (self.phase % 256) < 128 { 3 } else { -3 };
-
-        let mut signal = baseline as i16 + jitter + artifact;
-
-        signal = signal.clamp(0, 1023);
-
-        signal as u16
+        let span = self.max_us - self.min_us;
+        self.set_pulse_us(self.min_us + angle * span / self.max_angle as u32);
     }
-}
 
-/// A very bad random number generator that works with no_std
-pub struct LcgRng {
-    state: u32,
-}
-
-impl LcgRng {
-    pub fn new(seed: u32) -> Self {
-        Self { state: seed }
-    }
-
-    pub fn next_u32(&mut self) -> u32 {
-        // Use a mix of wrapping mul, add, xor and shifts to scramble bits
-        self.state = self.state.wrapping_mul(0x6C8E9CF5);
-        self.state ^= self.state >> 13;
-        self.state = self.state.wrapping_add(0xB5297A4D);
-        self.state ^= self.state << 17;
-        self.state = self.state.wrapping_sub(0xD6E8FEB8);
-        self.state ^= self.state >> 5;
-        self.state
+    /// If the angle is being backed off to let it cool
+    pub fn is_hot(&self) -> bool {
+        self.thermal.is_hot()
     }
 
-    pub fn rand_bounded_u32(&mut self, bound: u32) -> u32 {
-        self.next_u32() % bound
+    /// Send pulses `pulse_us` long, the duty for it comes from the timer's timebase
+    pub fn set_pulse_us(&mut self, pulse_us: u32) {
+        let pulse_us = pulse_us.clamp(self.min_us, self.max_us);
+        if let Some(duty) = BOARD.servo_timebase.duty_for_us(pulse_us) {
+            self.pin.set_duty(duty as u8);
+        }
     }
 }
 
-/// A rolling average for data over time
-pub struct ExponentialMovingAverage {
-    /// Stores the last value from the data
-    pub ema: f32,
-    /// How much the newest value effects the value.
-    /// A lower alpha means a slower responce time.
-    /// But a higher alpha has the ema follow the data more closly
-    pub alpha: f32,
-    last_input: f32,
-}
-
-impl ExponentialMovingAverage {
-    /// update the value from new data
-    pub fn update(&mut self, input: u16) -> u16 {
-        let input_f32 = input as f32;
-        let max_slope = (self.last_input - self.ema).abs();
-
-        let go_to = self.alpha * input_f32 + (1.0 - self.alpha) * self.ema;
-        let slope = go_to - self.ema;
-        self.ema = self.ema + slope.clamp(-max_slope, max_slope);
-
-        self.last_input = input_f32;
-
-        self.ema as u16
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = arduino_hal::Peripherals::take().unwrap();
+    let HandIo {
+        mut serial,
+        servo: mut s,
+    } = HandIo::init(dp);
+    let _ = ufmt::uwriteln!(&mut serial, "board:{}", BOARD.name);
+
+    // hold the angle at 0 for callibration, rounding up to whole 55 ms steps so it is
+    // never shorter than the board asks for
+    for _ in 0..=BOARD.calibration_ms / 55 {
+        s.set_angle(0);
+        delay_ms(55);
     }
 
-    pub fn new(alpha: f32) -> ExponentialMovingAverage {
-        ExponentialMovingAverage {
-            ema: 0.0,
-            alpha: alpha,
-            last_input: 0.0,
-        }
+    if cfg!(feature = "emg-sim") {
+        follow_emg(&mut s, &mut serial);
     }
-}
-
-pub fn fron_1023_to_90(number: u16) -> u8 {
-    ((number as u32).saturating_mul(90) / 1023) as u8
-}
 
-struct Servo {
-    pin: Pin<PwmOutput<Timer2Pwm>, arduino_hal::hal::port::PD3>,
-}
-
-impl Servo {
-    pub fn new(pin: Pin<PwmOutput<Timer2Pwm>, arduino_hal::hal::port::PD3>) -> Servo {
-        Servo { pin }
-    }
+    let mut u8_value = 0;
 
-    pub fn set_angle(&mut self, angle: u8) {
-        // let duty = 23 + ((angle as u32 * (31 - 23)) / 90) as u8;
-        self.pin.set_duty(angle);
+    loop {
+        s.set_angle(u8_value);
+        delay_ms(BOARD.sweep_step_ms);
+        let _ = ufmt::uwriteln!(&mut serial, "u8_value:{}", u8_value);
+        u8_value = if u8_value >= s.max_angle {
+            0
+        } else {
+            u8_value + 1
+        };
     }
 }
 
-#[arduino_hal::entry]
-fn main() -> ! {
-    let dp = arduino_hal::Peripherals::take().unwrap();
-    let pins = arduino_hal::pins!(dp);
-    let mut serial = arduino_hal::default_serial!(dp, pins, 57600);
-
-    let mut timer = Timer2Pwm::new(dp.TC2, arduino_hal::simple_pwm::Prescaler::Prescale1024);
-    let mut servo_pin = pins.d3.into_output().into_pwm(&mut timer); // or use D3 instead
-
+/// Drive the servo from the simulated EMG forever, printing each sample
+fn follow_emg(s: &mut Servo, serial: &mut arduino_hal::DefaultSerial) -> ! {
     // ========================== Testing ===================================
     let mut rng = LcgRng::new(42);
     let mut emg_sim = EmgSimulator::new();
     // ======================== Testing: End ================================
 
-    let mut ema = ExponentialMovingAverage::new(0.15); // the alpha
-                                                       // effects how much the new value is used
+    let mut oversampler = Oversampler::new(BOARD.oversample_bits);
 
-    servo_pin.enable();
-    let mut s = Servo::new(servo_pin);
+    let mut smoother = Smoother::new(BOARD.smoothing, BOARD.oversample_bits);
+    let mut commands = CommandReader::new();
+    let mut classifier = Classifier::new(BOARD.thresholds);
+    let mut grip = BOARD.grip;
 
-    // set the angle to 0 for callibration for 5 seconds
-    for _ in 0..=90 {
-        s.set_angle(0);
-        delay_ms(55);
-    }
+    loop {
+        if let Some(line) = commands.poll(serial) {
+            let known = match Command::parse(line) {
+                Some(Command::Smooth(smoothing)) => {
+                    smoother.retune(smoothing);
+                    true
+                }
+                Some(Command::Grip(synergy)) => {
+                    grip = synergy;
+                    true
+                }
+                None => false,
+            };
+            let _ = ufmt::uwriteln!(serial, "{}", if known { "ok" } else { "unknown command" });
+        }
 
-    let mut u8_value = 0;
+        // several conversions go into each sample, from 0 to `oversampler.full_scale()`
+        let raw = loop {
+            // use rng for testing and read for functional
+            let input = rng.rand_bounded_u32(1023) as u16;
+            // let input = a0.analog_read(adc);
 
-    loop {
-        s.set_angle(u8_value);
-        delay_ms(500);
-        let _ = ufmt::uwriteln!(&mut serial, "u8_value:{}", u8_value);
-        u8_value = u8_value.wrapping_add(1);
-
-        // // use rng for testing and read for functional
-        // let input = rng.rand_bounded_u32(1023) as u16;
-        // // let input = a0.analog_read(adc);
-
-        // let raw = emg_sim.next(input);
-        // let smoothed = ema.update(raw.clone());
-
-        // // from looking at the code provided in EMG_HAND_CM.ino (TEAMS GENERAL)
-        // // it seems that the servo rotates between 0 and 90
-        // // so we need a function that takes balues from 0 to 1023
-        // // to be from 0 to 90 for the hand to function
-        // let motor_out = fron_1023_to_90(smoothed);
-
-        // s.set_angle(motor_out);
-
-        // let _ = ufmt::uwriteln!(
-        //     &mut serial,
-        //     "raw:{}, smoothed:{}, motor:{}",
-        //     raw,
-        //     smoothed,
-        //     motor_out
-        // );
+            if let Some(sample) = oversampler.push(emg_sim.next(input)) {
+                break sample;
+            }
+        };
+        let smoothed = smoother.update(raw);
+
+        // the smoothed sample as a percent of full scale, how far the grip closes the
+        // finger for that, then the angle in the finger's range for that closure
+        let effort = to_percent(smoothed, oversampler.full_scale());
+        let closure = grip.closure(BOARD.finger, effort);
+        let motor_out = BOARD.finger_range.angle(closure);
+
+        s.set_angle(motor_out);
+
+        // ufmt can't print floats, so the slope is sent in hundredths of a count per sample.
+        // The graph reads ADC counts, so the extra bits are taken back off what is sent
+        let slope = (smoother.slope() * 100.0) as i32 >> BOARD.oversample_bits;
+
+        // classified from the same counts that are sent as `smoothed`, so the graph
+        // can run its classifier on them and check it gets the same state
+        let smoothed_counts = oversampler.to_counts(smoothed);
+        let state = classifier.update(smoothed_counts);
+
+        let _ = ufmt::uwriteln!(
+            serial,
+            "raw:{}, smoothed:{}, slope_x100:{}, motor:{}, motor_range:{}, state:{}, hot:{}",
+            oversampler.to_counts(raw),
+            smoothed_counts,
+            slope,
+            motor_out,
+            s.max_angle,
+            state.code(),
+            s.is_hot() as u8
+        );
     }
 }
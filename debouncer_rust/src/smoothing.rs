@@ -0,0 +1,112 @@
+//! The filter the EMG is smoothed with, picked in `board_config` and changeable over serial
+
+use hand_core::{ExponentialMovingAverage, KalmanFilter};
+
+/// Which filter smooths the EMG, with its settings. The Kalman noises are in ADC counts
+/// like the voltage graph's filters, so settings tuned there can be sent straight over.
+#[derive(Clone, Copy)]
+pub enum Smoothing {
+    Ema {
+        alpha: f32,
+    },
+    Kalman {
+        process_noise: f32,
+        measurement_noise: f32,
+    },
+}
+
+impl Smoothing {
+    /// The settings in a serial command, `EMA <alpha>` or
+    /// `KALMAN <process noise> <measurement noise>`, all in thousandths so the float
+    /// parser isn't built in. `None` if it isn't one of them.
+    pub fn parse(command: &str) -> Option<Smoothing> {
+        let mut words = command.split_ascii_whitespace();
+        let name = words.next()?;
+        let mut setting = || Some(words.next()?.parse::<u32>().ok()? as f32 / 1000.0);
+        let smoothing = match name {
+            "EMA" => Smoothing::Ema { alpha: setting()? },
+            "KALMAN" => Smoothing::Kalman {
+                process_noise: setting()?,
+                measurement_noise: setting()?,
+            },
+            _ => return None,
+        };
+        words.next().is_none().then_some(smoothing)
+    }
+}
+
+/// A `Smoothing` running over samples oversampled by `extra_bits`
+pub struct Smoother {
+    filter: Filter,
+    extra_bits: u8,
+}
+
+enum Filter {
+    Ema(ExponentialMovingAverage),
+    Kalman(KalmanFilter),
+}
+
+impl Smoother {
+    pub fn new(smoothing: Smoothing, extra_bits: u8) -> Smoother {
+        Smoother {
+            filter: Filter::start(smoothing, extra_bits),
+            extra_bits,
+        }
+    }
+
+    /// Change the settings. The same kind of filter carries on from where it is,
+    /// switching to the other kind starts it fresh
+    pub fn retune(&mut self, smoothing: Smoothing) {
+        let scale = noise_scale(self.extra_bits);
+        match (&mut self.filter, smoothing) {
+            (Filter::Ema(ema), Smoothing::Ema { alpha }) => ema.alpha = alpha,
+            (
+                Filter::Kalman(kalman),
+                Smoothing::Kalman {
+                    process_noise,
+                    measurement_noise,
+                },
+            ) => kalman.set_noise(process_noise * scale, measurement_noise * scale),
+            _ => self.filter = Filter::start(smoothing, self.extra_bits),
+        }
+    }
+
+    pub fn update(&mut self, sample: u16) -> u16 {
+        match &mut self.filter {
+            Filter::Ema(ema) => ema.update(sample),
+            Filter::Kalman(kalman) => kalman.update(sample),
+        }
+    }
+
+    /// How much the smoothed value is changing, in oversampled counts per sample
+    pub fn slope(&self) -> f32 {
+        match &self.filter {
+            Filter::Ema(ema) => ema.slope(),
+            Filter::Kalman(kalman) => kalman.rate(),
+        }
+    }
+}
+
+impl Filter {
+    fn start(smoothing: Smoothing, extra_bits: u8) -> Filter {
+        match smoothing {
+            Smoothing::Ema { alpha } => Filter::Ema(ExponentialMovingAverage::new(alpha)),
+            Smoothing::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                let scale = noise_scale(extra_bits);
+                Filter::Kalman(KalmanFilter::new(
+                    process_noise * scale,
+                    measurement_noise * scale,
+                ))
+            }
+        }
+    }
+}
+
+/// The noises are variances in ADC counts, so they go up by the square of how much
+/// bigger the oversampled counts are
+fn noise_scale(extra_bits: u8) -> f32 {
+    (1u32 << (2 * extra_bits)) as f32
+}
@@ -0,0 +1,514 @@
+/// A rolling average for data over time
+pub struct ExponentialMovingAverage {
+    /// Stores the last value from the data
+    pub ema: f32,
+    /// How much the newest value effects the value.
+    /// A lower alpha means a slower responce time.
+    /// But a higher alpha has the ema follow the data more closly
+    pub alpha: f32,
+    /// How much the slope is smoothed, the same way alpha smooths the data.
+    /// 1.0 leaves it as the raw change from one sample to the next,
+    /// lower values keep noise from being amplified
+    pub slope_alpha: f32,
+    last_input: f32,
+    slope: f32,
+}
+
+impl ExponentialMovingAverage {
+    /// update the value from new data
+    pub fn update(&mut self, input: u16) -> u16 {
+        let input_f32 = input as f32;
+        let max_slope = (self.last_input - self.ema).abs();
+
+        let go_to = self.alpha * input_f32 + (1.0 - self.alpha) * self.ema;
+        let slope = go_to - self.ema;
+        let change = slope.clamp(-max_slope, max_slope);
+        self.ema += change;
+        self.slope += self.slope_alpha * (change - self.slope);
+
+        self.last_input = input_f32;
+
+        self.ema as u16
+    }
+
+    /// How much the smoothed value changed on each of the last samples, in ADC counts
+    /// per sample, smoothed by `slope_alpha`. Multiply by the sample rate for counts per second.
+    pub fn slope(&self) -> f32 {
+        self.slope
+    }
+
+    pub fn new(alpha: f32) -> ExponentialMovingAverage {
+        ExponentialMovingAverage {
+            ema: 0.0,
+            alpha,
+            slope_alpha: 1.0,
+            last_input: 0.0,
+            slope: 0.0,
+        }
+    }
+}
+
+/// Bits after the point in `KalmanFilter`'s fixed-point numbers
+const FRACTION_BITS: u32 = 16;
+/// 1.0 in `KalmanFilter`'s fixed-point numbers
+const ONE: i64 = 1 << FRACTION_BITS;
+/// The gains are between 0 and 1 and can be very small, so they get more bits
+const GAIN_BITS: u32 = 28;
+/// Highest noise setting, so the fixed-point numbers can't overflow
+const MAX_NOISE: f32 = 10_000.0;
+/// The level and rate are kept within this either way, so they can't overflow either
+const LIMIT: i64 = (u16::MAX as i64) << FRACTION_BITS;
+
+/// Tracks the level of the data and how fast it is changing, with a Kalman filter.
+/// It follows fast contractions with less lag than an EMA smoothing just as much.
+/// Everything is kept in fixed point with 16 bits after the point, and 28 for the gains,
+/// so updating it doesn't need floats on the AVR.
+pub struct KalmanFilter {
+    /// How much the rate of change is expected to wander each sample.
+    /// Higher follows the data faster but lets more noise through
+    process_noise: i64,
+    /// How noisy the data is, as a variance in ADC counts squared
+    measurement_noise: i64,
+    level: i64,
+    rate: i64,
+    /// The covariance of the level and rate estimates, it is symmetric so one corner is left out
+    p_level: i64,
+    p_cross: i64,
+    p_rate: i64,
+}
+
+impl KalmanFilter {
+    /// update the estimate from new data
+    pub fn update(&mut self, input: u16) -> u16 {
+        // predict: carry on at the same rate
+        self.level += self.rate;
+        let q = self.process_noise;
+        self.p_level += 2 * self.p_cross + self.p_rate + q / 4;
+        self.p_cross += self.p_rate + q / 2;
+        self.p_rate += q;
+
+        // correct towards the new data, trusting it by how noisy it is
+        let innovation = ((input as i64) << FRACTION_BITS) - self.level;
+        let s = (self.p_level + self.measurement_noise).max(1);
+        let level_gain = gain(self.p_level, s);
+        let rate_gain = gain(self.p_cross, s);
+        let keep = (1 << GAIN_BITS) - level_gain;
+        self.level += apply_gain(level_gain, innovation);
+        self.rate += apply_gain(rate_gain, innovation);
+        self.p_rate -= apply_gain(rate_gain, self.p_cross);
+        self.p_cross = apply_gain(keep, self.p_cross);
+        self.p_level = apply_gain(keep, self.p_level);
+        // only a wild overshoot on data jumping rail to rail gets this far out
+        self.level = self.level.clamp(-LIMIT, 2 * LIMIT);
+        self.rate = self.rate.clamp(-LIMIT, LIMIT);
+
+        ((self.level + ONE / 2) >> FRACTION_BITS).clamp(0, u16::MAX as i64) as u16
+    }
+
+    /// How fast the level is changing, in ADC counts per sample
+    pub fn rate(&self) -> f32 {
+        self.rate as f32 / ONE as f32
+    }
+
+    /// Change the noise settings, carrying on from the level and rate it has now
+    pub fn set_noise(&mut self, process_noise: f32, measurement_noise: f32) {
+        self.process_noise = to_fixed(process_noise);
+        self.measurement_noise = to_fixed(measurement_noise);
+    }
+
+    /// Only the settings are turned from floats, the updates are all fixed point.
+    /// Noise settings above 10000 are taken as 10000.
+    pub fn new(process_noise: f32, measurement_noise: f32) -> KalmanFilter {
+        let measurement_noise = to_fixed(measurement_noise);
+        KalmanFilter {
+            process_noise: to_fixed(process_noise),
+            measurement_noise,
+            level: 0,
+            rate: 0,
+            p_level: measurement_noise,
+            p_cross: 0,
+            p_rate: ONE,
+        }
+    }
+}
+
+fn to_fixed(value: f32) -> i64 {
+    (value.clamp(0.0, MAX_NOISE) * ONE as f32 + 0.5) as i64
+}
+
+/// `a / b` as a gain, rounded, `b` has to be above 0
+fn gain(a: i64, b: i64) -> i64 {
+    ((a << GAIN_BITS) + b / 2).div_euclid(b)
+}
+
+/// `value` scaled by a gain, rounded
+fn apply_gain(gain: i64, value: i64) -> i64 {
+    (gain * value + (1 << (GAIN_BITS - 1))) >> GAIN_BITS
+}
+
+/// Follows the data up straight away but only lets it fall slowly, so a short strong
+/// contraction closes the hand and it relaxes gently after
+pub struct PeakHold {
+    /// How fast the output falls, in degrees a second of the servo it drives
+    pub degrees_per_second: f32,
+    /// Samples a second, to turn the fall into counts per sample
+    pub sample_rate: f32,
+    /// The servo's range, what the whole input from 0 to `full_scale` maps onto.
+    /// 90 for the fingers
+    pub max_angle: u8,
+    /// The largest input, 1023 for one ADC conversion. Oversampled samples go up to
+    /// `Oversampler::full_scale`
+    pub full_scale: u16,
+    level: f32,
+}
+
+impl PeakHold {
+    /// update the held value from new data
+    pub fn update(&mut self, input: u16) -> u16 {
+        let input = input as f32;
+        self.level = if input >= self.level {
+            input
+        } else {
+            (self.level - self.decay()).max(input)
+        };
+        self.level as u16
+    }
+
+    /// How far the output can fall each sample, in ADC counts
+    pub fn decay(&self) -> f32 {
+        let counts_per_degree = self.full_scale as f32 / self.max_angle.max(1) as f32;
+        self.degrees_per_second * counts_per_degree / self.sample_rate.max(f32::EPSILON)
+    }
+
+    /// Let go of the held value, so the output drops to the data on the next update
+    pub fn release(&mut self) {
+        self.level = 0.0;
+    }
+
+    /// Falling `degrees_per_second` of a finger's 90 degrees, at `sample_rate` samples a second
+    /// of single ADC conversions
+    pub fn new(degrees_per_second: f32, sample_rate: f32) -> PeakHold {
+        PeakHold {
+            degrees_per_second,
+            sample_rate,
+            max_angle: 90,
+            full_scale: 1023,
+            level: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `before` for a while then step to `after`, giving the smoothed value and slope
+    /// on each sample from the step on
+    fn step_response(
+        ema: &mut ExponentialMovingAverage,
+        before: u16,
+        after: u16,
+    ) -> [(u16, f32); 100] {
+        for _ in 0..200 {
+            ema.update(before);
+        }
+        core::array::from_fn(|_| (ema.update(after), ema.slope()))
+    }
+
+    /// The same Kalman filter in f64, to check the fixed point one against
+    struct KalmanReference {
+        q: f64,
+        r: f64,
+        level: f64,
+        rate: f64,
+        p_level: f64,
+        p_cross: f64,
+        p_rate: f64,
+    }
+
+    impl KalmanReference {
+        fn new(q: f64, r: f64) -> Self {
+            Self {
+                q,
+                r,
+                level: 0.0,
+                rate: 0.0,
+                p_level: r,
+                p_cross: 0.0,
+                p_rate: 1.0,
+            }
+        }
+
+        fn update(&mut self, input: u16) -> f64 {
+            self.level += self.rate;
+            self.p_level += 2.0 * self.p_cross + self.p_rate + self.q / 4.0;
+            self.p_cross += self.p_rate + self.q / 2.0;
+            self.p_rate += self.q;
+
+            let innovation = input as f64 - self.level;
+            let s = self.p_level + self.r;
+            let level_gain = self.p_level / s;
+            let rate_gain = self.p_cross / s;
+            self.level += level_gain * innovation;
+            self.rate += rate_gain * innovation;
+            self.p_rate -= rate_gain * self.p_cross;
+            self.p_cross *= 1.0 - level_gain;
+            self.p_level *= 1.0 - level_gain;
+            self.level
+        }
+    }
+
+    /// Run the fixed point filter and the f64 one over `input`, checking they agree
+    fn check_kalman(q: f32, r: f32, input: impl Iterator<Item = u16>) {
+        let mut filter = KalmanFilter::new(q, r);
+        // start the reference from the settings as the filter stores them, so only the
+        // arithmetic is being compared
+        let stored = |value: f32| to_fixed(value) as f64 / ONE as f64;
+        let mut reference = KalmanReference::new(stored(q), stored(r));
+        for (index, value) in input.enumerate() {
+            let level = filter.update(value) as f64;
+            let expected = reference.update(value);
+            assert!(
+                (level - expected.clamp(0.0, u16::MAX as f64)).abs() <= 1.0,
+                "sample {index}: {level} against {expected} (q {q}, r {r})"
+            );
+            // the covariances are rounded to a 65536th, which with very little process
+            // noise is a fraction of a percent of a fast rate
+            assert!(
+                (filter.rate() as f64 - reference.rate).abs()
+                    <= 0.05 + reference.rate.abs() / 200.0,
+                "sample {index}: rate {} against {} (q {q}, r {r})",
+                filter.rate(),
+                reference.rate
+            );
+        }
+    }
+
+    /// The settings the voltage graph starts with and the ends of its sliders
+    const KALMAN_SETTINGS: [(f32, f32); 5] = [
+        (0.05, 100.0),
+        (0.001, 1.0),
+        (0.001, 10_000.0),
+        (10.0, 1.0),
+        (10.0, 10_000.0),
+    ];
+
+    #[test]
+    fn kalman_step_matches_f64() {
+        for (q, r) in KALMAN_SETTINGS {
+            let step = (0..600).map(|index| if index < 100 { 200 } else { 940 });
+            check_kalman(q, r, step);
+        }
+    }
+
+    #[test]
+    fn kalman_ramp_matches_f64() {
+        for (q, r) in KALMAN_SETTINGS {
+            let ramp = (0..2000).map(|index| (index / 2).min(1023) as u16);
+            check_kalman(q, r, ramp);
+        }
+    }
+
+    #[test]
+    fn kalman_simulated_emg_matches_f64() {
+        for (q, r) in KALMAN_SETTINGS {
+            let mut simulator = crate::EmgSimulator::new();
+            let mut rng = crate::LcgRng::new(42);
+            let emg = (0..5000).map(|_| simulator.next(rng.rand_bounded_u32(1024) as u16));
+            check_kalman(q, r, emg);
+        }
+    }
+
+    #[test]
+    fn kalman_extremes_dont_overflow() {
+        for (q, r) in [
+            (MAX_NOISE, MAX_NOISE),
+            (MAX_NOISE, 0.0),
+            (0.0, MAX_NOISE),
+            (1e9, 1e9),
+        ] {
+            let mut filter = KalmanFilter::new(q, r);
+            for index in 0..2000 {
+                filter.update(if index % 50 < 25 { 0 } else { u16::MAX });
+            }
+        }
+    }
+
+    #[test]
+    fn kalman_tracks_a_ramp_without_lag() {
+        let mut filter = KalmanFilter::new(0.05, 100.0);
+        let mut level = 0;
+        for index in 0..1000 {
+            level = filter.update(index);
+        }
+        assert!(level.abs_diff(999) <= 2, "{level}");
+        assert!((filter.rate() - 1.0).abs() < 0.01, "{}", filter.rate());
+    }
+
+    #[test]
+    fn kalman_retunes_without_a_jump() {
+        let mut filter = KalmanFilter::new(0.05, 100.0);
+        for _ in 0..500 {
+            filter.update(600);
+        }
+        filter.set_noise(1.0, 10.0);
+        assert_eq!(filter.update(600), 600);
+
+        // once its covariance settles it follows a step the same as a fresh filter with them
+        let mut fresh = KalmanFilter::new(1.0, 10.0);
+        for _ in 0..500 {
+            filter.update(600);
+            fresh.update(600);
+        }
+        for _ in 0..20 {
+            assert!(filter.update(900).abs_diff(fresh.update(900)) <= 1);
+        }
+    }
+
+    #[test]
+    fn slope_is_the_change_in_the_smoothed_value() {
+        let mut ema = ExponentialMovingAverage::new(0.15);
+        for _ in 0..200 {
+            ema.update(0);
+        }
+        assert_eq!(ema.slope(), 0.0);
+
+        let mut last = ema.ema;
+        for _ in 0..100 {
+            ema.update(1000);
+            assert!((ema.slope() - (ema.ema - last)).abs() < 1e-3);
+            last = ema.ema;
+        }
+    }
+
+    #[test]
+    fn step_up_settles_with_a_decaying_slope() {
+        let alpha = 0.15;
+        let mut ema = ExponentialMovingAverage::new(alpha);
+        let response = step_response(&mut ema, 0, 1000);
+
+        // the change is held back by how far the last input was from the average,
+        // so the first sample of a step doesn't move it
+        assert_eq!(response[0], (0, 0.0));
+        let (_, peak) = response[1];
+        assert!((peak - alpha * 1000.0).abs() < 1e-3, "peak slope {peak}");
+
+        for pair in response[1..].windows(2) {
+            let ((low, slope), (high, next_slope)) = (pair[0], pair[1]);
+            assert!(high >= low);
+            assert!(next_slope <= slope && next_slope >= 0.0);
+        }
+
+        // within one count after 1 - 0.85^n of the way there, about 45 samples
+        let settled = response
+            .iter()
+            .position(|&(value, _)| value >= 999)
+            .unwrap();
+        assert!(
+            (40..=50).contains(&settled),
+            "settled after {settled} samples"
+        );
+        assert!(response[99].1 < 0.01);
+    }
+
+    #[test]
+    fn step_down_has_a_negative_slope() {
+        let mut ema = ExponentialMovingAverage::new(0.15);
+        let response = step_response(&mut ema, 1000, 200);
+        let (_, peak) = response[1];
+        assert!((peak + 0.15 * 800.0).abs() < 1e-2, "peak slope {peak}");
+        assert!(response.iter().all(|&(_, slope)| slope <= 0.0));
+        assert!(response[99].0 <= 201);
+    }
+
+    #[test]
+    fn slope_alpha_smooths_the_slope() {
+        let mut raw = ExponentialMovingAverage::new(0.15);
+        let mut smoothed = ExponentialMovingAverage::new(0.15);
+        smoothed.slope_alpha = 0.2;
+        let raw = step_response(&mut raw, 0, 1000);
+        let smoothed = step_response(&mut smoothed, 0, 1000);
+
+        // the average itself isn't changed, only how its slope is reported
+        for (raw, smoothed) in raw.iter().zip(&smoothed) {
+            assert_eq!(raw.0, smoothed.0);
+        }
+        let peak = |response: &[(u16, f32)]| response.iter().map(|r| r.1).fold(0.0, f32::max);
+        assert!(peak(&smoothed) < peak(&raw) / 2.0);
+        assert!(smoothed[99].1 < 0.1);
+    }
+
+    #[test]
+    fn peak_hold_decay_is_degrees_a_second() {
+        // 9 degrees a second of 90 is a tenth of the range a second
+        let peak = PeakHold::new(9.0, 100.0);
+        assert!((peak.decay() - 1.023).abs() < 1e-4);
+        // twice the samples, half the fall each
+        assert!((PeakHold::new(9.0, 200.0).decay() - 0.5115).abs() < 1e-4);
+
+        let mut wrist = PeakHold::new(9.0, 100.0);
+        wrist.max_angle = 180;
+        assert!((wrist.decay() - 0.5115).abs() < 1e-4);
+
+        // oversampled by 2 bits, the same degrees are four times the counts
+        let mut oversampled = PeakHold::new(9.0, 100.0);
+        oversampled.full_scale = crate::Oversampler::new(2).full_scale();
+        assert!((oversampled.decay() - 4.092).abs() < 1e-4);
+    }
+
+    #[test]
+    fn peak_hold_follows_up_straight_away() {
+        let mut peak = PeakHold::new(9.0, 100.0);
+        assert_eq!(peak.update(100), 100);
+        assert_eq!(peak.update(900), 900);
+        assert_eq!(peak.update(1023), 1023);
+    }
+
+    #[test]
+    fn peak_hold_holds_then_decays() {
+        let rate = 100.0;
+        let mut peak = PeakHold::new(45.0, rate);
+        peak.update(1000);
+
+        // half the range a second, so a second after dropping to 0 it is about half way
+        let mut held = [0; 100];
+        for value in held.iter_mut() {
+            *value = peak.update(0);
+        }
+        assert!(held.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(held[0] >= 994, "fell to {} on the first sample", held[0]);
+        assert!(
+            (485..=490).contains(&held[99]),
+            "{} after a second",
+            held[99]
+        );
+
+        // it never falls past the data
+        for _ in 0..200 {
+            assert!(peak.update(300) >= 300);
+        }
+        assert_eq!(peak.update(300), 300);
+    }
+
+    #[test]
+    fn peak_hold_retriggers_and_releases() {
+        let mut peak = PeakHold::new(45.0, 100.0);
+        peak.update(800);
+        for _ in 0..50 {
+            peak.update(0);
+        }
+        let fallen = peak.update(0);
+        assert!(fallen < 800);
+
+        // a new contraction above the held level takes it straight back up
+        assert_eq!(peak.update(900), 900);
+        // one below it is held under the decaying peak
+        assert!(peak.update(600) > 890);
+
+        // releasing drops it to the data straight away
+        peak.release();
+        assert_eq!(peak.update(200), 200);
+        // and it holds from there again
+        assert!(peak.update(150) > 190);
+    }
+}
@@ -0,0 +1,241 @@
+/// How closure builds up as the effort goes from 0 to 100 %
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseCurve {
+    /// Closure follows the effort
+    Linear,
+    /// Slow to start, for fine control at low effort
+    Gentle,
+    /// Fast to start, so a light flex already closes the finger a lot
+    Quick,
+}
+
+impl ResponseCurve {
+    /// The closure in percent for `percent` effort, 0 and 100 always map to themselves
+    pub fn apply(self, percent: u8) -> u8 {
+        let percent = percent.min(100) as u16;
+        let closure = match self {
+            Self::Linear => percent,
+            Self::Gentle => percent * percent / 100,
+            Self::Quick => 100 - (100 - percent) * (100 - percent) / 100,
+        };
+        closure as u8
+    }
+}
+
+/// The angles one finger's servo moves between. The tendon routing is different
+/// for every finger, so the same angle doesn't close them all the same amount.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FingerRange {
+    /// Angle for an open finger
+    pub open: u8,
+    /// Angle for a fully closed finger, can be below `open` for a servo mounted the other way
+    pub closed: u8,
+    pub curve: ResponseCurve,
+}
+
+impl FingerRange {
+    pub fn new(open: u8, closed: u8) -> FingerRange {
+        FingerRange {
+            open,
+            closed,
+            curve: ResponseCurve::Linear,
+        }
+    }
+
+    /// The servo angle for `percent` closure, 100 % gives `closed` exactly
+    pub fn angle(&self, percent: u8) -> u8 {
+        let closure = self.curve.apply(percent) as i16;
+        let (open, closed) = (self.open as i16, self.closed as i16);
+        (open + (closed - open) * closure / 100) as u8
+    }
+}
+
+/// The fingers in the order synergy tables list them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Finger {
+    Thumb,
+    Index,
+    Middle,
+    Ring,
+    Pinky,
+}
+
+impl Finger {
+    pub const ALL: [Finger; 5] = [
+        Finger::Thumb,
+        Finger::Index,
+        Finger::Middle,
+        Finger::Ring,
+        Finger::Pinky,
+    ];
+}
+
+/// How every finger closes as one effort goes from 0 to 100 %, so a single EMG
+/// channel gives a grasp where the fingers close one after another instead of together.
+/// Each finger has (effort, closure) breakpoints in percent, sorted by effort,
+/// with the closure going in a straight line between them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Synergy {
+    /// Breakpoints for each finger, in the order of `Finger::ALL`
+    pub fingers: [&'static [(u8, u8)]; 5],
+}
+
+impl Synergy {
+    /// Index and middle lead, ring and pinky follow, the thumb wraps over last
+    pub const POWER_GRASP: Synergy = Synergy {
+        fingers: [
+            &[(0, 0), (50, 0), (100, 100)],
+            &[(0, 0), (60, 100), (100, 100)],
+            &[(0, 0), (60, 100), (100, 100)],
+            &[(0, 0), (20, 0), (80, 100), (100, 100)],
+            &[(0, 0), (20, 0), (80, 100), (100, 100)],
+        ],
+    };
+
+    /// Thumb and index meet, the other fingers stay out of the way
+    pub const PINCH: Synergy = Synergy {
+        fingers: [
+            &[(0, 0), (100, 100)],
+            &[(0, 0), (100, 100)],
+            &[(0, 0), (100, 0)],
+            &[(0, 0), (100, 0)],
+            &[(0, 0), (100, 0)],
+        ],
+    };
+
+    /// Every finger follows the effort together, like without a synergy
+    pub const LOCKSTEP: Synergy = Synergy {
+        fingers: [&[(0, 0), (100, 100)]; 5],
+    };
+
+    /// The closure in percent of `finger` at `effort` percent. A table that isn't
+    /// sorted by effort gives a wrong closure rather than a panic.
+    pub fn closure(&self, finger: Finger, effort: u8) -> u8 {
+        let points = self.fingers[finger as usize];
+        let effort = effort.min(100);
+        let Some(&(first_effort, first_closure)) = points.first() else {
+            return 0;
+        };
+        if effort <= first_effort {
+            return first_closure;
+        }
+
+        for pair in points.windows(2) {
+            let ((start, from), (end, to)) = (pair[0], pair[1]);
+            if effort <= end {
+                let span = (end as i16 - start as i16).max(1);
+                let along = effort as i16 - start as i16;
+                let closure = from as i16 + (to as i16 - from as i16) * along / span;
+                return closure.clamp(0, 100) as u8;
+            }
+        }
+        points.last().map_or(0, |&(_, closure)| closure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [ResponseCurve; 3] = [
+        ResponseCurve::Linear,
+        ResponseCurve::Gentle,
+        ResponseCurve::Quick,
+    ];
+
+    /// The index needs 0 to 70 and the pinky 10 to 90, and one servo is mounted the other way
+    const RANGES: [(u8, u8); 5] = [(0, 70), (10, 90), (0, 180), (90, 0), (170, 20)];
+
+    const SYNERGIES: [Synergy; 3] = [Synergy::POWER_GRASP, Synergy::PINCH, Synergy::LOCKSTEP];
+
+    #[test]
+    fn synergy_tables_are_sorted_and_monotone() {
+        for synergy in SYNERGIES {
+            for (finger, points) in Finger::ALL.into_iter().zip(synergy.fingers) {
+                assert_eq!(points.first().map(|point| point.0), Some(0), "{finger:?}");
+                assert_eq!(points.last().map(|point| point.0), Some(100), "{finger:?}");
+                for pair in points.windows(2) {
+                    assert!(pair[0].0 < pair[1].0, "{finger:?} {pair:?}");
+                    assert!(pair[0].1 <= pair[1].1, "{finger:?} {pair:?}");
+                    assert!(pair[1].1 <= 100, "{finger:?} {pair:?}");
+                }
+
+                let mut last = 0;
+                for effort in 0..=100 {
+                    let closure = synergy.closure(finger, effort);
+                    assert!(closure >= last, "{finger:?} at {effort}");
+                    last = closure;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grasps_end_at_full_closure() {
+        for synergy in [Synergy::POWER_GRASP, Synergy::LOCKSTEP] {
+            for finger in Finger::ALL {
+                assert_eq!(synergy.closure(finger, 100), 100, "{finger:?}");
+            }
+        }
+        // a pinch only closes the thumb and index
+        let pinch = Finger::ALL.map(|finger| Synergy::PINCH.closure(finger, 100));
+        assert_eq!(pinch, [100, 100, 0, 0, 0]);
+    }
+
+    #[test]
+    fn unsorted_tables_dont_panic() {
+        let synergy = Synergy {
+            fingers: [
+                &[(0, 0), (80, 100), (40, 20), (100, 100)],
+                &[(100, 100), (0, 0)],
+                &[(50, 0), (50, 100), (10, 100)],
+                &[],
+                &[(0, 100), (100, 0)],
+            ],
+        };
+        for finger in Finger::ALL {
+            for effort in 0..=255 {
+                assert!(synergy.closure(finger, effort) <= 100);
+            }
+        }
+    }
+
+    #[test]
+    fn full_effort_reaches_the_configured_max() {
+        for (open, closed) in RANGES {
+            for curve in CURVES {
+                let range = FingerRange {
+                    curve,
+                    ..FingerRange::new(open, closed)
+                };
+                assert_eq!(range.angle(100), closed, "{range:?}");
+                assert_eq!(range.angle(255), closed, "{range:?}");
+                assert_eq!(range.angle(0), open, "{range:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn angles_stay_between_open_and_closed() {
+        for (open, closed) in RANGES {
+            for curve in CURVES {
+                let range = FingerRange {
+                    curve,
+                    ..FingerRange::new(open, closed)
+                };
+                let (low, high) = (open.min(closed), open.max(closed));
+                let mut last = open;
+                for percent in 0..=100 {
+                    let angle = range.angle(percent);
+                    assert!((low..=high).contains(&angle), "{range:?} at {percent}");
+                    // never moves back towards open as the effort goes up
+                    assert!(
+                        angle.abs_diff(open) >= last.abs_diff(open),
+                        "{range:?} at {percent}"
+                    );
+                    last = angle;
+                }
+            }
+        }
+    }
+}
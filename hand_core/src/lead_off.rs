@@ -0,0 +1,195 @@
+/// What an electrode coming off looks like, in ADC counts and samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeadOffLimits {
+    /// At or below this the amplifier is stuck against the bottom rail
+    pub rail_low: u16,
+    /// At or above this the amplifier is stuck against the top rail
+    pub rail_high: u16,
+    /// A block that moves less than this from its lowest to its highest sample is flat
+    pub min_spread: u16,
+    /// Samples in each block the signal is checked over
+    pub block: u16,
+    /// Samples of railed or flat signal before the electrode counts as off
+    pub off_after: u32,
+    /// Samples of normal signal before it counts as back on
+    pub on_after: u32,
+}
+
+impl Default for LeadOffLimits {
+    /// Half a second to go off and a second to come back, at 1 kHz
+    fn default() -> Self {
+        Self {
+            rail_low: 3,
+            rail_high: 1020,
+            min_spread: 2,
+            block: 50,
+            off_after: 500,
+            on_after: 1000,
+        }
+    }
+}
+
+/// Notices when an electrode lead has come off. The amplifier then sits against a rail,
+/// or floats with much less noise than even a relaxed muscle has.
+pub struct LeadOffDetector {
+    pub limits: LeadOffLimits,
+    off: bool,
+    /// The lowest and highest sample in the block so far
+    low: u16,
+    high: u16,
+    in_block: u16,
+    /// How long the signal has looked dead, or alive
+    dead_for: u32,
+    alive_for: u32,
+}
+
+impl LeadOffDetector {
+    pub fn new(limits: LeadOffLimits) -> Self {
+        Self {
+            limits,
+            off: false,
+            low: u16::MAX,
+            high: 0,
+            in_block: 0,
+            dead_for: 0,
+            alive_for: 0,
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.off
+    }
+
+    /// Check the next sample, returning if the electrode is off
+    pub fn update(&mut self, value: u16) -> bool {
+        let limits = &self.limits;
+        self.low = self.low.min(value);
+        self.high = self.high.max(value);
+        self.in_block += 1;
+        if self.in_block < limits.block.max(1) {
+            return self.off;
+        }
+
+        let block = self.in_block as u32;
+        // the whole block against the same rail, swinging between them is a hard contraction
+        let railed = self.high <= limits.rail_low || self.low >= limits.rail_high;
+        let dead = railed || self.high - self.low < limits.min_spread;
+        if dead {
+            self.dead_for = self.dead_for.saturating_add(block);
+            self.alive_for = 0;
+        } else {
+            self.alive_for = self.alive_for.saturating_add(block);
+            self.dead_for = 0;
+        }
+        if self.dead_for >= limits.off_after {
+            self.off = true;
+        } else if self.alive_for >= limits.on_after {
+            self.off = false;
+        }
+
+        self.low = u16::MAX;
+        self.high = 0;
+        self.in_block = 0;
+        self.off
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A relaxed muscle, wandering over a few dozen counts
+    fn resting(i: usize) -> u16 {
+        200 + (i * 37 % 50) as u16
+    }
+
+    /// Feed `count` samples from `signal` and give how many went in before the
+    /// detector's answer changed, or `None` if it didn't
+    fn samples_until_change(
+        detector: &mut LeadOffDetector,
+        signal: impl Fn(usize) -> u16,
+        count: usize,
+    ) -> Option<usize> {
+        let before = detector.is_off();
+        (0..count)
+            .find(|&i| detector.update(signal(i)) != before)
+            .map(|i| i + 1)
+    }
+
+    #[test]
+    fn railed_either_way_goes_off_after_half_a_second() {
+        for rail in [0, 2, 1021, 1023] {
+            let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+            assert_eq!(
+                samples_until_change(&mut detector, |_| rail, 2000),
+                Some(500),
+                "rail {rail}"
+            );
+            assert!(detector.is_off());
+        }
+    }
+
+    #[test]
+    fn flat_signal_goes_off_but_just_enough_noise_doesnt() {
+        let limits = LeadOffLimits::default();
+        let mut detector = LeadOffDetector::new(limits);
+        // a floating input, moving by less than min_spread
+        let flat = |i: usize| 512 + (i % 2) as u16;
+        assert_eq!(samples_until_change(&mut detector, flat, 2000), Some(500));
+
+        let mut detector = LeadOffDetector::new(limits);
+        let quiet = |i: usize| 512 + (i % 2) as u16 * limits.min_spread;
+        assert_eq!(samples_until_change(&mut detector, quiet, 10_000), None);
+    }
+
+    #[test]
+    fn resting_and_clipping_muscle_stays_on() {
+        let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+        assert_eq!(samples_until_change(&mut detector, resting, 10_000), None);
+        // a hard contraction hitting both rails isn't sat against either
+        let clipping = |i: usize| if i.is_multiple_of(3) { 0 } else { 1023 };
+        assert_eq!(samples_until_change(&mut detector, clipping, 10_000), None);
+    }
+
+    #[test]
+    fn short_dead_spells_dont_count() {
+        let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+        for _ in 0..10 {
+            samples_until_change(&mut detector, |_| 1023, 450);
+            samples_until_change(&mut detector, resting, 50);
+        }
+        assert!(!detector.is_off());
+    }
+
+    #[test]
+    fn comes_back_on_after_a_second_of_signal() {
+        let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+        samples_until_change(&mut detector, |_| 1023, 500);
+        assert!(detector.is_off());
+
+        // a blip of signal shorter than on_after isn't enough, and starts the count again
+        samples_until_change(&mut detector, resting, 900);
+        samples_until_change(&mut detector, |_| 1023, 50);
+        assert!(detector.is_off());
+
+        assert_eq!(
+            samples_until_change(&mut detector, resting, 5000),
+            Some(1000)
+        );
+        assert!(!detector.is_off());
+    }
+
+    #[test]
+    fn checks_a_sample_at_a_time_without_a_block() {
+        let limits = LeadOffLimits {
+            block: 0,
+            min_spread: 0,
+            off_after: 5,
+            on_after: 3,
+            ..LeadOffLimits::default()
+        };
+        let mut detector = LeadOffDetector::new(limits);
+        assert_eq!(samples_until_change(&mut detector, |_| 0, 100), Some(5));
+        assert_eq!(samples_until_change(&mut detector, |_| 500, 100), Some(3));
+    }
+}
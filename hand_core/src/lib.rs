@@ -0,0 +1,21 @@
+//! Code shared between the firmware and the voltage graph, so both agree on
+//! how the EMG signal is read
+#![no_std]
+
+mod filter;
+mod finger;
+mod lead_off;
+mod oversample;
+mod pwm;
+mod sim;
+mod state;
+mod thermal;
+
+pub use filter::{ExponentialMovingAverage, KalmanFilter, PeakHold};
+pub use finger::{Finger, FingerRange, ResponseCurve, Synergy};
+pub use lead_off::{LeadOffDetector, LeadOffLimits};
+pub use oversample::Oversampler;
+pub use pwm::PwmTimebase;
+pub use sim::{EmgSimulator, LcgRng, SimProfile};
+pub use state::{Classifier, EmgState, Thresholds};
+pub use thermal::{ThermalLimits, ThermalModel};
@@ -0,0 +1,151 @@
+/// The largest value a single 10-bit ADC conversion gives
+const ADC_FULL_SCALE: u16 = 1023;
+
+/// Adds up several ADC conversions into one sample with more bits.
+/// Every extra bit takes four times the conversions, and only helps
+/// when the signal has enough noise on it to wander between counts.
+pub struct Oversampler {
+    /// Bits added to the 10 from the ADC, from 0 to `MAX_EXTRA_BITS`
+    extra_bits: u8,
+    sum: u32,
+    count: u16,
+}
+
+impl Oversampler {
+    /// 3 extra bits takes 64 conversions a sample, for 13 bit samples
+    pub const MAX_EXTRA_BITS: u8 = 3;
+
+    pub fn new(extra_bits: u8) -> Oversampler {
+        Oversampler {
+            extra_bits: extra_bits.min(Self::MAX_EXTRA_BITS),
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    /// How many conversions go into each sample, the sampling has to leave time for all of them
+    pub fn conversions(&self) -> u16 {
+        1 << (2 * self.extra_bits)
+    }
+
+    /// The largest sample it gives, what 1023 is for a single conversion
+    pub fn full_scale(&self) -> u16 {
+        ADC_FULL_SCALE << self.extra_bits
+    }
+
+    /// Add one conversion, giving a sample once there are enough
+    pub fn push(&mut self, conversion: u16) -> Option<u16> {
+        self.sum += conversion.min(ADC_FULL_SCALE) as u32;
+        self.count += 1;
+        if self.count < self.conversions() {
+            return None;
+        }
+
+        // the sum of 4^n conversions has 2n more bits, n of them are noise.
+        // Adding half of what is shifted off rounds to the nearest instead of down.
+        let half = (1 << self.extra_bits) >> 1;
+        let sample = ((self.sum + half) >> self.extra_bits) as u16;
+        self.sum = 0;
+        self.count = 0;
+        Some(sample)
+    }
+
+    /// Scale a sample back down to 10-bit ADC counts, for thresholds set in counts
+    pub fn to_counts(&self, sample: u16) -> u16 {
+        sample >> self.extra_bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Push `conversions` and give the first sample that comes out
+    fn first_sample(oversampler: &mut Oversampler, conversions: &[u16]) -> Option<u16> {
+        conversions
+            .iter()
+            .find_map(|&conversion| oversampler.push(conversion))
+    }
+
+    #[test]
+    fn one_sample_every_four_to_the_extra_bits() {
+        for extra_bits in 0..=Oversampler::MAX_EXTRA_BITS {
+            let mut oversampler = Oversampler::new(extra_bits);
+            let conversions = oversampler.conversions();
+            assert_eq!(conversions, 4u16.pow(extra_bits as u32));
+
+            for _ in 0..3 {
+                for _ in 1..conversions {
+                    assert_eq!(oversampler.push(512), None);
+                }
+                assert_eq!(oversampler.push(512), Some(512 << extra_bits));
+            }
+        }
+    }
+
+    #[test]
+    fn no_extra_bits_passes_conversions_through() {
+        let mut oversampler = Oversampler::new(0);
+        for conversion in [0, 1, 511, 1023] {
+            assert_eq!(oversampler.push(conversion), Some(conversion));
+        }
+        assert_eq!(oversampler.full_scale(), 1023);
+    }
+
+    #[test]
+    fn extra_bits_are_capped() {
+        let oversampler = Oversampler::new(10);
+        assert_eq!(oversampler.conversions(), 64);
+        assert_eq!(oversampler.full_scale(), 8184);
+    }
+
+    #[test]
+    fn rounds_to_the_nearest() {
+        let mut oversampler = Oversampler::new(1);
+        // a mean of 100.25 is 200.5 at 11 bits, halfway rounds up
+        assert_eq!(
+            first_sample(&mut oversampler, &[100, 100, 100, 101]),
+            Some(201)
+        );
+
+        let mut oversampler = Oversampler::new(2);
+        let mut conversions = [100; 16];
+        // 100.0625 is 400.25 at 12 bits, which rounds down
+        conversions[0] = 101;
+        assert_eq!(first_sample(&mut oversampler, &conversions), Some(400));
+        // and 100.1875 is 400.75, which rounds up
+        conversions[1] = 101;
+        conversions[2] = 101;
+        assert_eq!(first_sample(&mut oversampler, &conversions), Some(401));
+    }
+
+    #[test]
+    fn dither_between_counts_shows_up_in_the_extra_bits() {
+        let mut oversampler = Oversampler::new(3);
+        // a quarter of the way from 300 to 301
+        let conversions: [u16; 64] = core::array::from_fn(|i| if i % 4 == 0 { 301 } else { 300 });
+        let sample = first_sample(&mut oversampler, &conversions).unwrap();
+        assert_eq!(sample, 300 * 8 + 2);
+        assert_eq!(oversampler.to_counts(sample), 300);
+    }
+
+    #[test]
+    fn full_scale_is_the_largest_sample() {
+        for extra_bits in 0..=Oversampler::MAX_EXTRA_BITS {
+            let mut oversampler = Oversampler::new(extra_bits);
+            let full_scale = oversampler.full_scale();
+            assert_eq!(
+                first_sample(&mut oversampler, &[1023; 64]),
+                Some(full_scale)
+            );
+            assert_eq!(oversampler.to_counts(full_scale), 1023);
+
+            // conversions past what the ADC can give are clamped
+            let mut oversampler = Oversampler::new(extra_bits);
+            assert_eq!(
+                first_sample(&mut oversampler, &[u16::MAX; 64]),
+                Some(full_scale)
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+/// How fast a PWM timer counts and where it wraps, so servo pulses can be
+/// given in microseconds instead of duty counts for one prescaler
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PwmTimebase {
+    /// The CPU clock, 16 MHz on the Nano
+    pub clock_hz: u32,
+    /// What the timer divides the clock by
+    pub prescaler: u16,
+    /// The count the timer wraps after, 255 for an 8-bit timer
+    pub top: u16,
+}
+
+impl PwmTimebase {
+    pub const fn new(clock_hz: u32, prescaler: u16, top: u16) -> PwmTimebase {
+        PwmTimebase {
+            clock_hz,
+            prescaler,
+            top,
+        }
+    }
+
+    /// Nanoseconds per timer count, 64000 for 16 MHz with a 1024 prescaler
+    pub const fn tick_ns(&self) -> u32 {
+        (self.prescaler as u64 * 1_000_000_000 / self.clock_hz as u64) as u32
+    }
+
+    /// Microseconds from one pulse to the next
+    pub const fn period_us(&self) -> u32 {
+        ((self.top as u64 + 1) * self.prescaler as u64 * 1_000_000 / self.clock_hz as u64) as u32
+    }
+
+    /// The duty count closest to a pulse `pulse_us` long.
+    /// `None` if it rounds to no pulse or doesn't fit in the period.
+    pub const fn duty_for_us(&self, pulse_us: u32) -> Option<u16> {
+        let scaled = pulse_us as u64 * self.clock_hz as u64;
+        let per_tick = self.prescaler as u64 * 1_000_000;
+        let ticks = (scaled + per_tick / 2) / per_tick;
+        // a duty of `top` is high for the whole period, so there is no pulse
+        if ticks == 0 || ticks > self.top as u64 {
+            None
+        } else {
+            Some((ticks - 1) as u16)
+        }
+    }
+
+    /// How long a pulse `duty` counts gives, in microseconds.
+    /// In fast PWM the output is high from 0 up to and including `duty`,
+    /// so the pulse is one tick longer than the duty.
+    pub const fn pulse_us(&self, duty: u16) -> u32 {
+        ((duty as u64 + 1) * self.prescaler as u64 * 1_000_000 / self.clock_hz as u64) as u32
+    }
+
+    /// If pulses from `min_us` to `max_us` can be made, and don't all round to the same duty
+    pub const fn can_represent(&self, min_us: u32, max_us: u32) -> bool {
+        match (self.duty_for_us(min_us), self.duty_for_us(max_us)) {
+            (Some(min), Some(max)) => min < max,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Timer2 on the Nano, with the 1024 prescaler
+    const NANO_TIMER2: PwmTimebase = PwmTimebase::new(16_000_000, 1024, 255);
+    /// The same timer on an 8 MHz board, like a 3.3 V Pro Mini
+    const SLOW_TIMER2: PwmTimebase = PwmTimebase::new(8_000_000, 1024, 255);
+
+    #[test]
+    fn timer2_at_16_mhz() {
+        assert_eq!(NANO_TIMER2.tick_ns(), 64_000);
+        assert_eq!(NANO_TIMER2.period_us(), 16_384);
+        // the duties the servo used to be driven with directly, each high for one more tick
+        assert_eq!(NANO_TIMER2.duty_for_us(1536), Some(23));
+        assert_eq!(NANO_TIMER2.duty_for_us(2048), Some(31));
+        assert_eq!(NANO_TIMER2.pulse_us(23), 1536);
+        assert_eq!(NANO_TIMER2.pulse_us(31), 2048);
+        // the shortest pulse is one tick
+        assert_eq!(NANO_TIMER2.pulse_us(0), 64);
+        assert_eq!(NANO_TIMER2.duty_for_us(64), Some(0));
+        // rounds to the nearest count
+        assert_eq!(NANO_TIMER2.duty_for_us(1567), Some(23));
+        assert_eq!(NANO_TIMER2.duty_for_us(1569), Some(24));
+        assert!(NANO_TIMER2.can_represent(1536, 2048));
+        assert!(NANO_TIMER2.can_represent(544, 2400));
+    }
+
+    #[test]
+    fn timer2_at_8_mhz() {
+        assert_eq!(SLOW_TIMER2.tick_ns(), 128_000);
+        assert_eq!(SLOW_TIMER2.period_us(), 32_768);
+        assert_eq!(SLOW_TIMER2.duty_for_us(1536), Some(11));
+        assert_eq!(SLOW_TIMER2.duty_for_us(2048), Some(15));
+        assert_eq!(SLOW_TIMER2.pulse_us(11), 1536);
+        assert!(SLOW_TIMER2.can_represent(1536, 2048));
+        // half the clock means half the counts, so the same duty gives twice the pulse
+        assert_eq!(SLOW_TIMER2.pulse_us(23), 2 * NANO_TIMER2.pulse_us(23));
+    }
+
+    #[test]
+    fn timer1_without_much_prescaling() {
+        let fast = PwmTimebase::new(16_000_000, 8, 39_999);
+        assert_eq!(fast.tick_ns(), 500);
+        assert_eq!(fast.period_us(), 20_000);
+        assert_eq!(fast.duty_for_us(1500), Some(2999));
+
+        let slow = PwmTimebase::new(8_000_000, 8, 19_999);
+        assert_eq!(slow.tick_ns(), 1000);
+        assert_eq!(slow.period_us(), 20_000);
+        assert_eq!(slow.duty_for_us(1500), Some(1499));
+        assert!(slow.can_represent(544, 2400));
+    }
+
+    #[test]
+    fn pulses_that_cant_be_made() {
+        for timebase in [NANO_TIMER2, SLOW_TIMER2] {
+            assert_eq!(timebase.duty_for_us(0), None);
+            // as long as the period, which is no pulse at all
+            assert_eq!(timebase.duty_for_us(timebase.period_us()), None);
+            assert_eq!(timebase.pulse_us(timebase.top), timebase.period_us());
+        }
+        // too short to round up to one count
+        assert_eq!(SLOW_TIMER2.duty_for_us(63), None);
+        // the whole range rounds to the same count
+        assert!(!SLOW_TIMER2.can_represent(1500, 1520));
+        // an 8 bit timer counting in half microseconds wraps long before a servo pulse ends
+        let too_fast = PwmTimebase::new(16_000_000, 8, 255);
+        assert!(!too_fast.can_represent(1000, 2000));
+    }
+
+    #[test]
+    fn every_duty_round_trips() {
+        for timebase in [NANO_TIMER2, SLOW_TIMER2] {
+            for duty in 0..timebase.top {
+                assert_eq!(timebase.duty_for_us(timebase.pulse_us(duty)), Some(duty));
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::EmgState;
+
+/// How the simulator picks which state to be in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimProfile {
+    /// Change state every 1000 samples, mostly relaxed
+    Random,
+    /// Stay in one state
+    Hold(EmgState),
+    /// An electrode has come off, so the signal sits against the top rail
+    LeadOff,
+}
+
+/// This is a simulator for when we don't have an EMG to test with, it uses random walks to get a seemingly resable graph for and EMG
+pub struct EmgSimulator {
+    step_count: u32,
+    state: EmgState,
+    pub profile: SimProfile,
+    phase: u16,
+    spike_remaining: u8, // Counts how many steps left in spike
+}
+
+impl Default for EmgSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmgSimulator {
+    pub fn new() -> Self {
+        Self {
+            step_count: 0,
+            state: EmgState::Relaxed,
+            profile: SimProfile::Random,
+            phase: 0,
+            spike_remaining: 0,
+        }
+    }
+
+    pub fn next(&mut self, noise: u16) -> u16 {
+        self.step_count = self.step_count.wrapping_add(1);
+        self.phase = self.phase.wrapping_add(17);
+
+        if self.profile == SimProfile::LeadOff {
+            return 1022 + noise % 2;
+        }
+
+        // Change state every 1000 samples based on noise
+        if let SimProfile::Hold(state) = self.profile {
+            self.state = state;
+        } else if self.step_count.is_multiple_of(1000) {
+            let r = noise % 100;
+            self.state = if r < 50 {
+                EmgState::Relaxed
+            } else if r < 80 {
+                EmgState::Intermediate
+            } else {
+                EmgState::Clenched
+            };
+        }
+
+        // Trigger spike if none active and noise meets condition
+        if self.spike_remaining == 0 && noise.is_multiple_of(200) {
+            // spike length pseudo-random from 1 to 5 inclusive
+            self.spike_remaining = (noise % 5 + 1) as u8;
+        }
+
+        // If in spike, output max value and decrement spike timer
+        if self.spike_remaining > 0 {
+            self.spike_remaining -= 1;
+            return 1023;
+        }
+
+        // Normal signal calculation
+        let (baseline, amplitude): (u16, u16) = match self.state {
+            EmgState::Relaxed => (200, 50),
+            EmgState::Intermediate => (620, 30),
+            EmgState::Clenched => (940, 10),
+        };
+
+        let jitter = ((noise % (2 * amplitude)) as i16) - (amplitude as i16);
+
+        let artifact = if (self.phase % 256) < 128 { 3 } else { -3 };
+
+        let mut signal = baseline as i16 + jitter + artifact;
+
+        signal = signal.clamp(0, 1023);
+
+        signal as u16
+    }
+}
+
+/// A very bad random number generator that works with no_std
+pub struct LcgRng {
+    state: u32,
+}
+
+impl LcgRng {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        // Use a mix of wrapping mul, add, xor and shifts to scramble bits
+        self.state = self.state.wrapping_mul(0x6C8E9CF5);
+        self.state ^= self.state >> 13;
+        self.state = self.state.wrapping_add(0xB5297A4D);
+        self.state ^= self.state << 17;
+        self.state = self.state.wrapping_sub(0xD6E8FEB8);
+        self.state ^= self.state >> 5;
+        self.state
+    }
+
+    pub fn rand_bounded_u32(&mut self, bound: u32) -> u32 {
+        self.next_u32() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LeadOffDetector, LeadOffLimits};
+
+    /// Run the simulator with noise the way the firmware makes it
+    fn samples<'a>(
+        simulator: &'a mut EmgSimulator,
+        rng: &'a mut LcgRng,
+        count: usize,
+    ) -> impl Iterator<Item = u16> + 'a {
+        (0..count).map(move |_| simulator.next(rng.rand_bounded_u32(1024) as u16))
+    }
+
+    /// The state a sample that isn't a spike came from
+    fn state_of(value: u16) -> Option<EmgState> {
+        match value {
+            147..=253 => Some(EmgState::Relaxed),
+            587..=653 => Some(EmgState::Intermediate),
+            927..=953 => Some(EmgState::Clenched),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn hold_stays_in_its_state() {
+        for state in [
+            EmgState::Relaxed,
+            EmgState::Intermediate,
+            EmgState::Clenched,
+        ] {
+            let mut simulator = EmgSimulator::new();
+            simulator.profile = SimProfile::Hold(state);
+            let mut rng = LcgRng::new(42);
+            let mut spikes = 0;
+            for value in samples(&mut simulator, &mut rng, 5000) {
+                if value == 1023 {
+                    spikes += 1;
+                } else {
+                    assert_eq!(state_of(value), Some(state), "{value}");
+                }
+            }
+            assert!(spikes < 100, "{spikes} spike samples");
+        }
+    }
+
+    #[test]
+    fn lead_off_sits_on_the_top_rail() {
+        let mut simulator = EmgSimulator::new();
+        simulator.profile = SimProfile::LeadOff;
+        let mut rng = LcgRng::new(42);
+        assert!(samples(&mut simulator, &mut rng, 5000).all(|value| value >= 1022));
+    }
+
+    #[test]
+    fn random_changes_state_every_thousand_samples() {
+        let mut simulator = EmgSimulator::new();
+        let mut rng = LcgRng::new(42);
+        let mut seen = [false; 3];
+        let mut block_state = None;
+        for (index, value) in samples(&mut simulator, &mut rng, 50_000).enumerate() {
+            // the state is picked on the thousandth sample, counting from one
+            if (index + 1) % 1000 == 0 {
+                block_state = None;
+            }
+            let Some(state) = state_of(value) else {
+                continue;
+            };
+            if index < 999 {
+                assert_eq!(state, EmgState::Relaxed);
+            }
+            assert_eq!(*block_state.get_or_insert(state), state, "sample {index}");
+            seen[state.code() as usize] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+    }
+
+    /// A simulator script, each step running a profile for some samples and saying
+    /// whether the electrode should count as off by the end of it
+    const LEAD_OFF_SCRIPT: [(SimProfile, usize, bool); 6] = [
+        (SimProfile::Hold(EmgState::Relaxed), 2000, false),
+        // half a second to count as off
+        (SimProfile::LeadOff, 400, false),
+        (SimProfile::LeadOff, 200, true),
+        // a second to count as back on, whatever the muscle is doing
+        (SimProfile::Hold(EmgState::Clenched), 900, true),
+        (SimProfile::Hold(EmgState::Clenched), 200, false),
+        (SimProfile::Random, 5000, false),
+    ];
+
+    #[test]
+    fn lead_off_script() {
+        let mut simulator = EmgSimulator::new();
+        let mut rng = LcgRng::new(7);
+        let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+        for (step, (profile, count, off)) in LEAD_OFF_SCRIPT.into_iter().enumerate() {
+            simulator.profile = profile;
+            for value in samples(&mut simulator, &mut rng, count) {
+                detector.update(value);
+            }
+            assert_eq!(detector.is_off(), off, "step {step}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+/// How hard the muscle is being flexed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmgState {
+    Relaxed,
+    Intermediate,
+    Clenched,
+}
+
+impl EmgState {
+    /// The number sent for the state in telemetry, 0 for Relaxed up to 2 for Clenched
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// The state sent as `code` in telemetry
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Relaxed),
+            1 => Some(Self::Intermediate),
+            2 => Some(Self::Clenched),
+            _ => None,
+        }
+    }
+}
+
+/// Where the signal has to cross to change state, in ADC counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Thresholds {
+    /// Between Relaxed and Intermediate
+    pub intermediate: u16,
+    /// Between Intermediate and Clenched
+    pub clenched: u16,
+    /// How far past a threshold the signal has to go to cross it,
+    /// so noise sitting on a threshold doesn't flip the state back and forth
+    pub hysteresis: u16,
+}
+
+impl Thresholds {
+    /// The defaults as a constant, for the firmware's board config
+    pub const DEFAULT: Thresholds = Thresholds {
+        intermediate: 400,
+        clenched: 800,
+        hysteresis: 30,
+    };
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Turns a smoothed signal into an `EmgState`, remembering the last state for the hysteresis
+pub struct Classifier {
+    pub thresholds: Thresholds,
+    state: EmgState,
+}
+
+impl Classifier {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            state: EmgState::Relaxed,
+        }
+    }
+
+    pub fn state(&self) -> EmgState {
+        self.state
+    }
+
+    /// Classify the next value
+    pub fn update(&mut self, value: u16) -> EmgState {
+        let thresholds = &self.thresholds;
+        let rising = |threshold: u16| value >= threshold.saturating_add(thresholds.hysteresis);
+        let falling = |threshold: u16| value < threshold.saturating_sub(thresholds.hysteresis);
+
+        self.state = match self.state {
+            EmgState::Relaxed if rising(thresholds.clenched) => EmgState::Clenched,
+            EmgState::Relaxed if rising(thresholds.intermediate) => EmgState::Intermediate,
+            EmgState::Intermediate if rising(thresholds.clenched) => EmgState::Clenched,
+            EmgState::Intermediate if falling(thresholds.intermediate) => EmgState::Relaxed,
+            EmgState::Clenched if falling(thresholds.intermediate) => EmgState::Relaxed,
+            EmgState::Clenched if falling(thresholds.clenched) => EmgState::Intermediate,
+            state => state,
+        };
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a fresh classifier with the default thresholds over `values`, giving the last state
+    fn classify(values: &[u16]) -> EmgState {
+        let mut classifier = Classifier::new(Thresholds::default());
+        for &value in values {
+            classifier.update(value);
+        }
+        classifier.state()
+    }
+
+    #[test]
+    fn defaults() {
+        let thresholds = Thresholds::default();
+        assert_eq!(thresholds, Thresholds::DEFAULT);
+        assert_eq!(thresholds.intermediate, 400);
+        assert_eq!(thresholds.clenched, 800);
+        assert_eq!(thresholds.hysteresis, 30);
+        assert_eq!(Classifier::new(thresholds).state(), EmgState::Relaxed);
+    }
+
+    #[test]
+    fn rising_has_to_clear_the_band() {
+        // inside the band above a threshold isn't enough
+        assert_eq!(classify(&[429]), EmgState::Relaxed);
+        assert_eq!(classify(&[430]), EmgState::Intermediate);
+        assert_eq!(classify(&[430, 829]), EmgState::Intermediate);
+        assert_eq!(classify(&[430, 830]), EmgState::Clenched);
+        // a jump straight past both goes straight to Clenched
+        assert_eq!(classify(&[830]), EmgState::Clenched);
+    }
+
+    #[test]
+    fn falling_has_to_clear_the_band() {
+        // inside the band below a threshold keeps the state
+        assert_eq!(classify(&[830, 770]), EmgState::Clenched);
+        assert_eq!(classify(&[830, 769]), EmgState::Intermediate);
+        assert_eq!(classify(&[430, 370]), EmgState::Intermediate);
+        assert_eq!(classify(&[430, 369]), EmgState::Relaxed);
+        // a drop straight past both goes straight to Relaxed
+        assert_eq!(classify(&[830, 369]), EmgState::Relaxed);
+    }
+
+    #[test]
+    fn noise_inside_the_band_doesnt_flip_the_state() {
+        let mut classifier = Classifier::new(Thresholds::default());
+        classifier.update(500);
+        for value in [371, 429, 380, 420, 400] {
+            assert_eq!(classifier.update(value), EmgState::Intermediate);
+        }
+        classifier.update(300);
+        for value in [371, 429, 380, 420, 400] {
+            assert_eq!(classifier.update(value), EmgState::Relaxed);
+        }
+        classifier.update(900);
+        for value in [771, 829, 780, 820, 800] {
+            assert_eq!(classifier.update(value), EmgState::Clenched);
+        }
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        for state in [
+            EmgState::Relaxed,
+            EmgState::Intermediate,
+            EmgState::Clenched,
+        ] {
+            assert_eq!(EmgState::from_code(state.code()), Some(state));
+        }
+        assert_eq!(EmgState::from_code(3), None);
+    }
+}
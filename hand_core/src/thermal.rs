@@ -0,0 +1,180 @@
+/// Settings for `ThermalModel`. The heat has no unit, holding full load
+/// forever would warm it up to `heating / cooling`.
+///
+/// While backed off the servo still heats, towards `100 - back_off` percent of
+/// that. It only cools enough to let go of the back-off during a held grip if
+/// `recover` is below that and `limit` is above it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThermalLimits {
+    /// Heat added each sample at full load
+    pub heating: f32,
+    /// Fraction of the heat lost each sample
+    pub cooling: f32,
+    /// Heat where the load starts being backed off
+    pub limit: f32,
+    /// Heat it has to cool to before the full load is allowed again
+    pub recover: f32,
+    /// Percent of the load taken off while it is too hot. It is kept small so a
+    /// held object isn't dropped, the grip only loosens a little while it cools.
+    pub back_off: u8,
+}
+
+impl ThermalLimits {
+    /// Tuned for about 100 samples a second, it takes over a minute
+    /// of holding full load to get too hot. Held full load would settle at 1
+    /// and the backed off load at 0.9, so `limit` and `recover` sit between them.
+    pub const DEFAULT: ThermalLimits = ThermalLimits {
+        heating: 0.0004,
+        cooling: 0.0004,
+        limit: 0.95,
+        recover: 0.92,
+        back_off: 10,
+    };
+}
+
+impl Default for ThermalLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A rough guess at how warm a servo is from how hard it has been driven,
+/// so a long heavy grip can be eased off before it cooks the servo
+pub struct ThermalModel {
+    pub limits: ThermalLimits,
+    heat: f32,
+    hot: bool,
+}
+
+impl ThermalModel {
+    pub fn new(limits: ThermalLimits) -> Self {
+        Self {
+            limits,
+            heat: 0.0,
+            hot: false,
+        }
+    }
+
+    pub fn heat(&self) -> f32 {
+        self.heat
+    }
+
+    /// If the load is being backed off, for a warning
+    pub fn is_hot(&self) -> bool {
+        self.hot
+    }
+
+    /// Add one sample of `load` percent and give back the load that is allowed.
+    /// The servo heats from the load it is allowed, not the one asked for.
+    pub fn update(&mut self, load: u8) -> u8 {
+        let load = load.min(100);
+        let limits = &self.limits;
+        let allowed = if self.hot {
+            load.saturating_sub(limits.back_off)
+        } else {
+            load
+        };
+        self.heat += limits.heating * allowed as f32 / 100.0 - limits.cooling * self.heat;
+
+        if self.heat >= limits.limit {
+            self.hot = true;
+        } else if self.heat < limits.recover {
+            self.hot = false;
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples a second the default limits are tuned for
+    const RATE: u32 = 100;
+
+    /// Samples of `load` until the model's hot flag turns `hot`, or `None` if it doesn't within `max`
+    fn samples_until(model: &mut ThermalModel, load: u8, hot: bool, max: u32) -> Option<u32> {
+        (1..=max).find(|_| {
+            model.update(load);
+            model.is_hot() == hot
+        })
+    }
+
+    #[test]
+    fn long_hold_backs_off_then_releases() {
+        let limits = ThermalLimits::default();
+        let mut model = ThermalModel::new(limits);
+
+        let engaged = samples_until(&mut model, 100, true, 10 * 60 * RATE).unwrap();
+        assert!(
+            (60 * RATE..120 * RATE).contains(&engaged),
+            "backed off after {engaged} samples"
+        );
+        assert_eq!(model.update(100), 100 - limits.back_off);
+        assert_eq!(model.update(5), 0);
+
+        // letting go only has to cool it a little past recover
+        let released = samples_until(&mut model, 0, false, 10 * 60 * RATE).unwrap();
+        assert!(model.heat() < limits.recover);
+        assert!(released < 5 * RATE, "released after {released} samples");
+        assert_eq!(model.update(100), 100);
+    }
+
+    #[test]
+    fn stays_backed_off_until_it_cools_past_recover() {
+        let limits = ThermalLimits::default();
+        let mut model = ThermalModel::new(limits);
+        samples_until(&mut model, 100, true, 10 * 60 * RATE).unwrap();
+
+        // easing off a little cools it below the limit but not below recover
+        while model.heat() >= limits.limit {
+            model.update(50);
+        }
+        assert!(model.heat() > limits.recover);
+        assert!(model.is_hot());
+        assert_eq!(model.update(80), 80 - limits.back_off);
+    }
+
+    #[test]
+    fn sustained_hold_releases_while_still_held() {
+        let limits = ThermalLimits::default();
+        let mut model = ThermalModel::new(limits);
+        samples_until(&mut model, 100, true, 10 * 60 * RATE).unwrap();
+
+        // the backed off load cools it, so the back-off lets go without the grip easing
+        let released = samples_until(&mut model, 100, false, 10 * 60 * RATE).unwrap();
+        assert!(
+            (5 * RATE..60 * RATE).contains(&released),
+            "released after {released} samples"
+        );
+        assert_eq!(model.update(100), 100);
+
+        // and it keeps cycling for as long as the grip is held, never getting much hotter
+        let mut backed_off = 0;
+        for _ in 0..10 * 60 * RATE {
+            if model.update(100) < 100 {
+                backed_off += 1;
+            }
+            assert!(model.heat() < limits.limit + limits.heating);
+        }
+        assert!(
+            (60 * RATE..9 * 60 * RATE).contains(&backed_off),
+            "backed off for {backed_off} samples"
+        );
+    }
+
+    #[test]
+    fn short_grips_never_back_off() {
+        let mut model = ThermalModel::new(ThermalLimits::default());
+        // ten second grips with ten seconds of rest, for ten minutes
+        for _ in 0..30 {
+            for _ in 0..10 * RATE {
+                assert_eq!(model.update(100), 100);
+            }
+            for _ in 0..10 * RATE {
+                model.update(0);
+            }
+        }
+        assert!(!model.is_hot());
+    }
+}
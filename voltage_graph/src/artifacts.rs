@@ -0,0 +1,266 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::Channels;
+use crate::clipping::ClipLimits;
+use crate::derived::{DerivedChannel, is_raw_emg};
+use crate::viewport::TimeViewport;
+
+/// How often the history is searched for artifacts again
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When a stretch of samples counts as a motion artifact. Signals on the arm
+/// jump around more than on the bench, so the limits need moving between the two.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArtifactLimits {
+    pub enabled: bool,
+    /// A jump between two samples this many times bigger than the usual jump is an artifact
+    pub jump_factor: f32,
+    /// Seconds marked either side of a jump, to cover the settling after it
+    pub margin: f64,
+    /// Mark clipped samples as artifacts too
+    pub include_clipping: bool,
+    /// Leave the marked spans out of the statistics and reports
+    pub exclude: bool,
+}
+
+impl Default for ArtifactLimits {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jump_factor: 20.0,
+            margin: 0.05,
+            include_clipping: true,
+            exclude: false,
+        }
+    }
+}
+
+impl ArtifactLimits {
+    /// Spans of (start, end) in `samples` that look like artifacts, in order of time
+    pub fn find(&self, samples: &[(f64, f32)], clip_limits: &ClipLimits) -> Vec<(f64, f64)> {
+        let mut jumps: Vec<f32> = samples
+            .windows(2)
+            .map(|pair| (pair[1].1 - pair[0].1).abs())
+            .collect();
+        if jumps.is_empty() {
+            return Vec::new();
+        }
+        // the median jump, so the artifacts themselves don't raise the limit much
+        let middle = jumps.len() / 2;
+        let (_, &mut usual, _) = jumps.select_nth_unstable_by(middle, f32::total_cmp);
+        // a flat signal still needs a real jump to count
+        let limit = self.jump_factor * usual.max(1.0);
+
+        let mut spans: Vec<(f64, f64)> = samples
+            .windows(2)
+            .filter(|pair| (pair[1].1 - pair[0].1).abs() > limit)
+            .map(|pair| (pair[0].0 - self.margin, pair[1].0 + self.margin))
+            .collect();
+        if self.include_clipping {
+            spans.extend(
+                clip_limits
+                    .spans(samples.iter())
+                    .into_iter()
+                    .map(|(start, end)| (start - self.margin, end + self.margin)),
+            );
+        }
+        merge(spans)
+    }
+}
+
+/// The artifacts found in the history, which is searched again every `SCAN_INTERVAL`
+#[derive(Default)]
+pub struct ArtifactScan {
+    /// Spans of (start, end) that look like motion artifacts, in order of time
+    pub spans: Vec<(f64, f64)>,
+    last_scan: Option<Instant>,
+}
+
+impl ArtifactScan {
+    /// Search the raw EMG channels for artifacts, once in a while
+    pub fn update(
+        &mut self,
+        limits: &ArtifactLimits,
+        clip_limits: &ClipLimits,
+        channels: &Channels,
+        derived: &[DerivedChannel],
+    ) {
+        if !limits.enabled {
+            self.spans.clear();
+            return;
+        }
+        if self
+            .last_scan
+            .is_some_and(|last| last.elapsed() < SCAN_INTERVAL)
+        {
+            return;
+        }
+        self.last_scan = Some(Instant::now());
+
+        let spans = channels
+            .iter()
+            .filter(|channel| is_raw_emg(channel, derived))
+            .flat_map(|channel| {
+                let samples: Vec<(f64, f32)> = channel.samples.range(..).copied().collect();
+                limits.find(&samples, clip_limits)
+            })
+            .collect();
+        self.spans = merge(spans);
+    }
+
+    /// The limits and a list of the artifacts found, clicking one centres `viewport` on it
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        limits: &mut ArtifactLimits,
+        viewport: &mut TimeViewport,
+    ) {
+        ui.heading("Artifacts");
+
+        let mut changed = ui
+            .checkbox(&mut limits.enabled, "Mark jumps and clipping")
+            .changed();
+        ui.add_enabled_ui(limits.enabled, |ui| {
+            changed |= ui
+                .add(
+                    egui::Slider::new(&mut limits.jump_factor, 2.0..=200.0)
+                        .logarithmic(true)
+                        .text("x the usual jump"),
+                )
+                .on_hover_text("Lower on the bench, higher on the arm where the signal is busier")
+                .changed();
+            changed |= ui
+                .add(
+                    egui::Slider::new(&mut limits.margin, 0.0..=1.0)
+                        .suffix(" s")
+                        .text("Margin"),
+                )
+                .changed();
+            changed |= ui
+                .checkbox(&mut limits.include_clipping, "Include clipping")
+                .changed();
+            ui.checkbox(&mut limits.exclude, "Leave out of statistics and reports");
+        });
+        if changed {
+            self.last_scan = None;
+        }
+
+        let total: f64 = self.spans.iter().map(|(start, end)| end - start).sum();
+        if !self.spans.is_empty() {
+            ui.label(format!(
+                "{} artifacts, {total:.2} s in all",
+                self.spans.len()
+            ));
+        }
+        egui::ScrollArea::vertical()
+            .id_salt("artifact list")
+            .max_height(120.0)
+            .show(ui, |ui| {
+                for &(start, end) in &self.spans {
+                    if ui
+                        .small_button(format!("{start:.2} s, {:.0} ms", (end - start) * 1000.0))
+                        .on_hover_text("Show on the plot")
+                        .clicked()
+                    {
+                        viewport.center_on((start + end) / 2.0);
+                    }
+                }
+            });
+    }
+}
+
+/// `spans` sorted by time with the ones that overlap joined together
+pub fn merge(mut spans: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = last_end.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// If `time` falls in one of `spans`, which have to be sorted and not overlap
+pub fn contains(spans: &[(f64, f64)], time: f64) -> bool {
+    let next = spans.partition_point(|&(start, _)| start <= time);
+    next > 0 && time <= spans[next - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A signal wobbling by 1 around 500, 100 samples a second for 2 s
+    fn wobble() -> Vec<(f64, f32)> {
+        (0..200)
+            .map(|i| (i as f64 / 100.0, 500.0 + (i % 2) as f32))
+            .collect()
+    }
+
+    #[test]
+    fn jump_is_marked_with_its_margin() {
+        let mut samples = wobble();
+        samples[100].1 = 900.0;
+
+        let spans = ArtifactLimits::default().find(&samples, &ClipLimits::default());
+        // the jumps up and back down are joined into one span
+        assert_eq!(spans.len(), 1);
+        let (start, end) = spans[0];
+        assert!((start - 0.94).abs() < 1e-4, "{start}");
+        assert!((end - 1.06).abs() < 1e-4, "{end}");
+    }
+
+    #[test]
+    fn usual_movement_is_not_an_artifact() {
+        let samples: Vec<(f64, f32)> = (0..200)
+            .map(|i| (i as f64 / 100.0, 500.0 + 10.0 * (i % 2) as f32))
+            .collect();
+
+        assert!(
+            ArtifactLimits::default()
+                .find(&samples, &ClipLimits::default())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn clipping_counts_when_asked() {
+        // climbs slowly into the top rail, so there is no jump
+        let samples: Vec<(f64, f32)> = (0..40)
+            .map(|i| (i as f64 / 100.0, 1000.0 + i as f32))
+            .collect();
+
+        let mut limits = ArtifactLimits::default();
+        let spans = limits.find(&samples, &ClipLimits::default());
+        assert_eq!(spans.len(), 1);
+        assert!((spans[0].0 - 0.15).abs() < 1e-4);
+
+        limits.include_clipping = false;
+        assert!(limits.find(&samples, &ClipLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn merge_joins_overlaps() {
+        let merged = merge(vec![(5.0, 6.0), (0.0, 2.0), (1.0, 3.0), (3.0, 4.0)]);
+        assert_eq!(merged, [(0.0, 4.0), (5.0, 6.0)]);
+    }
+
+    #[test]
+    fn contains_checks_the_span_around_a_time() {
+        let spans = [(0.0, 1.0), (2.0, 3.0)];
+
+        assert!(contains(&spans, 0.0));
+        assert!(contains(&spans, 1.0));
+        assert!(!contains(&spans, 1.5));
+        assert!(contains(&spans, 2.5));
+        assert!(!contains(&spans, 3.5));
+        assert!(!contains(&spans, -1.0));
+        assert!(!contains(&[], 0.0));
+    }
+}
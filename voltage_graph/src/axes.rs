@@ -0,0 +1,182 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Space added above and below the data, as a fraction of its span
+const PADDING: f32 = 0.05;
+/// The range only shrinks once the data takes up less than this fraction of it
+const SHRINK_FRACTION: f32 = 0.5;
+/// How long the data has to stay that small before the range shrinks to it,
+/// so a pause between contractions doesn't zoom in and back out
+const SHRINK_HOLD: Duration = Duration::from_secs(2);
+/// The smallest span shown, so a flat line doesn't zoom in on nothing
+const MIN_SPAN: f32 = 1e-3;
+
+/// Keeps a value axis steady while live data comes in.
+/// It moves as soon as the data goes past it, but only shrinks once the data
+/// has taken up much less of it for a while.
+#[derive(Default)]
+pub struct AutoRange {
+    shown: Option<Range<f32>>,
+    /// When the data started taking up too little of the range, while it still does
+    small_since: Option<Instant>,
+}
+
+impl AutoRange {
+    /// The range to show for data covering `data`
+    pub fn update(&mut self, data: Option<Range<f32>>) -> Option<Range<f32>> {
+        self.update_at(data, Instant::now())
+    }
+
+    /// The range to show for data covering `data` at `now`
+    fn update_at(&mut self, data: Option<Range<f32>>, now: Instant) -> Option<Range<f32>> {
+        let Some(data) = data else {
+            self.shown = None;
+            self.small_since = None;
+            return None;
+        };
+
+        let span = (data.end - data.start).max(MIN_SPAN);
+        let (inside, small) = self.shown.as_ref().map_or((false, false), |shown| {
+            (
+                shown.start <= data.start && shown.end >= data.end,
+                span < (shown.end - shown.start) * SHRINK_FRACTION,
+            )
+        });
+        if small {
+            self.small_since.get_or_insert(now);
+        } else {
+            self.small_since = None;
+        }
+        let held = self
+            .small_since
+            .is_some_and(|since| now.duration_since(since) >= SHRINK_HOLD);
+        if !inside || held {
+            self.small_since = None;
+            let middle = (data.start + data.end) / 2.0;
+            let half = span / 2.0 + span * PADDING;
+            self.shown = Some(middle - half..middle + half);
+        }
+        self.shown.clone()
+    }
+}
+
+/// A value range typed in by the user, used instead of the automatic one when enabled
+pub struct ManualRange {
+    pub enabled: bool,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for ManualRange {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min: 0.0,
+            max: 1023.0,
+        }
+    }
+}
+
+impl ManualRange {
+    /// The manual range if enabled, otherwise `auto`
+    pub fn or(&self, auto: Option<Range<f32>>) -> Option<Range<f32>> {
+        if self.enabled {
+            Some(self.min..self.max.max(self.min + MIN_SPAN))
+        } else {
+            auto
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `range` over the (seconds after `start`, data range) in `steps`,
+    /// giving the range shown after each
+    fn run(
+        range: &mut AutoRange,
+        start: Instant,
+        steps: &[(f32, Range<f32>)],
+    ) -> Vec<Option<Range<f32>>> {
+        steps
+            .iter()
+            .map(|(seconds, data)| {
+                let now = start + Duration::from_secs_f32(*seconds);
+                range.update_at(Some(data.clone()), now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_data_is_padded() {
+        let mut range = AutoRange::default();
+        let shown = range.update(Some(100.0..500.0)).unwrap();
+        assert_eq!(shown, 80.0..520.0);
+        assert_eq!(range.update(None), None);
+    }
+
+    #[test]
+    fn a_small_excursion_keeps_the_range() {
+        let mut range = AutoRange::default();
+        let start = Instant::now();
+        let shown = run(
+            &mut range,
+            start,
+            &[
+                (0.0, 100.0..500.0),
+                (0.1, 90.0..510.0),
+                (0.2, 150.0..400.0),
+                (0.3, 100.0..500.0),
+            ],
+        );
+        // still inside the padding and more than half of it, so it doesn't jitter
+        assert!(shown.iter().all(|shown| *shown == Some(80.0..520.0)));
+    }
+
+    #[test]
+    fn a_large_excursion_grows_it_straight_away() {
+        let mut range = AutoRange::default();
+        let start = Instant::now();
+        let shown = run(
+            &mut range,
+            start,
+            &[(0.0, 100.0..500.0), (0.1, 100.0..900.0)],
+        );
+        assert_eq!(shown[1], Some(60.0..940.0));
+    }
+
+    #[test]
+    fn it_shrinks_back_after_the_hold() {
+        let mut range = AutoRange::default();
+        let start = Instant::now();
+        let shown = run(
+            &mut range,
+            start,
+            &[
+                (0.0, 0.0..1000.0),
+                // a pause between contractions, the range waits
+                (0.5, 400.0..600.0),
+                (1.5, 400.0..600.0),
+                // the signal came back before the hold was up, so it starts over
+                (2.0, 0.0..1000.0),
+                (2.5, 400.0..600.0),
+                (4.0, 400.0..600.0),
+                (4.6, 400.0..600.0),
+            ],
+        );
+        assert!(shown[..6].iter().all(|shown| *shown == Some(-50.0..1050.0)));
+        assert_eq!(shown[6], Some(390.0..610.0));
+    }
+
+    #[test]
+    fn manual_range_overrides_when_enabled() {
+        let mut manual = ManualRange::default();
+        assert_eq!(manual.or(Some(1.0..2.0)), Some(1.0..2.0));
+        manual.enabled = true;
+        assert_eq!(manual.or(Some(1.0..2.0)), Some(0.0..1023.0));
+        // a max below the min still gives a range to draw
+        manual.max = -5.0;
+        assert_eq!(manual.or(None), Some(0.0..MIN_SPAN));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use eframe::egui;
+use hand_core::{EmgState, Thresholds};
+
+use crate::channel::Channels;
+use crate::classify::{self, FIRMWARE_STATE_CHANNEL};
+use crate::derived::{DerivedChannel, FilterKind};
+use crate::export::{self, Table};
+use crate::import::{self, CsvPreview};
+use crate::report::StateSummary;
+use crate::stats::Stats;
+use crate::tuning;
+
+/// The folder the summary and per-file CSVs are written to, inside the folder being processed
+const OUTPUT_FOLDER: &str = "batch";
+
+/// What is run over every file, copied from the app when the batch starts
+pub struct BatchConfig {
+    pub folder: PathBuf,
+    /// Filters added to every file that has their source, as (name, source, filter)
+    pub filters: Vec<(String, String, FilterKind)>,
+    pub thresholds: Thresholds,
+    /// The channel that is classified and summarized, the first channel is used
+    /// for files that don't have it
+    pub classify_channel: String,
+    /// Write every file out again with the filtered channels added.
+    /// How each filter did on the file's biggest step is written either way.
+    pub write_files: bool,
+}
+
+/// Messages from the thread working through the folder
+pub enum BatchEvent {
+    /// `done` of `total` files are finished and `file` is being read
+    Progress {
+        done: usize,
+        total: usize,
+        file: String,
+    },
+    Done(Result<BatchReport, String>),
+}
+
+/// How the batch went
+pub struct BatchReport {
+    /// Where the summary was written
+    pub summary: PathBuf,
+    pub processed: usize,
+    /// Files that couldn't be processed, with why
+    pub failed: Vec<(String, String)>,
+}
+
+/// One row of the summary
+struct FileSummary {
+    file: String,
+    /// The channel the numbers are for
+    channel: String,
+    duration: f64,
+    malformed_rows: usize,
+    gaps: usize,
+    stats: Stats,
+    states: StateSummary,
+    /// Places the firmware's state disagreed with the classifier here, `None` without a state channel
+    mismatches: Option<usize>,
+}
+
+/// Process every CSV file in `config.folder` on a background thread
+pub fn start_batch(config: BatchConfig, ctx: &egui::Context) -> Receiver<BatchEvent> {
+    let (sender, receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let result = run(&config, &sender, &ctx)
+            .map_err(|error| format!("Batch in {} failed: {error}", config.folder.display()));
+        let _ = sender.send(BatchEvent::Done(result));
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+fn run(
+    config: &BatchConfig,
+    sender: &Sender<BatchEvent>,
+    ctx: &egui::Context,
+) -> io::Result<BatchReport> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(&config.folder)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "csv"))
+        .collect();
+    // later parts of a split recording are loaded along with the first
+    paths.retain(|path| {
+        !CsvPreview::read(path.clone()).is_ok_and(|preview| preview.metadata.is_later_part())
+    });
+    paths.sort();
+
+    let output = config.folder.join(OUTPUT_FOLDER);
+    fs::create_dir_all(&output)?;
+    let summary_path = output.join("summary.csv");
+    let mut summary = BufWriter::new(File::create(&summary_path)?);
+    writeln!(
+        summary,
+        "file,status,channel,duration_s,malformed_rows,gaps,mean,rms,p95,\
+         contractions,mean_contraction_s,clenched_percent,state_mismatches"
+    )?;
+
+    let mut report = BatchReport {
+        summary: summary_path,
+        processed: 0,
+        failed: Vec::new(),
+    };
+    for (done, path) in paths.iter().enumerate() {
+        let file = path
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let _ = sender.send(BatchEvent::Progress {
+            done,
+            total: paths.len(),
+            file: file.clone(),
+        });
+        ctx.request_repaint();
+
+        // a bad file is noted in the summary and the rest carry on
+        match process(path, &file, config, &output) {
+            Ok(row) => {
+                write_row(&mut summary, &row)?;
+                report.processed += 1;
+            }
+            Err(error) => {
+                writeln!(summary, "{},{}", quote(&file), quote(&error))?;
+                report.failed.push((file, error));
+            }
+        }
+    }
+
+    summary.flush()?;
+    Ok(report)
+}
+
+/// Load one file, add the filters and work out its row of the summary
+fn process(
+    path: &Path,
+    file: &str,
+    config: &BatchConfig,
+    output: &Path,
+) -> Result<FileSummary, String> {
+    let preview = CsvPreview::read(path.to_path_buf())?;
+    let imported = import::load(&preview, |_| {})?;
+    if imported.channels.is_empty() {
+        return Err(String::from("No channels"));
+    }
+
+    let mut channels = Channels::new(0);
+    channels.load(imported.channels);
+    for (name, source, kind) in &config.filters {
+        // the filters see the whole file at once
+        DerivedChannel::new(name.clone(), source, *kind).update(&mut channels);
+    }
+
+    let channel = channels
+        .get(&config.classify_channel)
+        .or_else(|| channels.iter().next())
+        .ok_or("No channels")?;
+    let mut values: Vec<f32> = channel.samples.range(..).map(|&(_, value)| value).collect();
+    let stats = Stats::of(&mut values).ok_or_else(|| format!("No samples in {}", channel.name))?;
+    let spans = classify::spans(
+        channel.samples.range(..),
+        config.thresholds,
+        f64::NEG_INFINITY,
+    );
+    let states = StateSummary::from_spans(&channel.name, config.thresholds, &spans);
+    let mismatches = channels
+        .get(FIRMWARE_STATE_CHANNEL)
+        .map(|firmware| classify::mismatches(&spans, firmware.samples.range(..)).len());
+
+    // the lag and ripple of each filter on a real recording, to weigh them against each other
+    if !config.filters.is_empty() {
+        let filters = config
+            .filters
+            .iter()
+            .map(|(name, source, _)| (name.as_str(), source.as_str()));
+        let comparisons =
+            tuning::compare_filters(filters, &channels, f64::NEG_INFINITY, f64::INFINITY);
+        let out_path = output.join(Path::new(file).with_extension("filters.csv"));
+        tuning::write_csv(&out_path, &comparisons)
+            .map_err(|error| format!("Unable to write {}: {error}", out_path.display()))?;
+    }
+
+    if config.write_files {
+        let recordings: Vec<(String, Vec<(f64, f32)>)> = channels
+            .iter()
+            .map(|channel| {
+                let samples = channel.samples.range(..).copied().collect();
+                (channel.name.clone(), samples)
+            })
+            .collect();
+        let out_path = output.join(Path::new(file).with_extension("filtered.csv"));
+        export::write_csv(&out_path, &Table::from_channels(&recordings))
+            .map_err(|error| format!("Unable to write {}: {error}", out_path.display()))?;
+    }
+
+    let (start, end) = channels.time_span().unwrap_or_default();
+    Ok(FileSummary {
+        file: file.to_owned(),
+        channel: channel.name.clone(),
+        duration: end - start,
+        malformed_rows: imported.report.malformed_rows,
+        gaps: imported.report.gaps.len(),
+        stats,
+        states,
+        mismatches,
+    })
+}
+
+fn write_row(writer: &mut impl Write, row: &FileSummary) -> io::Result<()> {
+    let contractions = &row.states.contractions;
+    let mean_contraction = if contractions.is_empty() {
+        String::new()
+    } else {
+        let total: f64 = contractions.iter().map(|(start, end)| end - start).sum();
+        format!("{:.3}", total / contractions.len() as f64)
+    };
+    let classified: f64 = row.states.time_in_state.iter().map(|(_, time)| time).sum();
+    let clenched = row
+        .states
+        .time_in_state
+        .iter()
+        .find(|(state, _)| *state == EmgState::Clenched)
+        .map_or(0.0, |(_, time)| *time);
+    let mismatches = row
+        .mismatches
+        .map_or(String::new(), |mismatches| mismatches.to_string());
+
+    writeln!(
+        writer,
+        "{},ok,{},{:.3},{},{},{:.3},{:.3},{:.3},{},{mean_contraction},{:.1},{mismatches}",
+        quote(&row.file),
+        quote(&row.channel),
+        row.duration,
+        row.malformed_rows,
+        row.gaps,
+        row.stats.mean,
+        row.stats.rms,
+        row.stats.p95,
+        contractions.len(),
+        clenched * 100.0 / classified.max(f64::EPSILON),
+    )
+}
+
+/// `text` in quotes, so commas in a file or channel name don't split the cell
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
@@ -0,0 +1,310 @@
+use std::collections::vec_deque::Iter;
+
+use eframe::egui::{self, Color32};
+
+use crate::ring_buffer::RingBuffer;
+
+/// Colors given to channels in the order they show up
+const PALETTE: [Color32; 8] = [
+    Color32::from_rgb(220, 50, 47),
+    Color32::from_rgb(38, 139, 210),
+    Color32::from_rgb(133, 153, 0),
+    Color32::from_rgb(203, 75, 22),
+    Color32::from_rgb(108, 113, 196),
+    Color32::from_rgb(42, 161, 152),
+    Color32::from_rgb(211, 54, 130),
+    Color32::from_rgb(181, 137, 0),
+];
+
+/// Which y-axis a channel is plotted against
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Left,
+    /// For values on a different scale, like servo angles next to ADC counts
+    Right,
+}
+
+/// One named stream of samples, like `raw` or `motor`
+pub struct Channel {
+    pub name: String,
+    pub color: Color32,
+    pub visible: bool,
+    pub axis: Axis,
+    /// The gain of the amplifier in front of the ADC, for converting to millivolts
+    pub gain: f32,
+    /// Millivolts at the ADC when the electrodes read nothing, taken off before the gain
+    pub offset: f32,
+    /// Samples as (seconds since connecting, value). The time is an f64 since an f32
+    /// can't tell samples a millisecond apart once a session is a few hours long.
+    pub samples: RingBuffer<(f64, f32)>,
+}
+
+impl Channel {
+    fn new(name: &str, color: Color32, samples: RingBuffer<(f64, f32)>) -> Self {
+        // angles don't share a scale with the EMG
+        let axis = if name.contains("motor") || name.contains("angle") {
+            Axis::Right
+        } else {
+            Axis::Left
+        };
+
+        Self {
+            name: name.to_owned(),
+            color,
+            visible: true,
+            axis,
+            gain: 1.0,
+            offset: 0.0,
+            samples,
+        }
+    }
+
+    /// The samples from `start` to `end` seconds
+    pub fn between(&self, start: f64, end: f64) -> Iter<'_, (f64, f32)> {
+        let first = self.samples.partition_point(|&(time, _)| time < start);
+        let last = self.samples.partition_point(|&(time, _)| time <= end);
+        self.samples.range(first..last)
+    }
+
+    /// The value at `time` seconds, in a straight line between the samples either side.
+    /// `None` outside of the samples.
+    pub fn value_at(&self, time: f64) -> Option<f32> {
+        let next = self
+            .samples
+            .partition_point(|&(sample_time, _)| sample_time < time);
+        let after = self.samples.range(next..).next();
+        let before = next
+            .checked_sub(1)
+            .and_then(|previous| self.samples.range(previous..).next());
+        match (before, after) {
+            (_, Some(&(after_time, value))) if after_time == time => Some(value),
+            (Some(&(before_time, from)), Some(&(after_time, to))) => {
+                let fraction = ((time - before_time) / (after_time - before_time)) as f32;
+                Some(from + (to - from) * fraction)
+            }
+            _ => None,
+        }
+    }
+
+    /// The smallest and largest values from `start` to `end` seconds
+    pub fn value_range(&self, start: f64, end: f64) -> Option<(f32, f32)> {
+        self.between(start, end)
+            .fold(None, |range, &(_, value)| match range {
+                None => Some((value, value)),
+                Some((low, high)) => Some((value.min(low), value.max(high))),
+            })
+    }
+}
+
+/// Every channel that has been received, each with its own history
+pub struct Channels {
+    channels: Vec<Channel>,
+    /// How many samples each channel keeps
+    capacity: usize,
+    /// Colors picked for channels before, given back to channels with the same name
+    colors: Vec<(String, Color32)>,
+}
+
+impl Channels {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            channels: Vec::new(),
+            capacity,
+            colors: Vec::new(),
+        }
+    }
+
+    /// Give channels called these names these colors when they show up
+    pub fn remember_colors(&mut self, colors: Vec<(String, Color32)>) {
+        self.colors = colors;
+    }
+
+    /// The colors to remember, of the channels now and any remembered from before
+    pub fn colors(&self) -> Vec<(String, Color32)> {
+        let mut colors: Vec<(String, Color32)> = self
+            .channels
+            .iter()
+            .map(|channel| (channel.name.clone(), channel.color))
+            .collect();
+        for (name, color) in &self.colors {
+            if !colors.iter().any(|(known, _)| known == name) {
+                colors.push((name.clone(), *color));
+            }
+        }
+        colors
+    }
+
+    /// The remembered color for `name`, or the next one from the palette for the `index`th channel
+    fn color_for(&self, name: &str, index: usize) -> Color32 {
+        self.colors
+            .iter()
+            .find(|(known, _)| known == name)
+            .map_or(PALETTE[index % PALETTE.len()], |&(_, color)| color)
+    }
+
+    /// The channel called `name`, creating it if it doesn't exist yet
+    fn get_or_create(&mut self, name: &str) -> &mut Channel {
+        let index = match self
+            .channels
+            .iter()
+            .position(|channel| channel.name == name)
+        {
+            Some(index) => index,
+            None => {
+                let color = self.color_for(name, self.channels.len());
+                let samples = RingBuffer::new(self.capacity);
+                self.channels.push(Channel::new(name, color, samples));
+                self.channels.len() - 1
+            }
+        };
+        &mut self.channels[index]
+    }
+
+    /// Add a sample to the channel called `name`, creating it if this is the first one
+    pub fn push(&mut self, name: &str, time: f64, value: f32) {
+        self.get_or_create(name).samples.push((time, value));
+    }
+
+    /// Swap out every sample of the channel called `name`, keeping its color and visibility
+    pub fn replace(&mut self, name: &str, samples: Vec<(f64, f32)>) {
+        // a loaded file can have more samples than the history holds
+        let mut buffer = RingBuffer::new(self.capacity.max(samples.len()));
+        for sample in samples {
+            buffer.push(sample);
+        }
+        self.get_or_create(name).samples = buffer;
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.channels.retain(|channel| channel.name != name);
+    }
+
+    /// Replace every channel with whole recordings, each keeping all of its samples
+    pub fn load(&mut self, recordings: Vec<(String, Vec<(f64, f32)>)>) {
+        self.channels = recordings
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, samples))| {
+                Channel::new(&name, self.color_for(&name, i), RingBuffer::from(samples))
+            })
+            .collect();
+    }
+
+    /// Remove every channel
+    pub fn clear(&mut self) {
+        self.channels.clear();
+    }
+
+    /// Change how many samples each channel keeps, keeping the newest
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        for channel in &mut self.channels {
+            channel.samples.set_capacity(capacity);
+        }
+    }
+
+    /// The channel called `name`
+    pub fn get(&self, name: &str) -> Option<&Channel> {
+        self.channels.iter().find(|channel| channel.name == name)
+    }
+
+    /// Point `name` at the first channel if there is no channel called that.
+    /// Returns if it changed.
+    pub fn fall_back(&self, name: &mut String) -> bool {
+        match self.channels.first() {
+            Some(first) if self.get(name).is_none() => {
+                *name = first.name.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// A drop down of every channel to pick `name` from, returning if the pick changed
+    pub fn picker(&self, ui: &mut egui::Ui, combo: egui::ComboBox, name: &mut String) -> bool {
+        let mut changed = false;
+        combo.selected_text(name.as_str()).show_ui(ui, |ui| {
+            for channel in &self.channels {
+                changed |= ui
+                    .selectable_value(name, channel.name.clone(), channel.name.as_str())
+                    .changed();
+            }
+        });
+        changed
+    }
+
+    /// A row to show, color and pick the axis of each channel. With `millivolts` the
+    /// EMG channels get their amplifier's gain and offset too.
+    pub fn controls(&mut self, ui: &mut egui::Ui, millivolts: bool) {
+        if self.channels.is_empty() {
+            ui.label("No channels yet");
+        }
+        for channel in self.channels.iter_mut() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut channel.visible, channel.name.as_str());
+                ui.color_edit_button_srgba(&mut channel.color);
+                egui::ComboBox::from_id_salt(("axis", channel.name.as_str()))
+                    .selected_text(match channel.axis {
+                        Axis::Left => "Left",
+                        Axis::Right => "Right",
+                    })
+                    .width(60.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut channel.axis, Axis::Left, "Left");
+                        ui.selectable_value(&mut channel.axis, Axis::Right, "Right");
+                    });
+            });
+            if millivolts && channel.axis == Axis::Left {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut channel.gain)
+                            .range(0.001..=100_000.0)
+                            .prefix("Gain "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut channel.offset)
+                            .prefix("Offset ")
+                            .suffix(" mV"),
+                    );
+                });
+            }
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Channel> {
+        self.channels.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Channel> {
+        self.channels.iter_mut()
+    }
+
+    /// The times of the oldest and newest samples in any channel
+    pub fn time_span(&self) -> Option<(f64, f64)> {
+        self.channels
+            .iter()
+            .filter_map(|channel| {
+                let &(first, _) = channel.samples.front()?;
+                let &(last, _) = channel.samples.back()?;
+                Some((first, last))
+            })
+            .reduce(|(first, last), (start, end)| (first.min(start), last.max(end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_a_millisecond_apart_hours_in() {
+        let mut channels = Channels::new(10);
+        let start = 4.0 * 60.0 * 60.0;
+        for i in 0..4 {
+            channels.push("emg", start + i as f64 * 0.001, i as f32);
+        }
+        let emg = channels.get("emg").unwrap();
+        assert_eq!(emg.between(start + 0.001, start + 0.002).count(), 2);
+        assert_eq!(emg.value_at(start + 0.0015), Some(1.5));
+    }
+}
@@ -0,0 +1,309 @@
+use std::ops::Range;
+
+use eframe::egui;
+use hand_core::{Classifier, EmgState, Thresholds};
+
+use crate::channel::Channels;
+use crate::units::{Scale, Units};
+use crate::viewport::TimeViewport;
+
+/// The channel the firmware sends its classified state on, as `EmgState::code`.
+/// It classifies the values it sends as `smoothed`.
+pub const FIRMWARE_STATE_CHANNEL: &str = "state";
+
+/// A (start, end, state) span of time the signal stayed in one state
+pub type StateSpan = (f64, f64, EmgState);
+
+/// The state the firmware sent as `value`, `None` if it isn't a state code
+pub fn reported_state(value: f32) -> Option<EmgState> {
+    (value >= 0.0)
+        .then(|| EmgState::from_code(value.round() as u8))
+        .flatten()
+}
+
+/// Run the classifier over `samples` the same way the firmware would, and return the
+/// (start, end, state) spans from `start` seconds on. The state depends on what came
+/// before, so `samples` should begin with the oldest sample.
+pub fn spans<'a>(
+    samples: impl IntoIterator<Item = &'a (f64, f32)>,
+    thresholds: Thresholds,
+    start: f64,
+) -> Vec<StateSpan> {
+    let mut classifier = Classifier::new(thresholds);
+    let mut spans = Vec::new();
+    for &(time, value) in samples {
+        let state = classifier.update(value.clamp(0.0, u16::MAX as f32) as u16);
+        if time >= start {
+            extend_spans(&mut spans, time, state);
+        }
+    }
+    spans
+}
+
+/// The (start, end, state) spans of the states the firmware sent, as (time, code).
+/// Values that aren't a state code are skipped.
+pub fn reported_spans<'a>(reported: impl IntoIterator<Item = &'a (f64, f32)>) -> Vec<StateSpan> {
+    let mut spans = Vec::new();
+    for &(time, value) in reported {
+        if let Some(state) = reported_state(value) {
+            extend_spans(&mut spans, time, state);
+        }
+    }
+    spans
+}
+
+/// Carry the last span on to `time`, starting a new one there if the state changed
+fn extend_spans(spans: &mut Vec<StateSpan>, time: f64, state: EmgState) {
+    match spans.last_mut() {
+        Some((_, end, last)) if *last == state => *end = time,
+        Some((_, end, _)) => {
+            *end = time;
+            spans.push((time, time, state));
+        }
+        None => spans.push((time, time, state)),
+    }
+}
+
+/// The classifier's state at every sample, as (time, `EmgState::code`)
+pub fn codes<'a>(
+    samples: impl IntoIterator<Item = &'a (f64, f32)>,
+    thresholds: Thresholds,
+) -> Vec<(f64, f32)> {
+    let mut classifier = Classifier::new(thresholds);
+    samples
+        .into_iter()
+        .map(|&(time, value)| {
+            let state = classifier.update(value.clamp(0.0, u16::MAX as f32) as u16);
+            (time, state.code() as f32)
+        })
+        .collect()
+}
+
+/// Times where the states the firmware sent, as (time, code), start to disagree with
+/// `spans`. The firmware runs the same classifier on the `smoothed` values it sends, so
+/// with `spans` from that channel and the same thresholds any mismatch is a bug.
+pub fn mismatches<'a>(
+    spans: &[StateSpan],
+    reported: impl IntoIterator<Item = &'a (f64, f32)>,
+) -> Vec<f64> {
+    let (Some(&(start, _, _)), Some(&(_, end, _))) = (spans.first(), spans.last()) else {
+        return Vec::new();
+    };
+
+    let mut mismatches = Vec::new();
+    let mut agreed = true;
+    for &(time, value) in reported {
+        if time < start || time > end {
+            continue;
+        }
+        // spans share their boundary sample with the span before, it belongs to the later one
+        let span = spans
+            .partition_point(|&(_, span_end, _)| span_end <= time)
+            .min(spans.len() - 1);
+        let agrees = reported_state(value) == Some(spans[span].2);
+        if agreed && !agrees {
+            mismatches.push(time);
+        }
+        agreed = agrees;
+    }
+    mismatches
+}
+
+/// The classifier run over one channel, for shading the plot by state
+#[derive(Default)]
+pub struct Classification {
+    /// Where the classifier changes state, drawn as lines on the plot
+    pub thresholds: Thresholds,
+    /// Show the thresholds and shade the plot by the state the classifier picks
+    pub show_states: bool,
+    /// Name of the channel run through the classifier
+    pub channel: String,
+    /// Spans of (start, end, state) the classifier picked on the plot last frame
+    pub spans: Vec<StateSpan>,
+}
+
+impl Classification {
+    /// How the classified channel is shown, which the threshold lines have to match
+    pub fn scale(&self, channels: &Channels, units: &Units) -> Scale {
+        channels
+            .get(&self.channel)
+            .map_or(Scale::IDENTITY, |channel| units.scale(channel))
+    }
+
+    /// Where the threshold lines go, in the units the classified channel is shown in
+    pub fn threshold_values(&self, channels: &Channels, units: &Units) -> Vec<f32> {
+        let scale = self.scale(channels, units);
+        vec![
+            scale.apply(self.thresholds.intermediate as f32),
+            scale.apply(self.thresholds.clenched as f32),
+        ]
+    }
+
+    /// Rows for the state lane under the plot, named, from `times.start` to `times.end`:
+    /// the states picked here last frame, and the ones the firmware sent if it sends any
+    pub fn lanes(
+        &self,
+        channels: &Channels,
+        times: Range<f64>,
+    ) -> Vec<(&'static str, Vec<StateSpan>)> {
+        let mut lanes = vec![("graph", self.spans.clone())];
+        if let Some(firmware) = channels.get(FIRMWARE_STATE_CHANNEL) {
+            lanes.push((
+                "firmware",
+                reported_spans(firmware.between(times.start, times.end)),
+            ));
+        }
+        lanes
+    }
+
+    /// Run the classifier over the chosen channel up to `times.end`, the same way the
+    /// firmware would, and return the spans of each state from `times.start`
+    pub fn classify(&self, channels: &Channels, times: Range<f64>) -> Vec<StateSpan> {
+        let Some(channel) = channels.get(&self.channel) else {
+            return Vec::new();
+        };
+        spans(
+            channel.between(f64::NEG_INFINITY, times.end),
+            self.thresholds,
+            times.start,
+        )
+    }
+
+    /// The channel and thresholds, with how long it spent in each state and whether
+    /// it agrees with the firmware. Clicking a mismatch centres `viewport` on it.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        channels: &Channels,
+        viewport: &mut TimeViewport,
+    ) {
+        ui.heading("Thresholds");
+
+        channels.fall_back(&mut self.channel);
+
+        ui.checkbox(&mut self.show_states, "Shade by classified state");
+        channels.picker(
+            ui,
+            egui::ComboBox::from_id_salt("classify channel"),
+            &mut self.channel,
+        );
+
+        egui::Grid::new("thresholds").num_columns(2).show(ui, |ui| {
+            let thresholds = &mut self.thresholds;
+            ui.label("Intermediate");
+            ui.add(
+                egui::DragValue::new(&mut thresholds.intermediate).range(0..=thresholds.clenched),
+            );
+            ui.end_row();
+            ui.label("Clenched");
+            ui.add(
+                egui::DragValue::new(&mut thresholds.clenched)
+                    .range(thresholds.intermediate..=1023),
+            );
+            ui.end_row();
+            ui.label("Hysteresis");
+            ui.add(egui::DragValue::new(&mut thresholds.hysteresis).range(0..=200));
+            ui.end_row();
+        });
+
+        let total: f64 = self.spans.iter().map(|(start, end, _)| end - start).sum();
+        if self.show_states && total > 0.0 {
+            for state in [
+                EmgState::Relaxed,
+                EmgState::Intermediate,
+                EmgState::Clenched,
+            ] {
+                let time: f64 = self
+                    .spans
+                    .iter()
+                    .filter(|&&(_, _, span_state)| span_state == state)
+                    .map(|(start, end, _)| end - start)
+                    .sum();
+                ui.label(format!("{state:?}: {:.1}%", time / total * 100.0));
+            }
+        }
+
+        self.agreement(ui, channels, viewport);
+    }
+
+    /// The state the firmware sent last next to the one picked here, and the times they disagree
+    fn agreement(&self, ui: &mut egui::Ui, channels: &Channels, viewport: &mut TimeViewport) {
+        let (Some(firmware), Some(&(_, here_end, here))) =
+            (channels.get(FIRMWARE_STATE_CHANNEL), self.spans.last())
+        else {
+            return;
+        };
+        ui.separator();
+        let reported = firmware
+            .between(f64::NEG_INFINITY, f64::INFINITY)
+            .next_back()
+            .and_then(|&(_, value)| reported_state(value));
+        let (start, end) = (self.spans[0].0, here_end);
+        let mismatches = mismatches(&self.spans, firmware.between(start, end));
+        egui::Grid::new("state agreement")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Firmware");
+                ui.label(reported.map_or(String::from("-"), |state| format!("{state:?}")));
+                ui.end_row();
+                ui.label("Graph");
+                ui.label(format!("{here:?}"));
+                ui.end_row();
+                ui.label("Mismatches on the plot");
+                ui.label(mismatches.len().to_string());
+                ui.end_row();
+            });
+
+        ui.horizontal_wrapped(|ui| {
+            for &time in &mismatches {
+                if ui
+                    .small_button(format!("{time:.2} s"))
+                    .on_hover_text("Show on the plot")
+                    .clicked()
+                {
+                    viewport.center_on(time);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firmware_states_agree_with_the_same_classifier() {
+        let thresholds = Thresholds::default();
+        let smoothed: Vec<(f64, f32)> = [100.0, 450.0, 900.0, 820.0, 700.0, 300.0]
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index as f64, value))
+            .collect();
+        // what the firmware sends for the same samples
+        let mut classifier = Classifier::new(thresholds);
+        let reported: Vec<(f64, f32)> = smoothed
+            .iter()
+            .map(|&(time, value)| (time, classifier.update(value as u16).code() as f32))
+            .collect();
+
+        let here = spans(&smoothed, thresholds, f64::NEG_INFINITY);
+        assert_eq!(reported_spans(&reported), here);
+        assert_eq!(
+            here,
+            [
+                (0.0, 1.0, EmgState::Relaxed),
+                (1.0, 2.0, EmgState::Intermediate),
+                (2.0, 4.0, EmgState::Clenched),
+                (4.0, 5.0, EmgState::Intermediate),
+                (5.0, 5.0, EmgState::Relaxed),
+            ]
+        );
+        assert!(mismatches(&here, &reported).is_empty());
+
+        // a state the firmware got wrong shows up where it starts
+        let mut wrong = reported.clone();
+        wrong[3].1 = EmgState::Intermediate.code() as f32;
+        assert_eq!(mismatches(&here, &wrong), [3.0]);
+    }
+}
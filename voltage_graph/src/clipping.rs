@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+use eframe::egui::{self, Id};
+use serde::{Deserialize, Serialize};
+
+use crate::channel::Channels;
+use crate::derived::{DerivedChannel, is_raw_emg};
+
+/// Seconds of samples the clipped percentage is taken over
+pub const CLIP_WINDOW: f64 = 3.0;
+
+/// When a sample counts as stuck against the ADC's rails.
+/// Some boards never reach exactly 0 or 1023, so the limits can be moved in.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClipLimits {
+    /// At or below this many ADC counts is clipped
+    pub low: f32,
+    /// At or above this many ADC counts is clipped
+    pub high: f32,
+    /// Warn when more than this percentage of the last few seconds is clipped
+    pub warn_percent: f32,
+}
+
+impl Default for ClipLimits {
+    fn default() -> Self {
+        Self {
+            low: 3.0,
+            high: 1020.0,
+            warn_percent: 1.0,
+        }
+    }
+}
+
+impl ClipLimits {
+    pub fn is_clipped(&self, value: f32) -> bool {
+        value <= self.low || value >= self.high
+    }
+
+    /// How much of `samples` is clipped, from 0 to 100, `None` if there are none
+    pub fn percent<'a>(&self, samples: impl Iterator<Item = &'a (f64, f32)>) -> Option<f32> {
+        let (clipped, total) = samples.fold((0, 0), |(clipped, total), &(_, value)| {
+            (clipped + usize::from(self.is_clipped(value)), total + 1)
+        });
+        (total > 0).then(|| clipped as f32 * 100.0 / total as f32)
+    }
+
+    /// Spans of (start, end) where the samples are clipped.
+    /// Each span runs on to the next sample that isn't, so a single clipped sample still shows.
+    pub fn spans<'a>(&self, samples: impl Iterator<Item = &'a (f64, f32)>) -> Vec<(f64, f64)> {
+        let mut spans = Vec::new();
+        let mut start = None;
+        let mut last_time = None;
+        for &(time, value) in samples {
+            match (start, self.is_clipped(value)) {
+                (None, true) => start = Some(time),
+                (Some(clipped_from), false) => {
+                    spans.push((clipped_from, time));
+                    start = None;
+                }
+                _ => {}
+            }
+            last_time = Some(time);
+        }
+        if let Some((clipped_from, last_time)) = start.zip(last_time) {
+            spans.push((clipped_from, last_time));
+        }
+        spans
+    }
+
+    /// How much of the last few seconds of each raw EMG channel is clipped, in percent
+    pub fn clipped_percentages(
+        &self,
+        channels: &Channels,
+        derived: &[DerivedChannel],
+        newest: f64,
+    ) -> Vec<(String, f32)> {
+        channels
+            .iter()
+            .filter(|channel| is_raw_emg(channel, derived))
+            .filter_map(|channel| {
+                let samples = channel.between(newest - CLIP_WINDOW, newest);
+                self.percent(samples)
+                    .map(|percent| (channel.name.clone(), percent))
+            })
+            .collect()
+    }
+
+    /// Where the shown raw EMG channels are clipped during `times`
+    pub fn clipped_spans(
+        &self,
+        channels: &Channels,
+        derived: &[DerivedChannel],
+        times: Range<f64>,
+    ) -> Vec<(f64, f64)> {
+        channels
+            .iter()
+            .filter(|channel| channel.visible && is_raw_emg(channel, derived))
+            .flat_map(|channel| self.spans(channel.between(times.start, times.end)))
+            .collect()
+    }
+
+    /// The limits, with how much of each channel in `clipped` is clipped
+    pub fn controls(&mut self, ui: &mut egui::Ui, clipped: &[(String, f32)]) {
+        ui.heading("Clipping");
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.low)
+                    .range(0.0..=self.high)
+                    .prefix("At or below "),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.high)
+                    .range(self.low..=f32::MAX)
+                    .prefix("At or above "),
+            );
+        });
+        ui.add(
+            egui::Slider::new(&mut self.warn_percent, 0.1..=20.0)
+                .logarithmic(true)
+                .suffix(" %")
+                .text("Warn above"),
+        );
+
+        for (name, percent) in clipped {
+            let text = format!("{name}: {percent:.1} % clipped in the last {CLIP_WINDOW} s");
+            if *percent > self.warn_percent {
+                ui.colored_label(ui.visuals().error_fg_color, text);
+            } else {
+                ui.label(text);
+            }
+        }
+    }
+}
+
+/// A warning across the top of the window while too much of the EMG is clipped
+#[derive(Default)]
+pub struct ClipBanner {
+    /// The warning was closed, it comes back once clipping stops and starts again
+    dismissed: bool,
+}
+
+impl ClipBanner {
+    /// Shows the channels in `clipped` that are above `warn_percent`
+    pub fn show(&mut self, ctx: &egui::Context, clipped: &[(String, f32)], warn_percent: f32) {
+        let clipped: Vec<&(String, f32)> = clipped
+            .iter()
+            .filter(|&&(_, percent)| percent > warn_percent)
+            .collect();
+        if clipped.is_empty() {
+            self.dismissed = false;
+            return;
+        }
+        if self.dismissed {
+            return;
+        }
+
+        egui::TopBottomPanel::top(Id::new("clipping warning")).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let channels: Vec<String> = clipped
+                    .iter()
+                    .map(|(name, percent)| format!("{name} ({percent:.0} %)"))
+                    .collect();
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!(
+                        "The EMG is clipping: {}. Try turning the amplifier gain down.",
+                        channels.join(", ")
+                    ),
+                );
+                if ui.button("Dismiss").clicked() {
+                    self.dismissed = true;
+                }
+            });
+        });
+    }
+}
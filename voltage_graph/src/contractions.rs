@@ -0,0 +1,460 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use hand_core::EmgState;
+
+use crate::channel::Channel;
+use crate::viewport::TimeViewport;
+
+/// How often the contractions are found again, the classifier runs over the whole history
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+/// How far the angle has to move from where it was at the onset before the hand
+/// counts as responding, in degrees
+const RESPONSE_DEGREES: f32 = 2.0;
+
+/// One contraction, from leaving Relaxed until coming back to it
+pub struct Contraction {
+    pub onset: f64,
+    pub offset: f64,
+    /// The highest value of the classified channel during it
+    pub peak: f32,
+    /// Seconds from the onset until the angle started moving, `None` without an
+    /// angle channel or if it didn't move before the offset
+    pub latency: Option<f64>,
+    /// It was already going when the recording started, so `onset` is only the first sample
+    pub cut_at_start: bool,
+    /// It was still going when the recording ended, so `offset` is only the last sample
+    pub cut_at_end: bool,
+}
+
+impl Contraction {
+    /// How long it lasted, `None` if either end of the recording cut it off
+    pub fn duration(&self) -> Option<f64> {
+        (!self.cut_at_start && !self.cut_at_end).then_some(self.offset - self.onset)
+    }
+
+    /// Why the times can't be trusted, empty if they can
+    pub fn note(&self) -> &'static str {
+        match (self.cut_at_start, self.cut_at_end) {
+            (true, true) => "whole recording",
+            (true, false) => "started before recording",
+            (false, true) => "cut off at end",
+            (false, false) => "",
+        }
+    }
+}
+
+/// What the contractions table is sorted by
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Onset,
+    Duration,
+    Peak,
+    Latency,
+}
+
+/// The contractions in a recording, for seeing how quickly the hand responds
+pub struct ContractionView {
+    pub contractions: Vec<Contraction>,
+    pub sort_by: SortBy,
+    pub descending: bool,
+    last_update: Option<Instant>,
+}
+
+impl Default for ContractionView {
+    fn default() -> Self {
+        Self {
+            contractions: Vec::new(),
+            sort_by: SortBy::Onset,
+            descending: false,
+            last_update: None,
+        }
+    }
+}
+
+impl ContractionView {
+    /// Find the contractions in `spans` again, if it has been long enough since the last time.
+    /// `spans` should cover the whole of `channel` so the cut off ones can be told apart.
+    pub fn update(
+        &mut self,
+        spans: &[(f64, f64, EmgState)],
+        channel: &Channel,
+        angle: Option<&Channel>,
+    ) {
+        if self
+            .last_update
+            .is_some_and(|last| last.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        self.contractions = detect(spans, channel, angle);
+        self.sort();
+    }
+
+    /// Sort by `column`, or flip the order if it is already sorted by it
+    pub fn set_sort(&mut self, column: SortBy) {
+        if self.sort_by == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort_by = column;
+            self.descending = false;
+        }
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        let sort_by = self.sort_by;
+        // cut off contractions have no duration and go last, like missing latencies
+        let key = |contraction: &Contraction| match sort_by {
+            SortBy::Onset => Some(contraction.onset),
+            SortBy::Duration => contraction.duration(),
+            SortBy::Peak => Some(contraction.peak as f64),
+            SortBy::Latency => contraction.latency,
+        };
+        self.contractions.sort_by(|a, b| match (key(a), key(b)) {
+            (Some(a), Some(b)) if self.descending => b.total_cmp(&a),
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+    }
+
+    /// Onsets and offsets to mark on the plot, leaving out the ends the recording cut off
+    pub fn markers(&self) -> (Vec<f64>, Vec<f64>) {
+        let onsets = self
+            .contractions
+            .iter()
+            .filter(|contraction| !contraction.cut_at_start)
+            .map(|contraction| contraction.onset)
+            .collect();
+        let offsets = self
+            .contractions
+            .iter()
+            .filter(|contraction| !contraction.cut_at_end)
+            .map(|contraction| contraction.offset)
+            .collect();
+        (onsets, offsets)
+    }
+
+    /// Save the table as it is sorted, cut off contractions have no duration
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(writer, "onset_s,offset_s,duration_s,peak,latency_ms,note")?;
+        for contraction in &self.contractions {
+            let duration = contraction
+                .duration()
+                .map_or(String::new(), |duration| format!("{duration:.3}"));
+            let latency = contraction
+                .latency
+                .map_or(String::new(), |latency| format!("{:.1}", latency * 1000.0));
+            writeln!(
+                writer,
+                "{:.3},{:.3},{duration},{},{latency},{}",
+                contraction.onset,
+                contraction.offset,
+                contraction.peak,
+                contraction.note()
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    /// A table of the contractions in `channel`, sorted by clicking a column's header.
+    /// Clicking an onset centres `viewport` on it. Returns whether export was clicked.
+    pub fn table(&mut self, ui: &mut egui::Ui, channel: &str, viewport: &mut TimeViewport) -> bool {
+        let durations: Vec<f64> = self
+            .contractions
+            .iter()
+            .filter_map(|contraction| contraction.duration())
+            .collect();
+        let latencies: Vec<f64> = self
+            .contractions
+            .iter()
+            .filter_map(|contraction| contraction.latency)
+            .collect();
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+
+        let mut export = false;
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} contractions in {channel}, mean duration {:.2} s, mean latency {}",
+                self.contractions.len(),
+                mean(&durations),
+                if latencies.is_empty() {
+                    String::from("-")
+                } else {
+                    format!("{:.0} ms", mean(&latencies) * 1000.0)
+                },
+            ));
+            if ui
+                .add_enabled(
+                    !self.contractions.is_empty(),
+                    egui::Button::new("Export CSV"),
+                )
+                .clicked()
+            {
+                export = true;
+            }
+        });
+
+        let mut sort = None;
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("contractions table")
+                .num_columns(7)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("#");
+                    for (header, column) in [
+                        ("Onset", Some(SortBy::Onset)),
+                        ("Offset", None),
+                        ("Duration", Some(SortBy::Duration)),
+                        ("Peak", Some(SortBy::Peak)),
+                        ("Latency", Some(SortBy::Latency)),
+                    ] {
+                        match column {
+                            Some(column) => {
+                                let arrow = if self.sort_by != column {
+                                    ""
+                                } else if self.descending {
+                                    " ⏷"
+                                } else {
+                                    " ⏶"
+                                };
+                                if ui.button(format!("{header}{arrow}")).clicked() {
+                                    sort = Some(column);
+                                }
+                            }
+                            None => {
+                                ui.strong(header);
+                            }
+                        }
+                    }
+                    ui.strong("Note");
+                    ui.end_row();
+
+                    for (i, contraction) in self.contractions.iter().enumerate() {
+                        ui.monospace((i + 1).to_string());
+                        if ui
+                            .small_button(format!("{:.3} s", contraction.onset))
+                            .on_hover_text("Show on the plot")
+                            .clicked()
+                        {
+                            viewport.center_on(contraction.onset);
+                        }
+                        ui.monospace(format!("{:.3} s", contraction.offset));
+                        ui.monospace(
+                            contraction
+                                .duration()
+                                .map_or(String::from("-"), |duration| format!("{duration:.3} s")),
+                        );
+                        ui.monospace(format!("{:.1}", contraction.peak));
+                        ui.monospace(contraction.latency.map_or(String::from("-"), |latency| {
+                            format!("{:.0} ms", latency * 1000.0)
+                        }));
+                        ui.label(contraction.note());
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if let Some(column) = sort {
+            self.set_sort(column);
+        }
+        export
+    }
+}
+
+/// 1 at the sample each contraction starts, -1 where it ends and 0 everywhere else,
+/// as (time, flag) for every sample of `samples`. Ends cut off by the recording aren't flagged.
+pub fn flags<'a>(
+    samples: impl IntoIterator<Item = &'a (f64, f32)>,
+    contractions: &[Contraction],
+) -> Vec<(f64, f32)> {
+    let mut onsets = contractions
+        .iter()
+        .filter(|contraction| !contraction.cut_at_start)
+        .map(|contraction| contraction.onset)
+        .peekable();
+    let mut offsets = contractions
+        .iter()
+        .filter(|contraction| !contraction.cut_at_end)
+        .map(|contraction| contraction.offset)
+        .peekable();
+    samples
+        .into_iter()
+        .map(|&(time, _)| {
+            let flag = if onsets.next_if(|&onset| onset <= time).is_some() {
+                1.0
+            } else if offsets.next_if(|&offset| offset <= time).is_some() {
+                -1.0
+            } else {
+                0.0
+            };
+            (time, flag)
+        })
+        .collect()
+}
+
+/// The contractions in `spans` from the classifier, with their peak in `channel` and
+/// how long `angle` took to start moving after each onset
+pub fn detect(
+    spans: &[(f64, f64, EmgState)],
+    channel: &Channel,
+    angle: Option<&Channel>,
+) -> Vec<Contraction> {
+    let mut contractions = Vec::new();
+    let mut current: Option<Contraction> = None;
+    for (i, &(start, end, state)) in spans.iter().enumerate() {
+        if state == EmgState::Relaxed {
+            contractions.extend(current.take());
+            continue;
+        }
+        match &mut current {
+            // Intermediate and Clenched spans next to each other are one contraction
+            Some(contraction) => contraction.offset = end,
+            None => {
+                current = Some(Contraction {
+                    onset: start,
+                    offset: end,
+                    peak: 0.0,
+                    latency: None,
+                    cut_at_start: i == 0,
+                    cut_at_end: false,
+                })
+            }
+        }
+    }
+    if let Some(mut contraction) = current {
+        contraction.cut_at_end = true;
+        contractions.push(contraction);
+    }
+
+    for contraction in &mut contractions {
+        let (onset, offset) = (contraction.onset, contraction.offset);
+        contraction.peak = channel
+            .between(onset, offset)
+            .map(|&(_, value)| value)
+            .fold(f32::NEG_INFINITY, f32::max);
+        contraction.latency = angle.and_then(|angle| {
+            let start = angle.value_at(onset)?;
+            angle
+                .between(onset, offset)
+                .find(|&&(_, value)| (value - start).abs() > RESPONSE_DEGREES)
+                .map(|&(time, _)| time - onset)
+        });
+    }
+    contractions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::Channels;
+
+    /// `raw` resting at 100 with a contraction from 1 s to 2 s peaking at 900,
+    /// and `angle` moving 0.3 s into it. Samples every 0.1 s for 5 s.
+    fn recording() -> Channels {
+        let mut channels = Channels::new(1000);
+        for i in 0..=50 {
+            let time = i as f64 / 10.0;
+            let raw = match i {
+                15 => 900.0,
+                10..20 => 800.0,
+                _ => 100.0,
+            };
+            channels.push("raw", time, raw);
+            channels.push(
+                "angle",
+                time,
+                if (13..25).contains(&i) { 45.0 } else { 0.0 },
+            );
+        }
+        channels
+    }
+
+    const SPANS: [(f64, f64, EmgState); 5] = [
+        (0.0, 1.0, EmgState::Relaxed),
+        (1.0, 1.5, EmgState::Intermediate),
+        (1.5, 2.0, EmgState::Clenched),
+        (2.0, 3.0, EmgState::Relaxed),
+        (3.0, 5.0, EmgState::Clenched),
+    ];
+
+    #[test]
+    fn joins_neighbouring_spans_and_finds_the_peak_and_latency() {
+        let channels = recording();
+        let contractions = detect(&SPANS, channels.get("raw").unwrap(), channels.get("angle"));
+
+        assert_eq!(contractions.len(), 2);
+        let first = &contractions[0];
+        assert_eq!((first.onset, first.offset), (1.0, 2.0));
+        assert_eq!(first.peak, 900.0);
+        assert!((first.latency.unwrap() - 0.3).abs() < 1e-4);
+        assert!((first.duration().unwrap() - 1.0).abs() < 1e-6);
+        assert_eq!(first.note(), "");
+    }
+
+    #[test]
+    fn ends_cut_off_by_the_recording() {
+        let channels = recording();
+        let raw = channels.get("raw").unwrap();
+
+        let contractions = detect(&SPANS, raw, None);
+        let last = &contractions[1];
+        assert!(last.cut_at_end && !last.cut_at_start);
+        assert_eq!(last.duration(), None);
+        assert_eq!(last.note(), "cut off at end");
+        // no angle channel, no latency
+        assert_eq!(contractions[0].latency, None);
+
+        let contractions = detect(&SPANS[1..], raw, None);
+        assert!(contractions[0].cut_at_start);
+        assert_eq!(contractions[0].note(), "started before recording");
+    }
+
+    #[test]
+    fn angle_that_never_moves_has_no_latency() {
+        let mut channels = recording();
+        channels.replace("angle", (0..=50).map(|i| (i as f64 / 10.0, 10.0)).collect());
+
+        let contractions = detect(&SPANS, channels.get("raw").unwrap(), channels.get("angle"));
+        assert_eq!(contractions[0].latency, None);
+    }
+
+    #[test]
+    fn cut_off_contractions_sort_last() {
+        let channels = recording();
+        let mut view = ContractionView {
+            contractions: detect(&SPANS, channels.get("raw").unwrap(), None),
+            ..Default::default()
+        };
+
+        view.set_sort(SortBy::Duration);
+        assert!(!view.contractions[0].cut_at_end);
+        // flipping the order still leaves the one without a duration at the bottom
+        view.set_sort(SortBy::Duration);
+        assert!(view.descending);
+        assert!(view.contractions[1].cut_at_end);
+
+        view.set_sort(SortBy::Onset);
+        assert!(!view.descending);
+        assert_eq!(view.contractions[0].onset, 1.0);
+    }
+
+    #[test]
+    fn flags_leave_out_cut_off_ends() {
+        let channels = recording();
+        let raw = channels.get("raw").unwrap();
+        let contractions = detect(&SPANS, raw, None);
+
+        let flags = flags(raw.between(0.0, 5.0), &contractions);
+        let flagged: Vec<(f64, f32)> = flags.into_iter().filter(|&(_, flag)| flag != 0.0).collect();
+        assert_eq!(flagged, [(1.0, 1.0), (2.0, -1.0), (3.0, 1.0)]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::channel::{Channel, Channels};
+use crate::plot;
+use crate::stats::StatsWindow;
+use crate::theme::ChartStyle;
+
+/// How often the correlation is worked out again, it is slow for long windows
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How alike two channels are when one is shifted in time against the other,
+/// to see which one leads and by how much
+pub struct CorrelationView {
+    /// Name of the channel the other is compared with
+    pub first: String,
+    /// Name of the channel that is shifted
+    pub second: String,
+    pub window: StatsWindow,
+    /// The furthest the second channel is shifted either way, in milliseconds
+    pub max_lag_ms: f32,
+    /// (lag in ms, correlation from -1 to 1), a positive lag is the second channel being later
+    pub points: Vec<(f32, f32)>,
+    last_update: Option<Instant>,
+}
+
+impl Default for CorrelationView {
+    fn default() -> Self {
+        Self {
+            first: String::new(),
+            second: String::new(),
+            window: StatsWindow::LastFiveSeconds,
+            max_lag_ms: 500.0,
+            points: Vec::new(),
+            last_update: None,
+        }
+    }
+}
+
+impl CorrelationView {
+    /// Correlate `first` and `second` from `start` to `end` seconds,
+    /// if it has been long enough since the last time
+    pub fn update(&mut self, first: &Channel, second: &Channel, (start, end): (f64, f64)) {
+        if self
+            .last_update
+            .is_some_and(|last| last.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        // the channels may not be sampled at the same times, so the second is
+        // read at the times of the first, where they overlap
+        let (times, (a, b)): (Vec<f64>, (Vec<f32>, Vec<f32>)) = first
+            .between(start, end)
+            .filter_map(|&(time, value)| Some((time, (value, second.value_at(time)?))))
+            .unzip();
+        self.points = match sample_period(&times) {
+            Some(period) => {
+                let max_lag = (self.max_lag_ms / 1000.0 / period) as isize;
+                correlate(&a, &b, max_lag)
+                    .into_iter()
+                    .map(|(lag, r)| (lag as f32 * period * 1000.0, r))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+    }
+
+    /// Correlate the chosen channels over `span`, starting with the first two channels,
+    /// like raw against the angle, when the chosen ones aren't there
+    pub fn follow(&mut self, channels: &Channels, span: Option<(f64, f64)>) {
+        let mut names = channels.iter().map(|channel| channel.name.clone());
+        if channels.get(&self.first).is_none()
+            && let Some(name) = names.next()
+        {
+            self.first = name;
+        }
+        if channels.get(&self.second).is_none()
+            && let Some(name) = names.next()
+        {
+            self.second = name;
+        }
+
+        let first = channels.get(&self.first);
+        let second = channels.get(&self.second);
+        if let (Some(span), Some(first), Some(second)) = (span, first, second) {
+            self.update(first, second, span);
+        }
+    }
+
+    /// The channels, window and lags, with the peak under them while it is `shown`
+    pub fn controls(&mut self, ui: &mut egui::Ui, channels: &Channels, shown: bool) {
+        ui.heading("Cross-correlation");
+
+        let mut changed = false;
+        for (label, name) in [
+            ("Reference", &mut self.first),
+            ("Shifted", &mut self.second),
+        ] {
+            changed |= channels.picker(ui, egui::ComboBox::from_label(label), name);
+        }
+        egui::ComboBox::from_id_salt("correlation window")
+            .selected_text(self.window.name())
+            .show_ui(ui, |ui| {
+                for window in StatsWindow::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.window, window, window.name())
+                        .changed();
+                }
+            });
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut self.max_lag_ms, 10.0..=2000.0)
+                    .logarithmic(true)
+                    .text("Max lag (ms)"),
+            )
+            .changed();
+
+        if changed {
+            self.refresh();
+        }
+        if shown {
+            match self.peak() {
+                Some((lag, r)) => {
+                    ui.label(format!("Peak at {lag:.1} ms, r = {r:.2}"));
+                }
+                None => {
+                    ui.label("Not enough overlapping samples");
+                }
+            }
+        }
+    }
+
+    /// Draw the correlation against the lag in the shifted channel's color
+    pub fn draw(&self, ui: &egui::Ui, channels: &Channels, style: &ChartStyle) {
+        let color = channels
+            .get(&self.second)
+            .map_or(egui::Color32::RED, |channel| channel.color);
+        plot::draw_correlation(ui, &self.points, self.peak(), color, style);
+    }
+
+    /// Work it out again on the next update, after a setting changed
+    pub fn refresh(&mut self) {
+        self.last_update = None;
+    }
+
+    /// The lag in ms where the channels are most alike, and the correlation there
+    pub fn peak(&self) -> Option<(f32, f32)> {
+        self.points
+            .iter()
+            .copied()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+/// Seconds between samples on average
+fn sample_period(times: &[f64]) -> Option<f32> {
+    let (first, last) = (times.first()?, times.last()?);
+    (last > first).then(|| ((last - first) / (times.len() - 1) as f64) as f32)
+}
+
+/// The normalized cross-correlation of `a` and `b` for every lag from `-max_lag` to `max_lag`
+/// samples, where a positive lag compares `a` with `b` that many samples later
+fn correlate(a: &[f32], b: &[f32], max_lag: isize) -> Vec<(isize, f32)> {
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len().max(1) as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let a: Vec<f32> = a.iter().map(|value| value - mean_a).collect();
+    let b: Vec<f32> = b.iter().map(|value| value - mean_b).collect();
+    let energy = |values: &[f32]| values.iter().map(|value| value * value).sum::<f32>();
+    let scale = (energy(&a) * energy(&b)).sqrt();
+    if scale <= f32::EPSILON {
+        return Vec::new();
+    }
+
+    let n = a.len() as isize;
+    let max_lag = max_lag.min(n - 1);
+    (-max_lag..=max_lag)
+        .map(|lag| {
+            let sum: f32 = (0.max(-lag)..n.min(n - lag))
+                .map(|i| a[i as usize] * b[(i + lag) as usize])
+                .sum();
+            (lag, sum / scale)
+        })
+        .collect()
+}
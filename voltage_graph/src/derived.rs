@@ -0,0 +1,649 @@
+use eframe::egui;
+use hand_core::{ExponentialMovingAverage, KalmanFilter, PeakHold};
+use serde::{Deserialize, Serialize};
+
+use crate::MAX_SAMPLE_RATE;
+use crate::channel::{Axis, Channel, Channels};
+use crate::tuning;
+
+/// The filters a derived channel can run, with their settings
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum FilterKind {
+    Ema {
+        alpha: f32,
+    },
+    /// How fast the EMA is changing, in ADC counts per sample
+    EmaSlope {
+        alpha: f32,
+        slope_alpha: f32,
+    },
+    /// A level and rate Kalman filter, to compare with the EMA
+    Kalman {
+        process_noise: f32,
+        measurement_noise: f32,
+    },
+    /// Jumps up with the signal and falls back by `degrees_per_second` of a finger's
+    /// 90 degrees, with the samples `sample_rate` a second apart. Presets saved before
+    /// it took degrees get the defaults.
+    PeakHold {
+        #[serde(default = "default_peak_decay")]
+        degrees_per_second: f32,
+        #[serde(default = "default_peak_sample_rate")]
+        sample_rate: f32,
+    },
+}
+
+fn default_peak_decay() -> f32 {
+    20.0
+}
+
+fn default_peak_sample_rate() -> f32 {
+    1000.0
+}
+
+impl FilterKind {
+    /// A peak hold with the default decay, for samples `sample_rate` a second apart
+    pub fn peak_hold(sample_rate: f32) -> Self {
+        Self::PeakHold {
+            degrees_per_second: default_peak_decay(),
+            sample_rate,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ema { .. } => "ema",
+            Self::EmaSlope { .. } => "ema slope",
+            Self::Kalman { .. } => "kalman",
+            Self::PeakHold { .. } => "peak hold",
+        }
+    }
+
+    /// How many samples the filter takes to come up from zero to within 5 % of a steady
+    /// input. The Kalman filter and peak hold follow the data within a sample or two.
+    pub fn settling_samples(&self) -> usize {
+        match *self {
+            Self::Ema { alpha } | Self::EmaSlope { alpha, .. } => {
+                (3.0 / alpha.max(f32::EPSILON)).ceil() as usize
+            }
+            Self::Kalman { .. } | Self::PeakHold { .. } => 0,
+        }
+    }
+
+    /// The serial command that has the firmware smooth with this filter, for the ones
+    /// it can run. It takes its settings in thousandths.
+    pub fn command(&self) -> Option<String> {
+        let thousandths = |setting: f32| (setting * 1000.0).round() as u32;
+        match *self {
+            Self::Ema { alpha } => Some(format!("EMA {}", thousandths(alpha))),
+            Self::Kalman {
+                process_noise,
+                measurement_noise,
+            } => Some(format!(
+                "KALMAN {} {}",
+                thousandths(process_noise),
+                thousandths(measurement_noise)
+            )),
+            Self::EmaSlope { .. } | Self::PeakHold { .. } => None,
+        }
+    }
+
+    fn start(&self) -> Filter {
+        match *self {
+            Self::Ema { alpha } => Filter::Ema(ExponentialMovingAverage::new(alpha)),
+            Self::EmaSlope { alpha, slope_alpha } => {
+                let mut ema = ExponentialMovingAverage::new(alpha);
+                ema.slope_alpha = slope_alpha;
+                Filter::EmaSlope(ema)
+            }
+            Self::Kalman {
+                process_noise,
+                measurement_noise,
+            } => Filter::Kalman(KalmanFilter::new(process_noise, measurement_noise)),
+            Self::PeakHold {
+                degrees_per_second,
+                sample_rate,
+            } => Filter::PeakHold(PeakHold::new(degrees_per_second, sample_rate)),
+        }
+    }
+}
+
+/// A named set of filters to compare, saved between launches
+#[derive(Clone, Deserialize, Serialize)]
+pub struct FilterPreset {
+    pub name: String,
+    pub filters: Vec<FilterKind>,
+}
+
+/// A filter part way through a channel, using the same types as the firmware
+enum Filter {
+    Ema(ExponentialMovingAverage),
+    EmaSlope(ExponentialMovingAverage),
+    Kalman(KalmanFilter),
+    PeakHold(PeakHold),
+}
+
+impl Filter {
+    fn update(&mut self, value: f32) -> f32 {
+        // the firmware filters raw ADC counts
+        let counts = value.clamp(0.0, u16::MAX as f32) as u16;
+        match self {
+            Self::Ema(ema) => ema.update(counts) as f32,
+            Self::EmaSlope(ema) => {
+                ema.update(counts);
+                ema.slope()
+            }
+            Self::Kalman(kalman) => kalman.update(counts) as f32,
+            Self::PeakHold(peak) => peak.update(counts) as f32,
+        }
+    }
+}
+
+/// A channel made by running a filter over another channel
+pub struct DerivedChannel {
+    pub name: String,
+    /// Name of the channel being filtered
+    pub source: String,
+    pub kind: FilterKind,
+    filter: Filter,
+    /// Time of the newest source sample that has been filtered
+    last_time: Option<f64>,
+}
+
+impl DerivedChannel {
+    pub fn new(name: String, source: &str, kind: FilterKind) -> Self {
+        Self {
+            name,
+            source: source.to_owned(),
+            kind,
+            filter: kind.start(),
+            last_time: None,
+        }
+    }
+
+    /// Filter the whole source channel again, after a setting changed
+    pub fn restart(&mut self) {
+        self.filter = self.kind.start();
+        self.last_time = None;
+    }
+
+    /// Let go of a held peak, like the firmware does on a button press
+    pub fn release(&mut self) {
+        if let Filter::PeakHold(peak) = &mut self.filter {
+            peak.release();
+        }
+    }
+
+    /// All of `source` run through a fresh filter, for exports that shouldn't depend on
+    /// when the filter was added or last changed. While the filter is still settling the
+    /// values are NaN when `blank_settling` is set, so they come out as blank cells.
+    pub fn filter_all(&self, source: &Channel, blank_settling: bool) -> Vec<(f64, f32)> {
+        let mut filter = self.kind.start();
+        let settling = if blank_settling {
+            self.kind.settling_samples()
+        } else {
+            0
+        };
+        source
+            .samples
+            .range(..)
+            .enumerate()
+            .map(|(i, &(time, value))| {
+                let filtered = filter.update(value);
+                (time, if i < settling { f32::NAN } else { filtered })
+            })
+            .collect()
+    }
+
+    /// Filter the source samples that arrived since the last update into the derived channel
+    pub fn update(&mut self, channels: &mut Channels) {
+        // the channels were cleared or a file was loaded
+        if channels.get(&self.name).is_none() {
+            self.restart();
+        }
+        let Some(source) = channels.get(&self.source) else {
+            return;
+        };
+        let newest = source.samples.back().map(|&(time, _)| time);
+        if newest < self.last_time {
+            self.restart();
+        }
+
+        let after = self.last_time.unwrap_or(f64::NEG_INFINITY);
+        let first_new = source.samples.partition_point(|&(time, _)| time <= after);
+        let filtered: Vec<(f64, f32)> = source
+            .samples
+            .range(first_new..)
+            .map(|&(time, value)| (time, self.filter.update(value)))
+            .collect();
+
+        if self.last_time.is_none() {
+            channels.replace(&self.name, filtered);
+        } else {
+            for (time, value) in filtered {
+                channels.push(&self.name, time, value);
+            }
+        }
+        self.last_time = newest;
+    }
+}
+
+/// If `channel` holds raw ADC counts from the EMG, not angles or a filter's output
+pub fn is_raw_emg(channel: &Channel, derived: &[DerivedChannel]) -> bool {
+    channel.axis == Axis::Left && !derived.iter().any(|derived| derived.name == channel.name)
+}
+
+/// What is typed and picked for adding filters, and saving them as presets
+pub struct FilterControls {
+    /// The channel new filters are added to
+    source: String,
+    /// Alphas typed in to add an EMA for each, to compare them
+    ema_alphas: String,
+    /// Name typed in for saving the filters as a preset
+    preset_name: String,
+}
+
+impl Default for FilterControls {
+    fn default() -> Self {
+        Self {
+            source: String::new(),
+            ema_alphas: String::from("0.05, 0.15, 0.3"),
+            preset_name: String::new(),
+        }
+    }
+}
+
+impl FilterControls {
+    /// Add, remove and change the settings of the filtered channels in `derived`.
+    /// Peak holds are added at `sample_rate`. Returns a command to send if a filter's
+    /// settings should be sent to the firmware.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        derived: &mut Vec<DerivedChannel>,
+        channels: &mut Channels,
+        sample_rate: f32,
+    ) -> Option<String> {
+        ui.heading("Filters");
+
+        let mut command = None;
+        let mut removed = None;
+        let mut duplicated = None;
+        let mut renamed = None;
+        for (i, filter) in derived.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(filter.name.as_str());
+                if ui.small_button("Duplicate").clicked() {
+                    duplicated = Some(i);
+                }
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                if let Some(send) = filter.kind.command()
+                    && ui
+                        .small_button("Send to hand")
+                        .on_hover_text(format!("Smooth with these settings on the hand: {send}"))
+                        .clicked()
+                {
+                    command = Some(send);
+                }
+            });
+
+            let own_name = filter.name.clone();
+            egui::ComboBox::from_id_salt(("derived source", i))
+                .selected_text(filter.source.as_str())
+                .show_ui(ui, |ui| {
+                    for channel in channels.iter().filter(|channel| channel.name != own_name) {
+                        if ui
+                            .selectable_value(
+                                &mut filter.source,
+                                channel.name.clone(),
+                                channel.name.as_str(),
+                            )
+                            .changed()
+                        {
+                            renamed = Some(i);
+                        }
+                    }
+                });
+            let settings_changed = match &mut filter.kind {
+                FilterKind::Ema { alpha } => ui
+                    .add(egui::Slider::new(alpha, 0.01..=1.0).text("Alpha"))
+                    .changed(),
+                FilterKind::EmaSlope { alpha, slope_alpha } => {
+                    ui.add(egui::Slider::new(alpha, 0.01..=1.0).text("Alpha"))
+                        .changed()
+                        | ui.add(egui::Slider::new(slope_alpha, 0.01..=1.0).text("Slope alpha"))
+                            .on_hover_text("Smooths the slope, 1 leaves it raw")
+                            .changed()
+                }
+                FilterKind::Kalman {
+                    process_noise,
+                    measurement_noise,
+                } => {
+                    ui.add(
+                        egui::Slider::new(process_noise, 0.001..=10.0)
+                            .logarithmic(true)
+                            .text("Process noise"),
+                    )
+                    .on_hover_text("Higher follows changes faster but lets more noise through")
+                    .changed()
+                        | ui.add(
+                            egui::Slider::new(measurement_noise, 1.0..=10_000.0)
+                                .logarithmic(true)
+                                .text("Measurement noise"),
+                        )
+                        .on_hover_text("The variance of the noise on the signal")
+                        .changed()
+                }
+                FilterKind::PeakHold {
+                    degrees_per_second,
+                    sample_rate,
+                } => {
+                    let changed = ui
+                        .add(
+                            egui::Slider::new(degrees_per_second, 0.1..=180.0)
+                                .logarithmic(true)
+                                .text("Decay (°/s)"),
+                        )
+                        .on_hover_text("How fast a finger opens after the contraction, of its 90°")
+                        .changed()
+                        | ui.add(
+                            egui::Slider::new(sample_rate, 10.0..=MAX_SAMPLE_RATE)
+                                .logarithmic(true)
+                                .text("Sample rate (Hz)"),
+                        )
+                        .changed();
+                    if ui.button("Release").clicked() {
+                        filter.release();
+                    }
+                    changed
+                }
+            };
+            if settings_changed {
+                filter.restart();
+            }
+        }
+
+        if let Some(i) = renamed {
+            // the name follows the source, so drop the trace under the old name
+            channels.remove(&derived[i].name);
+            let (source, kind) = (derived[i].source.clone(), derived[i].kind);
+            derived[i].name = String::new();
+            let name = unique_name(derived, channels, &source, kind);
+            derived[i] = DerivedChannel::new(name, &source, kind);
+        }
+        if let Some(i) = duplicated {
+            let (source, kind) = (derived[i].source.clone(), derived[i].kind);
+            add_filter(derived, channels, &source, kind);
+        }
+        if let Some(i) = removed {
+            let filter = derived.remove(i);
+            channels.remove(&filter.name);
+        }
+
+        channels.fall_back(&mut self.source);
+        ui.horizontal_wrapped(|ui| {
+            channels.picker(
+                ui,
+                egui::ComboBox::from_id_salt("filter source"),
+                &mut self.source,
+            );
+            if ui
+                .add_enabled(!self.source.is_empty(), egui::Button::new("Add EMA"))
+                .clicked()
+            {
+                add_filter(
+                    derived,
+                    channels,
+                    &self.source,
+                    FilterKind::Ema { alpha: 0.15 },
+                );
+            }
+            if ui
+                .add_enabled(!self.source.is_empty(), egui::Button::new("Add EMA slope"))
+                .clicked()
+            {
+                let kind = FilterKind::EmaSlope {
+                    alpha: 0.15,
+                    slope_alpha: 0.2,
+                };
+                add_filter(derived, channels, &self.source, kind);
+            }
+            if ui
+                .add_enabled(!self.source.is_empty(), egui::Button::new("Add Kalman"))
+                .on_hover_text("Compare its lag and ripple with the EMA in the table below")
+                .clicked()
+            {
+                let kind = FilterKind::Kalman {
+                    process_noise: 0.05,
+                    measurement_noise: 100.0,
+                };
+                add_filter(derived, channels, &self.source, kind);
+            }
+            if ui
+                .add_enabled(!self.source.is_empty(), egui::Button::new("Add peak hold"))
+                .on_hover_text("Closes with a short contraction and relaxes slowly")
+                .clicked()
+            {
+                add_filter(
+                    derived,
+                    channels,
+                    &self.source,
+                    FilterKind::peak_hold(sample_rate),
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.ema_alphas)
+                    .hint_text("Alphas")
+                    .desired_width(120.0),
+            );
+            let alphas = tuning::parse_alphas(&self.ema_alphas);
+            if ui
+                .add_enabled(
+                    !self.source.is_empty() && !alphas.is_empty(),
+                    egui::Button::new("Add EMAs"),
+                )
+                .on_hover_text("Add an EMA for each alpha, to compare them in the table below")
+                .clicked()
+            {
+                for alpha in alphas {
+                    add_filter(derived, channels, &self.source, FilterKind::Ema { alpha });
+                }
+            }
+        });
+        command
+    }
+
+    /// Save the filters as a named preset in `presets`, or bring a saved one back
+    pub fn preset_controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        derived: &mut Vec<DerivedChannel>,
+        channels: &mut Channels,
+        presets: &mut Vec<FilterPreset>,
+    ) {
+        let mut loaded = None;
+        let mut deleted = None;
+        for (i, preset) in presets.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(preset.name.as_str());
+                if ui.small_button("Load").clicked() {
+                    loaded = Some(i);
+                }
+                if ui.small_button("Delete").clicked() {
+                    deleted = Some(i);
+                }
+            });
+        }
+
+        if let Some(i) = loaded {
+            for filter in derived.drain(..) {
+                channels.remove(&filter.name);
+            }
+            for kind in presets[i].filters.clone() {
+                add_filter(derived, channels, &self.source, kind);
+            }
+        }
+        if let Some(i) = deleted {
+            presets.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.preset_name).hint_text("Preset name"));
+            if ui
+                .add_enabled(
+                    !self.preset_name.is_empty() && !derived.is_empty(),
+                    egui::Button::new("Save preset"),
+                )
+                .clicked()
+            {
+                let preset = FilterPreset {
+                    name: std::mem::take(&mut self.preset_name),
+                    filters: derived.iter().map(|filter| filter.kind).collect(),
+                };
+                // saving under a name that is taken replaces it
+                presets.retain(|existing| existing.name != preset.name);
+                presets.push(preset);
+            }
+        });
+    }
+}
+
+/// Start a new filter on `source`
+pub fn add_filter(
+    derived: &mut Vec<DerivedChannel>,
+    channels: &Channels,
+    source: &str,
+    kind: FilterKind,
+) {
+    let name = unique_name(derived, channels, source, kind);
+    derived.push(DerivedChannel::new(name, source, kind));
+}
+
+/// A name for a filter on `source` that no other channel has, so copies can be told apart
+fn unique_name(
+    derived: &[DerivedChannel],
+    channels: &Channels,
+    source: &str,
+    kind: FilterKind,
+) -> String {
+    let base = format!("{source} {}", kind.name());
+    let taken = |name: &str| {
+        channels.get(name).is_some() || derived.iter().any(|filter| filter.name == name)
+    };
+
+    let mut name = base.clone();
+    let mut copy = 1;
+    while taken(&name) {
+        copy += 1;
+        name = format!("{base} {copy}");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A noisy step, in ADC counts a millisecond apart
+    fn samples() -> Vec<(f64, f32)> {
+        (0..400)
+            .map(|i| {
+                let level = if i < 200 { 100.0 } else { 700.0 };
+                let noise = ((i * 37) % 41) as f32 - 20.0;
+                (i as f64 / 1000.0, level + noise)
+            })
+            .collect()
+    }
+
+    /// What the firmware's filter gives for the samples
+    fn run(mut update: impl FnMut(u16) -> u16) -> Vec<f32> {
+        samples()
+            .into_iter()
+            .map(|(_, value)| update(value as u16) as f32)
+            .collect()
+    }
+
+    /// The derived channel's values, filtered in two goes like samples coming in live
+    fn derive(kind: FilterKind) -> Vec<f32> {
+        let samples = samples();
+        let (first, second) = samples.split_at(250);
+        let mut channels = Channels::new(1000);
+        for &(time, value) in first {
+            channels.push("raw", time, value);
+        }
+        let mut derived = DerivedChannel::new(String::from("filtered"), "raw", kind);
+        derived.update(&mut channels);
+        for &(time, value) in second {
+            channels.push("raw", time, value);
+        }
+        derived.update(&mut channels);
+
+        let filtered = channels.get("filtered").unwrap();
+        filtered
+            .samples
+            .range(..)
+            .map(|&(_, value)| value)
+            .collect()
+    }
+
+    #[test]
+    fn ema_matches_the_firmware() {
+        let mut ema = ExponentialMovingAverage::new(0.15);
+        assert_eq!(
+            derive(FilterKind::Ema { alpha: 0.15 }),
+            run(|x| ema.update(x))
+        );
+    }
+
+    #[test]
+    fn kalman_matches_the_firmware() {
+        let kind = FilterKind::Kalman {
+            process_noise: 0.05,
+            measurement_noise: 100.0,
+        };
+        let mut kalman = KalmanFilter::new(0.05, 100.0);
+        assert_eq!(derive(kind), run(|x| kalman.update(x)));
+    }
+
+    #[test]
+    fn peak_hold_matches_the_firmware() {
+        let mut peak = PeakHold::new(20.0, 1000.0);
+        assert_eq!(
+            derive(FilterKind::peak_hold(1000.0)),
+            run(|x| peak.update(x))
+        );
+    }
+
+    #[test]
+    fn filter_all_matches_updating() {
+        let kind = FilterKind::Ema { alpha: 0.15 };
+        let mut channels = Channels::new(0);
+        channels.load(vec![(String::from("raw"), samples())]);
+        let derived = DerivedChannel::new(String::from("filtered"), "raw", kind);
+        let all: Vec<f32> = derived
+            .filter_all(channels.get("raw").unwrap(), false)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(all, derive(kind));
+    }
+
+    #[test]
+    fn commands_in_thousandths() {
+        let kalman = FilterKind::Kalman {
+            process_noise: 0.05,
+            measurement_noise: 100.0,
+        };
+        assert_eq!(
+            FilterKind::Ema { alpha: 0.15 }.command().as_deref(),
+            Some("EMA 150")
+        );
+        assert_eq!(kalman.command().as_deref(), Some("KALMAN 50 100000"));
+        assert_eq!(FilterKind::peak_hold(1000.0).command(), None);
+    }
+}
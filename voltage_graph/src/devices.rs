@@ -0,0 +1,246 @@
+use std::time::Instant;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::ports::{BAUD_RATES, PortEntry};
+use crate::serial::{ConnectionStatus, PortConfig, SerialEvent, SerialSource};
+
+/// A board read alongside the main connection, like a second hand or a reference
+/// EMG logger. Its channels are named `<name>/<channel>` so they don't clash.
+pub struct Device {
+    pub name: String,
+    pub port_name: String,
+    pub baud_rate: u32,
+    /// Seconds added to this board's times to line it up with the main connection
+    pub offset: f64,
+    pub status: ConnectionStatus,
+    link: Option<SerialSource>,
+}
+
+/// What is saved in a session about each board that was recorded
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DeviceInfo {
+    /// The prefix of the board's channels
+    pub name: String,
+    pub port_name: String,
+    pub baud_rate: u32,
+    /// Seconds that were added to its times
+    pub offset: f64,
+}
+
+impl Device {
+    pub fn new(name: String, baud_rate: u32) -> Self {
+        Self {
+            name,
+            port_name: String::new(),
+            baud_rate,
+            offset: 0.0,
+            status: ConnectionStatus::Disconnected,
+            link: None,
+        }
+    }
+
+    /// Start reading the port with times counted from `start`, the main connection's start
+    pub fn connect(&mut self, start: Instant, ctx: &egui::Context) {
+        let config = PortConfig {
+            port_name: self.port_name.clone(),
+            baud_rate: self.baud_rate,
+            sample_rate: None,
+            auto_reconnect: true,
+        };
+        self.link = Some(SerialSource::open_from(config, start, ctx));
+        self.status = ConnectionStatus::Connecting;
+    }
+
+    pub fn disconnect(&mut self) {
+        self.link = None;
+        self.status = ConnectionStatus::Disconnected;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.link.is_some()
+    }
+
+    /// When this board's time axis starts, if it is connected
+    pub fn start(&self) -> Option<Instant> {
+        self.link.as_ref().map(SerialSource::start)
+    }
+
+    /// What this board's `channel` is called in the graph
+    pub fn channel_name(&self, channel: &str) -> String {
+        format!("{}/{channel}", self.name)
+    }
+
+    /// If `channel` in the graph came from this board
+    pub fn owns(&self, channel: &str) -> bool {
+        channel
+            .strip_prefix(self.name.as_str())
+            .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    /// Events from the board since the last call, with its channels named and the
+    /// offset added to its times. The status is kept up to date from them, and a
+    /// port that keeps failing to come back is only reported the first time.
+    pub fn poll(&mut self) -> Vec<SerialEvent> {
+        let Some(link) = &self.link else {
+            return Vec::new();
+        };
+        let events: Vec<SerialEvent> = link.poll().collect();
+
+        let mut polled = Vec::with_capacity(events.len());
+        for mut event in events {
+            match &mut event {
+                SerialEvent::Connected => self.status = ConnectionStatus::Connected,
+                SerialEvent::Samples { time, values, .. } => {
+                    *time += self.offset;
+                    for (name, _) in values.iter_mut() {
+                        *name = self.channel_name(name);
+                    }
+                }
+                SerialEvent::Text(_) => {}
+                SerialEvent::Reconnecting { reason, .. } => {
+                    let repeat = matches!(self.status, ConnectionStatus::Reconnecting(_));
+                    self.status = ConnectionStatus::Reconnecting(reason.clone());
+                    if repeat {
+                        continue;
+                    }
+                }
+                SerialEvent::Disconnected(reason) => {
+                    self.status = ConnectionStatus::Lost(reason.clone());
+                    self.link = None;
+                }
+            }
+            polled.push(event);
+        }
+        polled
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo {
+            name: self.name.clone(),
+            port_name: self.port_name.clone(),
+            baud_rate: self.baud_rate,
+            offset: self.offset,
+        }
+    }
+}
+
+/// A row for connecting each board in `devices`, and a button to add another at `baud_rate`.
+/// Names can only change while nothing is `streaming`, and boards only connect while
+/// no file is open. Returns the boards whose times were nudged, and by how many seconds.
+pub fn controls(
+    ui: &mut egui::Ui,
+    devices: &mut Vec<Device>,
+    ports: &[PortEntry],
+    baud_rate: u32,
+    streaming: bool,
+    file_open: bool,
+    start: Option<Instant>,
+) -> Vec<(String, f64)> {
+    let mut removed = None;
+    let mut shifts = Vec::new();
+    for (i, device) in devices.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.add_enabled_ui(!device.is_connected(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.add_enabled(
+                        !streaming,
+                        egui::TextEdit::singleline(&mut device.name).desired_width(60.0),
+                    );
+                    egui::ComboBox::from_id_salt("device port")
+                        .selected_text(device.port_name.as_str())
+                        .show_ui(ui, |ui| {
+                            for port in ports {
+                                ui.selectable_value(
+                                    &mut device.port_name,
+                                    port.name.clone(),
+                                    port.name.as_str(),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("device baud")
+                        .selected_text(device.baud_rate.to_string())
+                        .show_ui(ui, |ui| {
+                            for baud_rate in BAUD_RATES {
+                                ui.selectable_value(
+                                    &mut device.baud_rate,
+                                    baud_rate,
+                                    baud_rate.to_string(),
+                                );
+                            }
+                        });
+                });
+            });
+
+            ui.horizontal(|ui| {
+                if device.is_connected() {
+                    if ui.button("Disconnect").clicked() {
+                        device.disconnect();
+                    }
+                } else {
+                    if ui
+                        .add_enabled(
+                            !device.port_name.is_empty() && !file_open,
+                            egui::Button::new("Connect"),
+                        )
+                        .clicked()
+                    {
+                        device.connect(start.unwrap_or_else(Instant::now), ui.ctx());
+                    }
+                    if ui
+                        .add_enabled(!streaming, egui::Button::new("Remove"))
+                        .clicked()
+                    {
+                        removed = Some(i);
+                    }
+                }
+
+                let mut offset_ms = device.offset * 1000.0;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut offset_ms)
+                            .speed(1.0)
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("Nudge this board's times to line them up with the others")
+                    .changed()
+                {
+                    let offset = offset_ms / 1000.0;
+                    shifts.push((device.name.clone(), offset - device.offset));
+                    device.offset = offset;
+                }
+            });
+
+            match &device.status {
+                ConnectionStatus::Disconnected => ui.label("Not connected"),
+                ConnectionStatus::Connecting => ui.label("Connecting..."),
+                ConnectionStatus::Connected => ui.colored_label(
+                    egui::Color32::GREEN,
+                    format!("Connected to {}", device.port_name),
+                ),
+                ConnectionStatus::Reconnecting(reason) => ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!("{reason}, waiting for it to come back..."),
+                ),
+                ConnectionStatus::Lost(reason) => {
+                    ui.colored_label(egui::Color32::RED, reason.as_str())
+                }
+            };
+        });
+    }
+
+    if ui
+        .add_enabled(!streaming, egui::Button::new("Add board"))
+        .on_hover_text("Read another board at the same time, on the same time axis")
+        .clicked()
+    {
+        let name = format!("dev{}", (b'B' + devices.len() as u8) as char);
+        devices.push(Device::new(name, baud_rate));
+    }
+
+    if let Some(i) = removed {
+        devices.remove(i);
+    }
+    shifts
+}
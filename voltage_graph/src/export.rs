@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use eframe::egui;
+
+use crate::channel::Channels;
+use crate::session::Annotation;
+
+/// What goes into CSV exports
+pub struct ExportOptions {
+    /// Add a millivolt column next to each ADC channel
+    pub millivolts: bool,
+    /// Write the timeline markers to a file next to the export
+    pub annotations: bool,
+    /// Channels unticked, so new channels are exported unless left out
+    pub excluded: HashSet<String>,
+    /// Add a column with the classifier's state code for every sample
+    pub states: bool,
+    /// Add a column flagging where contractions start (1) and end (-1)
+    pub contractions: bool,
+    /// Leave filtered values blank until the filter has settled from its start at zero
+    pub blank_settling: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            millivolts: false,
+            annotations: false,
+            excluded: HashSet::new(),
+            states: false,
+            contractions: false,
+            blank_settling: true,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// The export button with which channels and computed columns go in. The
+    /// computed columns come from `classified`. Returns whether export was clicked.
+    pub fn controls(&mut self, ui: &mut egui::Ui, channels: &Channels, classified: &str) -> bool {
+        let mut export = false;
+        ui.horizontal(|ui| {
+            export = ui.button("Export CSV").clicked();
+            ui.checkbox(&mut self.millivolts, "With millivolts");
+            ui.checkbox(&mut self.annotations, "With markers")
+                .on_hover_text("Also write the timeline markers to a .markers.csv file");
+        });
+        egui::CollapsingHeader::new("Columns to export").show(ui, |ui| {
+            for channel in channels.iter() {
+                let mut included = !self.excluded.contains(&channel.name);
+                if ui.checkbox(&mut included, channel.name.as_str()).changed() {
+                    if included {
+                        self.excluded.remove(&channel.name);
+                    } else {
+                        self.excluded.insert(channel.name.clone());
+                    }
+                }
+            }
+            ui.checkbox(&mut self.states, "Classifier state")
+                .on_hover_text(format!(
+                    "The state of {classified} at each of its samples: \
+                     0 relaxed, 1 intermediate, 2 clenched"
+                ));
+            ui.checkbox(&mut self.contractions, "Contraction onsets")
+                .on_hover_text(format!(
+                    "1 where a contraction in {classified} starts, -1 where it ends, 0 elsewhere"
+                ));
+            ui.checkbox(&mut self.blank_settling, "Blank while filters settle")
+                .on_hover_text(
+                    "Filters start from zero, so the first samples of an EMA are left \
+                     blank until it is within 5 % of the data. Cells are also blank \
+                     where a channel has no sample at that row's time.",
+                );
+        });
+        export
+    }
+}
+
+/// Columns of data to be written out, every column is the same length
+pub struct Table {
+    /// The time column's header and then one per value column
+    pub headers: Vec<String>,
+    pub times: Vec<f64>,
+    pub columns: Vec<Vec<f32>>,
+}
+
+impl Table {
+    /// Line up named channels of (time, value) samples on one time column.
+    /// A channel with no sample at a time gets NaN there.
+    pub fn from_channels(channels: &[(String, Vec<(f64, f32)>)]) -> Self {
+        let mut times: Vec<f64> = channels
+            .iter()
+            .flat_map(|(_, samples)| samples.iter().map(|&(time, _)| time))
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+
+        let mut headers = vec![String::from("time_s")];
+        let mut columns = Vec::with_capacity(channels.len());
+        for (name, samples) in channels {
+            // both are in order of time, so walk them together
+            let mut samples = samples.iter().peekable();
+            let column = times
+                .iter()
+                .map(
+                    |&time| match samples.next_if(|&&(sample_time, _)| sample_time <= time) {
+                        Some(&(sample_time, value)) if sample_time == time => value,
+                        _ => f32::NAN,
+                    },
+                )
+                .collect();
+            headers.push(name.clone());
+            columns.push(column);
+        }
+
+        Self {
+            headers,
+            times,
+            columns,
+        }
+    }
+}
+
+/// Write named channels of (time, value) samples to `path` as a CSV on a background thread,
+/// and the markers, when given, to a `.markers.csv` file next to it.
+/// The receiver gets the number of rows written once it is done.
+pub fn export_csv(
+    path: PathBuf,
+    channels: Vec<(String, Vec<(f64, f32)>)>,
+    annotations: Option<Vec<Annotation>>,
+    ctx: &egui::Context,
+) -> Receiver<Result<usize, String>> {
+    let (sender, receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let table = Table::from_channels(&channels);
+        let mut result = write_csv(&path, &table)
+            .map_err(|error| format!("Unable to write {}: {error}", path.display()));
+        if let Some(annotations) = annotations
+            && result.is_ok()
+        {
+            let markers_path = path.with_extension("markers.csv");
+            if let Err(error) = write_annotations(&markers_path, &annotations) {
+                result = Err(format!(
+                    "Unable to write {}: {error}",
+                    markers_path.display()
+                ));
+            }
+        }
+        let _ = sender.send(result);
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+/// Write a header row and then one row per sample, values that are not numbers are left blank
+pub fn write_csv(path: &Path, table: &Table) -> io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "{}", table.headers.join(","))?;
+
+    for (row, time) in table.times.iter().enumerate() {
+        write!(writer, "{time}")?;
+        for column in &table.columns {
+            let value = column[row];
+            if value.is_finite() {
+                write!(writer, ",{value}")?;
+            } else {
+                writer.write_all(b",")?;
+            }
+        }
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+    Ok(table.times.len())
+}
+
+/// Write one row per marker with its time and note, quoting the note so commas in it are kept
+fn write_annotations(path: &Path, annotations: &[Annotation]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "time_s,note")?;
+    for annotation in annotations {
+        writeln!(
+            writer,
+            "{},\"{}\"",
+            annotation.time,
+            annotation.note.replace('"', "\"\"")
+        )?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels() -> Vec<(String, Vec<(f64, f32)>)> {
+        vec![
+            (
+                String::from("raw"),
+                vec![(0.0, 1.0), (0.5, 2.0), (1.0, 3.0)],
+            ),
+            (String::from("motor"), vec![(0.5, 45.0), (1.5, 90.0)]),
+        ]
+    }
+
+    #[test]
+    fn channels_line_up_on_one_time_column() {
+        let table = Table::from_channels(&channels());
+
+        assert_eq!(table.headers, ["time_s", "raw", "motor"]);
+        assert_eq!(table.times, [0.0, 0.5, 1.0, 1.5]);
+        assert_eq!(table.columns[0][..3], [1.0, 2.0, 3.0]);
+        assert!(table.columns[0][3].is_nan());
+        assert!(table.columns[1][0].is_nan());
+        assert_eq!(table.columns[1][1], 45.0);
+        assert!(table.columns[1][2].is_nan());
+        assert_eq!(table.columns[1][3], 90.0);
+    }
+
+    #[test]
+    fn missing_values_are_written_blank() {
+        let path =
+            std::env::temp_dir().join(format!("voltage_graph_export_{}.csv", std::process::id()));
+
+        let rows = write_csv(&path, &Table::from_channels(&channels())).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows, 4);
+        assert_eq!(written, "time_s,raw,motor\n0,1,\n0.5,2,45\n1,3,\n1.5,,90\n");
+    }
+
+    #[test]
+    fn notes_are_quoted() {
+        let path =
+            std::env::temp_dir().join(format!("voltage_graph_markers_{}.csv", std::process::id()));
+        let annotations = [Annotation {
+            time: 1.5,
+            note: String::from("grip, \"hard\""),
+        }];
+
+        write_annotations(&path, &annotations).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, "time_s,note\n1.5,\"grip, \"\"hard\"\"\"\n");
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+
+use eframe::egui;
+
+use crate::viewport::TimeViewport;
+
+/// A step in time this many times longer than usual is a gap
+pub const GAP_FACTOR: f64 = 5.0;
+/// How many recent steps between timestamps the usual step is worked out from
+const STEP_HISTORY: usize = 64;
+/// Steps needed before a long one can be told apart from the usual
+const MIN_STEPS: usize = 8;
+
+/// Why there are no samples in a gap
+#[derive(Clone, Copy)]
+pub enum GapCause {
+    /// The port dropped and came back
+    Reconnect,
+    /// The sequence numbers on the lines skipped this many samples
+    Sequence(u64),
+    /// The firmware's timestamps, or the times in a file, jumped much further than usual
+    Timestamps,
+}
+
+impl GapCause {
+    pub fn describe(self) -> String {
+        match self {
+            Self::Reconnect => String::from("connection lost"),
+            Self::Sequence(missing) => format!("{missing} samples skipped"),
+            Self::Timestamps => String::from("timestamps jumped"),
+        }
+    }
+}
+
+/// A stretch of time with no samples in it, the lines on the plot aren't joined across it
+#[derive(Clone, Copy)]
+pub struct Gap {
+    /// The last sample before it, or when the connection dropped
+    pub lost: f64,
+    /// The first sample after it, infinite until the data comes back
+    pub back: f64,
+    pub cause: GapCause,
+}
+
+impl Gap {
+    pub fn duration(&self) -> f64 {
+        self.back - self.lost
+    }
+}
+
+/// The gaps that overlap `start` to `end`, with how many seconds of them are in it
+pub fn within(gaps: &[Gap], start: f64, end: f64) -> (usize, f64) {
+    gaps.iter()
+        .filter(|gap| gap.lost < end && gap.back > start)
+        .fold((0, 0.0), |(count, seconds), gap| {
+            (count + 1, seconds + gap.back.min(end) - gap.lost.max(start))
+        })
+}
+
+/// A warning if samples are missing between `start` and `end`
+pub fn warning(gaps: &[Gap], start: f64, end: f64) -> Option<String> {
+    let (count, missing) = within(gaps, start, end);
+    (count > 0).then(|| format!("Spans {count} gaps, {missing:.2} s with no samples"))
+}
+
+/// The gaps in the data, to check a contraction isn't really a dropout.
+/// Clicking one centres `viewport` on it.
+pub fn controls(ui: &mut egui::Ui, gaps: &[Gap], viewport: &mut TimeViewport) {
+    ui.heading("Gaps");
+
+    if gaps.is_empty() {
+        ui.label("No samples missing")
+            .on_hover_text("Found from the firmware's seq or timestamp fields, or reconnects");
+        return;
+    }
+    egui::ScrollArea::vertical()
+        .id_salt("gap list")
+        .max_height(120.0)
+        .show(ui, |ui| {
+            for gap in gaps {
+                let duration = if gap.back.is_finite() {
+                    format!("{:.0} ms", gap.duration() * 1000.0)
+                } else {
+                    String::from("ongoing")
+                };
+                if ui
+                    .small_button(format!(
+                        "{:.2} s, {duration}, {}",
+                        gap.lost,
+                        gap.cause.describe()
+                    ))
+                    .on_hover_text("Show on the plot")
+                    .clicked()
+                {
+                    viewport.center_on(gap.lost);
+                }
+            }
+        });
+}
+
+/// Notices samples missing from a live stream, from the sequence numbers on the lines
+/// or a step between the firmware's timestamps much longer than usual
+#[derive(Default)]
+pub struct GapDetector {
+    last_time: Option<f64>,
+    last_sequence: Option<u64>,
+    /// The latest steps between timestamps, leaving out the gaps
+    steps: VecDeque<f64>,
+}
+
+impl GapDetector {
+    /// Forget the stream, after a reconnect or when a new one starts
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Check the next sample, returning the gap before it if samples went missing.
+    /// Times the line arrived at jitter too much to find gaps in, so without a
+    /// `sequence` only `timestamped` times are checked.
+    pub fn push(&mut self, time: f64, timestamped: bool, sequence: Option<u64>) -> Option<Gap> {
+        // the sequence is kept from the very first sample, so a gap straight after it shows
+        let last_sequence = std::mem::replace(&mut self.last_sequence, sequence);
+        let last_time = self.last_time.replace(time)?;
+
+        if let (Some(last), Some(sequence)) = (last_sequence, sequence) {
+            // a sequence number going back means the board restarted
+            let missing = sequence.checked_sub(last + 1)?;
+            return (missing > 0).then_some(Gap {
+                lost: last_time,
+                back: time,
+                cause: GapCause::Sequence(missing),
+            });
+        }
+
+        let step = time - last_time;
+        if !timestamped || step < 0.0 {
+            return None;
+        }
+        if self
+            .usual_step()
+            .is_some_and(|usual| step > usual * GAP_FACTOR)
+        {
+            return Some(Gap {
+                lost: last_time,
+                back: time,
+                cause: GapCause::Timestamps,
+            });
+        }
+        self.steps.push_back(step);
+        if self.steps.len() > STEP_HISTORY {
+            self.steps.pop_front();
+        }
+        None
+    }
+
+    /// The median of the latest steps, `None` until there are enough of them
+    fn usual_step(&self) -> Option<f64> {
+        if self.steps.len() < MIN_STEPS {
+            return None;
+        }
+        let mut steps: Vec<f64> = self.steps.iter().copied().collect();
+        steps.sort_by(f64::total_cmp);
+        Some(steps[steps.len() / 2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples a millisecond apart
+    const STEP: f64 = 0.001;
+
+    /// A detector that has seen enough steady timestamps to know the usual step,
+    /// with the last sample at 0.1 s
+    fn settled() -> GapDetector {
+        let mut detector = GapDetector::default();
+        for index in 0..=100 {
+            assert!(detector.push(index as f64 * STEP, true, None).is_none());
+        }
+        detector
+    }
+
+    #[test]
+    fn a_long_step_is_a_gap() {
+        let mut detector = settled();
+        let gap = detector.push(0.2, true, None).unwrap();
+        assert_eq!((gap.lost, gap.back), (0.1, 0.2));
+        assert!(matches!(gap.cause, GapCause::Timestamps));
+        // and the stream carries on from it
+        assert!(detector.push(0.201, true, None).is_none());
+    }
+
+    #[test]
+    fn steps_below_the_threshold_arent_gaps() {
+        let mut detector = settled();
+        let mut time = 0.1;
+        for factor in [2.0, 4.0, 4.9] {
+            time += STEP * factor;
+            assert!(detector.push(time, true, None).is_none(), "{factor} steps");
+            time += STEP;
+            detector.push(time, true, None);
+        }
+        // the arrival times of untimestamped lines jitter, so they never count
+        assert!(detector.push(time + 1.0, false, None).is_none());
+    }
+
+    #[test]
+    fn back_to_back_gaps_are_both_found() {
+        let mut detector = settled();
+        let first = detector.push(0.2, true, None).unwrap();
+        let second = detector.push(0.3, true, None).unwrap();
+        assert_eq!((first.lost, first.back), (0.1, 0.2));
+        assert_eq!((second.lost, second.back), (0.2, 0.3));
+    }
+
+    #[test]
+    fn a_gap_at_the_start_needs_sequence_numbers() {
+        // before the usual step is known a long one can't be told apart from it
+        let mut detector = GapDetector::default();
+        detector.push(0.0, true, None);
+        assert!(detector.push(1.0, true, None).is_none());
+
+        // sequence numbers show it straight away
+        let mut detector = GapDetector::default();
+        detector.push(0.0, true, Some(0));
+        let gap = detector.push(1.0, true, Some(1000)).unwrap();
+        assert_eq!((gap.lost, gap.back), (0.0, 1.0));
+        assert!(matches!(gap.cause, GapCause::Sequence(999)));
+        // and a board restarting isn't one
+        assert!(detector.push(1.001, true, Some(0)).is_none());
+    }
+
+    #[test]
+    fn a_gap_at_the_end_is_counted_up_to_the_end() {
+        let gaps = [Gap {
+            lost: 8.0,
+            back: f64::INFINITY,
+            cause: GapCause::Reconnect,
+        }];
+        assert_eq!(within(&gaps, 0.0, 10.0), (1, 2.0));
+        assert_eq!(within(&gaps, 0.0, 8.0), (0, 0.0));
+        assert!(warning(&gaps, 9.0, 10.0).is_some());
+    }
+}
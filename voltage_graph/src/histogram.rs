@@ -0,0 +1,189 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::channel::{Channel, Channels};
+use crate::plot;
+use crate::stats::StatsWindow;
+use crate::theme::ChartStyle;
+use crate::units::{Scale, Units};
+
+/// How often the histogram is counted again, it doesn't need to keep up with every frame
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How the values of one channel are spread out, to see where thresholds cut them
+pub struct HistogramView {
+    /// Name of the channel to count
+    pub channel: String,
+    pub window: StatsWindow,
+    pub bin_count: usize,
+    /// Draw the counts on a log scale, so rare values still show
+    pub log_scale: bool,
+    /// Samples in each bin
+    pub counts: Vec<usize>,
+    /// The value the first bin starts at
+    low: f32,
+    bin_width: f32,
+    /// The bin that was clicked on
+    pub selected: Option<usize>,
+    last_update: Option<Instant>,
+}
+
+impl Default for HistogramView {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            window: StatsWindow::LastFiveSeconds,
+            bin_count: 50,
+            log_scale: false,
+            counts: Vec::new(),
+            low: 0.0,
+            bin_width: 1.0,
+            selected: None,
+            last_update: None,
+        }
+    }
+}
+
+impl HistogramView {
+    /// Count the samples of `channel` from `start` to `end` seconds, shown through `scale`,
+    /// if it has been long enough since the last time
+    pub fn update(&mut self, channel: &Channel, (start, end): (f64, f64), scale: Scale) {
+        if self
+            .last_update
+            .is_some_and(|last| last.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        let values: Vec<f32> = channel
+            .between(start, end)
+            .map(|&(_, value)| scale.apply(value))
+            .collect();
+        let low = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let high = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        if !low.is_finite() || !high.is_finite() {
+            self.counts.clear();
+            return;
+        }
+
+        let bin_count = self.bin_count.max(1);
+        self.low = low;
+        // a flat signal still gets bins with some width
+        self.bin_width = ((high - low) / bin_count as f32).max(f32::EPSILON);
+        self.counts = vec![0; bin_count];
+        for value in values {
+            let bin = ((value - low) / self.bin_width) as usize;
+            self.counts[bin.min(bin_count - 1)] += 1;
+        }
+    }
+
+    /// Count the chosen channel over `span`, on the first channel when the chosen one
+    /// isn't there. With no span, like no selection to count over, the bars are cleared.
+    pub fn follow(&mut self, channels: &Channels, span: Option<(f64, f64)>, units: &Units) {
+        channels.fall_back(&mut self.channel);
+        if let Some(channel) = channels.get(&self.channel) {
+            match span {
+                Some(span) => self.update(channel, span, units.scale(channel)),
+                None => self.counts.clear(),
+            }
+        }
+    }
+
+    /// The channel, window and bins, with what the clicked bar holds while it is `shown`
+    pub fn controls(&mut self, ui: &mut egui::Ui, channels: &Channels, shown: bool) {
+        ui.heading("Histogram");
+
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= channels.picker(
+                ui,
+                egui::ComboBox::from_id_salt("histogram channel"),
+                &mut self.channel,
+            );
+            egui::ComboBox::from_id_salt("histogram window")
+                .selected_text(self.window.name())
+                .show_ui(ui, |ui| {
+                    for window in StatsWindow::ALL {
+                        changed |= ui
+                            .selectable_value(&mut self.window, window, window.name())
+                            .changed();
+                    }
+                });
+        });
+        changed |= ui
+            .add(egui::Slider::new(&mut self.bin_count, 5..=200).text("Bins"))
+            .changed();
+        ui.checkbox(&mut self.log_scale, "Log scale");
+
+        if changed {
+            self.refresh();
+        }
+        if shown {
+            match self.selected {
+                Some(bin) if bin < self.counts.len() => {
+                    let (start, end) = self.bin_range(bin);
+                    ui.label(format!(
+                        "{start:.2} to {end:.2}: {} samples",
+                        self.counts[bin]
+                    ));
+                }
+                _ => {
+                    ui.label("Click a bar to see its range and count");
+                }
+            }
+        }
+    }
+
+    /// Draw the bars with the `thresholds` on them. Clicking picks a bar.
+    pub fn draw(
+        &mut self,
+        ui: &mut egui::Ui,
+        channels: &Channels,
+        thresholds: &[f32],
+        value_label: &str,
+        style: &ChartStyle,
+    ) {
+        let color = channels
+            .get(&self.channel)
+            .map_or(egui::Color32::RED, |channel| channel.color);
+        let x_pixels = plot::draw_histogram(ui, self, thresholds, color, value_label, style);
+
+        let rect = ui.max_rect();
+        let response = ui.interact(rect, ui.id().with("histogram bars"), egui::Sense::click());
+        if response.clicked()
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            let values = self.values();
+            let pixel_width = (x_pixels.end - x_pixels.start).max(1) as f32;
+            let fraction = (pointer.x - rect.left() - x_pixels.start as f32) / pixel_width;
+            let value = values.start + fraction * (values.end - values.start);
+            self.selected = self.bin_at(value);
+        }
+    }
+
+    /// Count again on the next update, after a setting changed
+    pub fn refresh(&mut self) {
+        self.last_update = None;
+        self.selected = None;
+    }
+
+    /// The values covered by every bin
+    pub fn values(&self) -> Range<f32> {
+        self.low..self.low + self.bin_width * self.counts.len() as f32
+    }
+
+    /// The values covered by `bin`, from its start to its end
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        let start = self.low + self.bin_width * bin as f32;
+        (start, start + self.bin_width)
+    }
+
+    /// The bin `value` falls in, `None` if it is outside all of them
+    pub fn bin_at(&self, value: f32) -> Option<usize> {
+        let bin = ((value - self.low) / self.bin_width).floor();
+        (bin >= 0.0 && (bin as usize) < self.counts.len()).then_some(bin as usize)
+    }
+}
@@ -0,0 +1,536 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use eframe::egui;
+
+use crate::gaps::GAP_FACTOR;
+use crate::session::{Annotation, SessionMetadata};
+
+/// How many rows are parsed between progress updates
+const PROGRESS_INTERVAL: usize = 100_000;
+/// How many malformed line numbers are kept for the report
+const MAX_REPORTED_LINES: usize = 10;
+
+/// The first line of a CSV file, used to choose which columns to load
+#[derive(Clone)]
+pub struct CsvPreview {
+    pub path: PathBuf,
+    /// Column names, or `column 1`, `column 2`... when the file has no header row
+    pub headers: Vec<String>,
+    pub has_header: bool,
+    pub time_column: usize,
+    /// Which columns to load as channels, one per header
+    pub load_columns: Vec<bool>,
+    /// The session metadata, empty for a plain CSV
+    pub metadata: SessionMetadata,
+}
+
+impl CsvPreview {
+    /// Read the start of `path` and guess which column holds the time
+    pub fn read(path: PathBuf) -> Result<Self, String> {
+        let (metadata, first_line) = read_preamble(&path)
+            .map_err(|error| format!("Unable to read {}: {error}", path.display()))?;
+        let fields: Vec<&str> = first_line.trim().split(',').map(str::trim).collect();
+
+        // a header row is one where something isn't a number
+        let has_header = fields.iter().any(|field| field.parse::<f32>().is_err());
+        let headers = if has_header {
+            fields.iter().map(|field| field.to_string()).collect()
+        } else {
+            (1..=fields.len()).map(|i| format!("column {i}")).collect()
+        };
+
+        let time_column = fields
+            .iter()
+            .position(|field| field.to_ascii_lowercase().starts_with("time"))
+            .unwrap_or(0);
+        let load_columns = (0..fields.len())
+            .map(|column| column != time_column)
+            .collect();
+
+        Ok(Self {
+            path,
+            headers,
+            has_header,
+            time_column,
+            load_columns,
+            metadata: metadata.unwrap_or_default(),
+        })
+    }
+
+    /// If the columns match what this app exports, so there is nothing to ask the user
+    pub fn is_known_layout(&self) -> bool {
+        self.has_header
+            && self
+                .headers
+                .first()
+                .is_some_and(|header| header == "time_s")
+    }
+
+    /// A window for picking which column holds the time and which to load as channels.
+    /// Returns `Some(true)` when import is clicked and `Some(false)` when it is cancelled.
+    pub fn mapping_window(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let mut import = false;
+        let mut cancel = false;
+        egui::Window::new("Import CSV")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Choose which columns to load");
+                egui::ComboBox::from_label("Time (s)")
+                    .selected_text(self.headers[self.time_column].as_str())
+                    .show_ui(ui, |ui| {
+                        for (i, header) in self.headers.iter().enumerate() {
+                            ui.selectable_value(&mut self.time_column, i, header.as_str());
+                        }
+                    });
+                for (i, header) in self.headers.iter().enumerate() {
+                    if i != self.time_column {
+                        ui.checkbox(&mut self.load_columns[i], header.as_str());
+                    }
+                }
+                ui.checkbox(&mut self.has_header, "First row is a header");
+
+                ui.horizontal(|ui| {
+                    import = ui.button("Import").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        (import || cancel).then_some(import)
+    }
+}
+
+/// What was loaded from a CSV file
+pub struct Imported {
+    /// Named channels of (time, value) samples, in order of time
+    pub channels: Vec<(String, Vec<(f64, f32)>)>,
+    pub metadata: SessionMetadata,
+    /// Markers saved in the session file
+    pub annotations: Vec<Annotation>,
+    pub report: ImportReport,
+}
+
+/// Problems found while loading a CSV file
+pub struct ImportReport {
+    pub path: PathBuf,
+    /// How many files the session was split over, 1 for most
+    pub parts: usize,
+    /// How many rows could not be read or went back in time
+    pub malformed_rows: usize,
+    /// Line numbers of the first few malformed rows
+    pub malformed_lines: Vec<usize>,
+    /// Spans of time, (start, end), with no samples in them
+    pub gaps: Vec<(f64, f64)>,
+}
+
+impl ImportReport {
+    /// What was loaded, and what in it couldn't be
+    pub fn summary(&self, ui: &mut egui::Ui) {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        if self.parts > 1 {
+            ui.label(format!(
+                "Showing {name} and the rest of its {} parts",
+                self.parts
+            ));
+        } else {
+            ui.label(format!("Showing {name}"));
+        }
+        if self.malformed_rows > 0 {
+            let lines: Vec<String> = self.malformed_lines.iter().map(usize::to_string).collect();
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "{} malformed rows skipped (lines {}...)",
+                    self.malformed_rows,
+                    lines.join(", ")
+                ),
+            );
+        }
+        if !self.gaps.is_empty() {
+            let longest = self
+                .gaps
+                .iter()
+                .map(|(start, end)| end - start)
+                .fold(0.0, f64::max);
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "{} gaps in the data, the longest is {longest:.3} s",
+                    self.gaps.len()
+                ),
+            );
+        }
+    }
+}
+
+/// Messages from the thread reading a file
+pub enum ImportEvent {
+    /// How much of the file has been read, from 0 to 1
+    Progress(f32),
+    /// Boxed since a whole recording is much bigger than a progress update
+    Done(Result<Box<Imported>, String>),
+}
+
+/// Load the columns chosen in `preview` on a background thread
+pub fn start_import(preview: &CsvPreview, ctx: &egui::Context) -> Receiver<ImportEvent> {
+    let (sender, receiver) = mpsc::channel();
+    let preview = preview.clone();
+    let ctx = ctx.clone();
+
+    thread::spawn(move || {
+        let result = load(&preview, |fraction| {
+            let _ = sender.send(ImportEvent::Progress(fraction));
+            ctx.request_repaint();
+        });
+        let _ = sender.send(ImportEvent::Done(result.map(Box::new)));
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+/// Load the columns chosen in `preview`, telling `progress` how much of the file has been read.
+/// A file that is one part of a split recording is loaded along with the other parts
+/// in its folder, one after another on the shared time axis.
+pub fn load(preview: &CsvPreview, progress: impl Fn(f32)) -> Result<Imported, String> {
+    let parts = session_parts(preview);
+    let path = &preview.path;
+    let value_columns: Vec<(usize, String)> = preview
+        .headers
+        .iter()
+        .enumerate()
+        .filter(|&(column, _)| column != preview.time_column && preview.load_columns[column])
+        .map(|(column, header)| (column, header.clone()))
+        .collect();
+
+    let mut rows = Rows {
+        times: Vec::new(),
+        channels: vec![Vec::new(); value_columns.len()],
+        malformed_rows: 0,
+        malformed_lines: Vec::new(),
+        annotations: Vec::new(),
+    };
+    for (i, part) in parts.iter().enumerate() {
+        // every part is written with the same columns as the first
+        read_samples(
+            part,
+            preview.has_header,
+            preview.time_column,
+            &value_columns,
+            &mut rows,
+            |fraction| progress((i as f32 + fraction) / parts.len() as f32),
+        )
+        .map_err(|error| format!("Unable to read {}: {error}", part.display()))?;
+    }
+    // a marker can be written into more than one part
+    rows.annotations
+        .sort_by(|a, b| a.time.total_cmp(&b.time).then_with(|| a.note.cmp(&b.note)));
+    rows.annotations
+        .dedup_by(|a, b| a.time == b.time && a.note == b.note);
+
+    let mut metadata = preview.metadata.clone();
+    metadata.part = None;
+    Ok(Imported {
+        report: ImportReport {
+            path: path.clone(),
+            parts: parts.len(),
+            malformed_rows: rows.malformed_rows,
+            malformed_lines: rows.malformed_lines,
+            gaps: find_gaps(&rows.times),
+        },
+        channels: value_columns
+            .into_iter()
+            .map(|(_, name)| name)
+            .zip(rows.channels)
+            .collect(),
+        metadata,
+        annotations: rows.annotations,
+    })
+}
+
+/// The files of the session `preview` is part of, in order. Just `preview`'s own file
+/// when it isn't part of a split recording, or the folder can't be read.
+fn session_parts(preview: &CsvPreview) -> Vec<PathBuf> {
+    let id = &preview.metadata.session_id;
+    let folder = preview.path.parent().unwrap_or(Path::new("."));
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) if !id.is_empty() => entries,
+        _ => return vec![preview.path.clone()],
+    };
+
+    let mut parts: Vec<(usize, PathBuf)> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "csv"))
+        .filter_map(|path| {
+            let (metadata, _) = read_preamble(&path).ok()?;
+            let metadata = metadata.filter(|metadata| metadata.session_id == *id)?;
+            Some((metadata.part.unwrap_or(1), path))
+        })
+        .collect();
+    parts.sort_by_key(|&(part, _)| part);
+    if parts.is_empty() {
+        return vec![preview.path.clone()];
+    }
+    parts.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Everything parsed out of the rows of a file
+struct Rows {
+    /// The time of every row that was read
+    times: Vec<f64>,
+    /// (time, value) samples for each value column, blank cells are left out
+    channels: Vec<Vec<(f64, f32)>>,
+    /// How many rows could not be read
+    malformed_rows: usize,
+    /// Line numbers of the first few malformed rows, in whichever part they are
+    malformed_lines: Vec<usize>,
+    annotations: Vec<Annotation>,
+}
+
+/// Read the session metadata, if there is any, and the first line of the CSV after it
+fn read_preamble(path: &Path) -> io::Result<(Option<SessionMetadata>, String)> {
+    let mut metadata = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.starts_with('#') {
+            metadata = metadata.or_else(|| SessionMetadata::from_line(&line));
+        } else if !line.trim().is_empty() {
+            return Ok((metadata, line));
+        }
+    }
+    Ok((metadata, String::new()))
+}
+
+/// Parse every row of `path` onto the end of `rows`, reading the time and each of `value_columns`
+fn read_samples(
+    path: &Path,
+    has_header: bool,
+    time_column: usize,
+    value_columns: &[(usize, String)],
+    rows: &mut Rows,
+    progress: impl Fn(f32),
+) -> io::Result<()> {
+    let file = File::open(path)?;
+    let total_bytes = file.metadata()?.len().max(1) as f32;
+    let reader = BufReader::new(file);
+
+    let mut bytes_read = 0;
+    let mut header_skipped = !has_header;
+    let mut values = Vec::with_capacity(value_columns.len());
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        bytes_read += line.len() + 1;
+
+        if index % PROGRESS_INTERVAL == 0 {
+            progress(bytes_read as f32 / total_bytes);
+        }
+
+        // metadata, markers and comments
+        if line.starts_with('#') {
+            rows.annotations.extend(Annotation::from_line(&line));
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !header_skipped {
+            header_skipped = true;
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let last_time = rows.times.last().copied().unwrap_or(f64::NEG_INFINITY);
+        let time = fields
+            .get(time_column)
+            .and_then(|field| field.parse::<f64>().ok())
+            // time has to keep going forward for the plot to find samples by time
+            .filter(|&time| time.is_finite() && time > last_time);
+
+        // a blank cell is a channel with no sample on this row, anything else has to be a number
+        values.clear();
+        let mut row_ok = time.is_some();
+        for (column, _) in value_columns {
+            match fields.get(*column).copied().unwrap_or_default() {
+                "" => values.push(None),
+                field => match field.parse::<f32>() {
+                    Ok(value) if value.is_finite() => values.push(Some(value)),
+                    _ => row_ok = false,
+                },
+            }
+        }
+
+        match time {
+            Some(time) if row_ok => {
+                rows.times.push(time);
+                for (channel, value) in rows.channels.iter_mut().zip(&values) {
+                    if let Some(value) = value {
+                        channel.push((time, *value));
+                    }
+                }
+            }
+            _ => {
+                rows.malformed_rows += 1;
+                if rows.malformed_lines.len() < MAX_REPORTED_LINES {
+                    rows.malformed_lines.push(index + 1);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Find where the time between rows is much longer than usual
+fn find_gaps(times: &[f64]) -> Vec<(f64, f64)> {
+    let mut steps: Vec<f64> = times
+        .windows(2)
+        .take(10_000)
+        .map(|pair| pair[1] - pair[0])
+        .collect();
+    if steps.is_empty() {
+        return Vec::new();
+    }
+    steps.sort_by(f64::total_cmp);
+    let usual_step = steps[steps.len() / 2];
+
+    times
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > usual_step * GAP_FACTOR)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{Table, write_csv};
+    use crate::session::{Recorder, Rotation};
+
+    /// A folder of its own for each test, so the parts of one can't be mixed up with another
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!(
+            "voltage_graph_import_{name}_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn load_file(path: PathBuf) -> Imported {
+        load(&CsvPreview::read(path).unwrap(), |_| {}).unwrap()
+    }
+
+    #[test]
+    fn exported_csv_loads_back() {
+        let folder = temp_folder("export");
+        let path = folder.join("export.csv");
+        let channels = vec![
+            (
+                String::from("raw"),
+                vec![(0.0, 512.0), (0.01, 498.0), (0.02, 505.0)],
+            ),
+            (String::from("motor"), vec![(0.01, 43.0)]),
+        ];
+        write_csv(&path, &Table::from_channels(&channels)).unwrap();
+
+        let preview = CsvPreview::read(path.clone()).unwrap();
+        assert!(preview.is_known_layout());
+        assert_eq!(preview.time_column, 0);
+        assert_eq!(preview.load_columns, [false, true, true]);
+
+        let imported = load_file(path);
+        fs::remove_dir_all(&folder).unwrap();
+        // the blank motor cells are left out rather than read as samples
+        assert_eq!(imported.channels, channels);
+        assert_eq!(imported.report.malformed_rows, 0);
+        assert!(imported.report.gaps.is_empty());
+    }
+
+    #[test]
+    fn plain_csv_without_a_header() {
+        let folder = temp_folder("plain");
+        let path = folder.join("plain.csv");
+        fs::write(&path, "0,1,10\n1,2,20\n").unwrap();
+
+        let preview = CsvPreview::read(path).unwrap();
+        assert!(!preview.has_header);
+        assert!(!preview.is_known_layout());
+        assert_eq!(preview.headers, ["column 1", "column 2", "column 3"]);
+
+        let imported = load(&preview, |_| {}).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(imported.channels[0].1, [(0.0, 1.0), (1.0, 2.0)]);
+        assert_eq!(imported.channels[1].1, [(0.0, 10.0), (1.0, 20.0)]);
+    }
+
+    #[test]
+    fn malformed_rows_and_gaps_are_reported() {
+        let folder = temp_folder("malformed");
+        let path = folder.join("malformed.csv");
+        let mut csv = String::from("time_s,raw\n");
+        for i in 0..20 {
+            csv += &format!("{},{i}\n", i as f64 / 10.0);
+        }
+        // not a number, time going back, and then a second with nothing in it
+        csv += "2.0,oops\n1.5,7\n3.0,8\n";
+        fs::write(&path, csv).unwrap();
+
+        let imported = load_file(path);
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(imported.channels[0].1.len(), 21);
+        assert_eq!(imported.report.malformed_rows, 2);
+        assert_eq!(imported.report.malformed_lines, [22, 23]);
+        assert_eq!(imported.report.gaps.len(), 1);
+        let (start, end) = imported.report.gaps[0];
+        assert!((start - 1.9).abs() < 1e-4 && end == 3.0);
+    }
+
+    #[test]
+    fn split_session_loads_every_part() {
+        let folder = temp_folder("parts");
+        let rotation = Rotation {
+            enabled: true,
+            minutes: 1.0,
+            megabytes: 100.0,
+        };
+        let metadata = SessionMetadata {
+            notes: String::from("left forearm"),
+            ..Default::default()
+        };
+        let marker = Annotation {
+            time: 90.0,
+            note: String::from("grip"),
+        };
+
+        let mut recorder = Recorder::create(folder.join("long.csv"), &metadata, rotation).unwrap();
+        for second in 0..150 {
+            let values = [(String::from("raw"), second as f32)];
+            recorder
+                .record(second as f64, &values, std::slice::from_ref(&marker))
+                .unwrap();
+        }
+        recorder.finish(std::slice::from_ref(&marker)).unwrap();
+
+        // opening a later part still brings in the whole session
+        let imported = load_file(folder.join("long.part2.csv"));
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(imported.report.parts, 3);
+        assert_eq!(imported.metadata.notes, "left forearm");
+        assert_eq!(imported.metadata.part, None);
+        let raw = &imported.channels[0].1;
+        assert_eq!(raw.len(), 150);
+        assert!(
+            raw.iter()
+                .enumerate()
+                .all(|(i, &(time, _))| time == i as f64)
+        );
+        // written into more than one part, loaded once
+        assert_eq!(imported.annotations.len(), 1);
+    }
+}
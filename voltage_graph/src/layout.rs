@@ -0,0 +1,153 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::viewport::{LinkedPanes, PANES, TimeViewport};
+
+/// What a pane of the main area can show. They all use the same channels and time window.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum View {
+    /// The channels against time, with the angle pane when it is turned on
+    Time,
+    Spectrum,
+    Spectrogram,
+    Histogram,
+    /// A table of statistics for every channel
+    Statistics,
+    /// Cross-correlation of two channels against lag
+    Correlation,
+    /// A table of the contractions found by the classifier, with their timing
+    Contractions,
+}
+
+impl View {
+    pub const ALL: [Self; 7] = [
+        Self::Time,
+        Self::Spectrum,
+        Self::Spectrogram,
+        Self::Histogram,
+        Self::Statistics,
+        Self::Correlation,
+        Self::Contractions,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Time => "Time",
+            Self::Spectrum => "Spectrum",
+            Self::Spectrogram => "Spectrogram",
+            Self::Histogram => "Histogram",
+            Self::Statistics => "Statistics",
+            Self::Correlation => "Correlation",
+            Self::Contractions => "Contractions",
+        }
+    }
+
+    /// If it is drawn against time, so it can be zoomed and linked with the other pane
+    pub fn has_time_axis(self) -> bool {
+        matches!(self, Self::Time | Self::Spectrogram)
+    }
+}
+
+/// How the main area is divided
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Split {
+    /// One view fills it
+    Single,
+    SideBySide,
+    /// One view above the other
+    Stacked,
+}
+
+impl Split {
+    pub const ALL: [Self; 3] = [Self::Single, Self::SideBySide, Self::Stacked];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Single => "Single",
+            Self::SideBySide => "Side by side",
+            Self::Stacked => "Stacked",
+        }
+    }
+}
+
+/// Which views are shown in the main area and where
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Layout {
+    /// The view picked with the tabs, on the left or at the top when split
+    pub first: View,
+    /// The other view when split
+    pub second: View,
+    pub split: Split,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            first: View::Time,
+            second: View::Spectrum,
+            split: Split::Single,
+        }
+    }
+}
+
+impl Layout {
+    /// The view in each pane on screen, in order
+    pub fn views(&self) -> Vec<View> {
+        match self.split {
+            Split::Single => vec![self.first],
+            Split::SideBySide | Split::Stacked => vec![self.first, self.second],
+        }
+    }
+
+    /// If `view` is on screen, so it needs to be kept up to date
+    pub fn shows(&self, view: View) -> bool {
+        self.first == view || (self.split != Split::Single && self.second == view)
+    }
+
+    /// Tabs to pick the views and whether the main area is split in two. A pane with a
+    /// time axis can be linked to `viewport` with `link`.
+    pub fn tabs(&mut self, ui: &mut egui::Ui, link: &mut LinkedPanes, viewport: &mut TimeViewport) {
+        ui.horizontal(|ui| {
+            for view in View::ALL {
+                ui.selectable_value(&mut self.first, view, view.name());
+            }
+            ui.separator();
+            egui::ComboBox::from_id_salt("split")
+                .selected_text(self.split.name())
+                .show_ui(ui, |ui| {
+                    for split in Split::ALL {
+                        ui.selectable_value(&mut self.split, split, split.name());
+                    }
+                });
+            if self.split != Split::Single {
+                egui::ComboBox::from_id_salt("second view")
+                    .selected_text(self.second.name())
+                    .show_ui(ui, |ui| {
+                        for view in View::ALL {
+                            ui.selectable_value(&mut self.second, view, view.name());
+                        }
+                    });
+
+                let names = match self.split {
+                    Split::SideBySide => ["Link left", "Link right"],
+                    _ => ["Link top", "Link bottom"],
+                };
+                for (pane, view) in [self.first, self.second].into_iter().enumerate() {
+                    if !view.has_time_axis() {
+                        continue;
+                    }
+                    let mut linked = link.is_linked(pane);
+                    if ui
+                        .checkbox(&mut linked, names[pane])
+                        .on_hover_text("Zoom and pan with the other pane and share its cursor")
+                        .changed()
+                    {
+                        link.set_linked(pane, linked, PANES, viewport);
+                    }
+                }
+            }
+        });
+        ui.separator();
+    }
+}
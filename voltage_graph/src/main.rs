@@ -1,6 +1,123 @@
-use eframe::egui::{self, CentralPanel, Id, SidePanel, Visuals};
-use egui_plotter::EguiBackend;
-use plotters::prelude::*;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Instant;
+
+use eframe::egui::{self, CentralPanel, Id, SidePanel};
+use hand_core::{LeadOffDetector, LeadOffLimits};
+
+mod artifacts;
+mod axes;
+mod batch;
+mod channel;
+mod classify;
+mod clipping;
+mod contractions;
+mod correlation;
+mod derived;
+mod devices;
+mod export;
+mod gaps;
+mod histogram;
+mod import;
+mod layout;
+mod noise;
+mod pacing;
+mod playback;
+mod plot;
+mod ports;
+mod rate;
+mod report;
+mod ring_buffer;
+mod serial;
+mod session;
+mod settings;
+mod simulator;
+mod spectrogram;
+mod spectrum;
+mod stats;
+mod terminal;
+mod theme;
+mod toast;
+mod trigger;
+mod tuning;
+mod units;
+mod viewport;
+
+use artifacts::ArtifactScan;
+use axes::{AutoRange, ManualRange};
+use batch::{BatchConfig, BatchEvent};
+use channel::{Axis, Channel, Channels};
+use classify::Classification;
+use clipping::ClipBanner;
+use contractions::ContractionView;
+use correlation::CorrelationView;
+use derived::{DerivedChannel, FilterControls};
+use devices::{Device, DeviceInfo};
+use export::ExportOptions;
+use gaps::{Gap, GapCause, GapDetector};
+use histogram::HistogramView;
+use import::{CsvPreview, ImportEvent, ImportReport};
+use layout::{Split, View};
+use noise::NoiseCheck;
+use pacing::{FramePacing, LIVE_REPAINT_INTERVAL};
+use playback::Playback;
+use plot::{Axes, ImageExport, Overlay, PlotArea};
+use ports::{BAUD_RATES, PortEntry};
+use rate::RateMeter;
+use report::{ChannelSummary, MARKER_IMAGE_SECONDS, MarkedImage, SessionReport, StateSummary};
+use serial::{ConnectionStatus, PortConfig, SerialEvent, SerialSource, UdpConfig};
+use session::{Annotation, Recorder, SessionMetadata};
+use settings::Settings;
+use simulator::{SIMULATOR_CHANNEL, SimulatorSource};
+use spectrogram::Spectrogram;
+use spectrum::SpectrumView;
+use stats::{Stats, StatsView, StatsWindow};
+use terminal::{TERMINAL_PANEL, Terminal};
+use theme::Theme;
+use toast::Toasts;
+use trigger::Trigger;
+use tuning::FilterComparison;
+use units::ValueAxis;
+use viewport::{LinkedPanes, TimeViewport};
+
+/// The fastest the hand is expected to send samples, used to size the history
+const MAX_SAMPLE_RATE: f32 = 1000.0;
+/// The most memory the sample history can take up, shared between the channels
+const MAX_HISTORY_BYTES: usize = 512 * 1024 * 1024;
+/// The longest history that can be picked, if the memory allows it
+const MAX_HISTORY_SECONDS: f32 = 4.0 * 60.0 * 60.0;
+/// Id of the side panel, egui remembers its size between sessions
+const SOURCES_PANEL: &str = "Graph sources";
+/// How close in pixels the pointer has to be to a threshold line to drag it
+const GRAB_DISTANCE: f32 = 6.0;
+/// How much of the height the EMG pane gets when the angles have their own
+const EMG_PANE_FRACTION: f32 = 0.6;
+/// Height of each row of the state lane under the plot, in points
+const STATE_LANE_ROW_HEIGHT: f32 = 14.0;
+/// How much of the window the arrow keys pan by
+const PAN_STEP: f64 = 0.1;
+/// How much the plus and minus keys zoom by
+const ZOOM_STEP: f32 = 1.25;
+/// Keys and what they do, for the help window
+const SHORTCUTS: [(&str, &str); 11] = [
+    ("Space", "Pause or resume"),
+    ("L", "Back to live"),
+    ("Left / Right", "Pan back and forward in time"),
+    ("+ / -", "Zoom in and out"),
+    ("M", "Drop a marker at the pointer"),
+    ("N", "Add a timeline marker"),
+    ("1 to 9", "Show or hide a channel"),
+    ("S", "Export CSV"),
+    ("R", "Start or stop recording"),
+    ("F12", "Show frame timing"),
+    ("?", "Show this list"),
+];
+/// How far the measured sample rate can be from the set one before it is flagged
+const RATE_TOLERANCE: f32 = 0.03;
+/// Seconds of each raw EMG channel checked for a loose electrode, long enough for
+/// the detector to go off and come back on
+const LEAD_OFF_WINDOW: f64 = 2.0;
 
 fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
@@ -19,96 +136,2502 @@ fn main() -> color_eyre::Result<()> {
     Ok(())
 }
 
-enum Theme {
-    Light,
-    Dark,
+/// `seconds` in the biggest unit that reads well, like "90 s", "12 min" or "2.5 h"
+fn format_duration(seconds: f32) -> String {
+    if seconds < 120.0 {
+        format!("{seconds:.0} s")
+    } else if seconds < 2.0 * 60.0 * 60.0 {
+        format!("{:.0} min", seconds / 60.0)
+    } else {
+        format!("{:.1} h", seconds / 3600.0)
+    }
+}
+
+/// Where samples come from
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Serial,
+    /// The firmware's EMG simulator, run in the app
+    Simulator,
+    /// Telemetry lines sent over UDP, like from the wireless bridge
+    Network,
+    /// A CSV file
+    File,
+}
+
+impl Source {
+    const ALL: [Self; 4] = [Self::Serial, Self::Simulator, Self::Network, Self::File];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Serial => "Serial",
+            Self::Simulator => "Simulator",
+            Self::Network => "Network (UDP)",
+            Self::File => "File",
+        }
+    }
 }
 
 struct VisualGraph {
-    theme: Theme,
-    voltage_data: Vec<(f32, f32)>,
-    frequency: f32,
-    amplitude: f32,
-    phase: f32,
+    /// Every named stream of samples, as (seconds since connecting, value)
+    channels: Channels,
+    /// Scale each channel to fill the plot, to compare their shapes
+    normalized: bool,
+    /// Plot the channels on the right axis, like servo angles, in a pane under the EMG
+    split_angles: bool,
+    /// Channels made by filtering other channels
+    derived: Vec<DerivedChannel>,
+    filter_controls: FilterControls,
+    /// How the filters respond to a step, to compare them
+    comparison: FilterComparison,
+    /// Spans that look like motion artifacts
+    artifacts: ArtifactScan,
+    /// The part of the history shown on the plot
+    viewport: TimeViewport,
+    /// Which panes follow `viewport`, and the cursor line they share
+    link: LinkedPanes,
+    left_range: AutoRange,
+    right_range: AutoRange,
+    manual_left: ManualRange,
+    manual_right: ManualRange,
+    spectrum: SpectrumView,
+    spectrogram: Spectrogram,
+    histogram: HistogramView,
+    correlation: CorrelationView,
+    contractions: ContractionView,
+    /// What the statistics in the side panel are taken over
+    stats: StatsView,
+    /// A span of time dragged out on the plot, as (where the drag started, where it is now)
+    selection: Option<(f64, f64)>,
+    /// A point clicked on the plot, as (time, value), the readout measures from it
+    marker: Option<(f64, f32)>,
+    /// Where the pointer is on the plot, as (time, value)
+    cursor: Option<(f64, f32)>,
+    /// Notes on moments of the session, in order of time
+    annotations: Vec<Annotation>,
+    /// Show the list of keyboard shortcuts
+    show_shortcuts: bool,
+    /// Timing of recent frames, shown in a corner with F12
+    pacing: FramePacing,
+    /// Live samples taken in since the last frame
+    frame_samples: usize,
+    /// Freezes the plot around a level crossing
+    trigger: Trigger,
+    /// The classifier the plot is shaded by
+    classification: Classification,
+    noise: NoiseCheck,
+    clip_banner: ClipBanner,
+    /// Which threshold line is being dragged, 0 for intermediate and 1 for clenched
+    dragged_threshold: Option<usize>,
+    source: Source,
+    simulator: SimulatorSource,
+    settings: Settings,
+    ports: Vec<PortEntry>,
+    serial: Option<SerialSource>,
+    /// Other boards read at the same time as the main connection
+    devices: Vec<Device>,
+    status: ConnectionStatus,
+    /// The sample rate of timestamped serial data
+    measured_rate: RateMeter,
+    /// Where samples are missing, in order of time
+    gaps: Vec<Gap>,
+    /// Finds gaps in the serial data as it comes in
+    gap_detector: GapDetector,
+    /// Commands sent over the serial connection and the firmware's replies
+    terminal: Terminal,
+    toasts: Toasts,
+    /// A CSV export running in the background
+    export: Option<Receiver<Result<usize, String>>>,
+    /// What goes into CSV exports
+    export_options: ExportOptions,
+    /// Width and height of exported images, in pixels
+    image_size: (u32, u32),
+    /// An image of the plot to save when it is next drawn
+    image_export: Option<ImageExport>,
+    /// A CSV file waiting for the user to pick which columns to load
+    import_preview: Option<CsvPreview>,
+    /// A CSV file being loaded in the background, and how far along it is
+    import: Option<(Receiver<ImportEvent>, f32)>,
+    /// A folder of recordings being processed in the background, with how far along it is
+    batch: Option<(Receiver<BatchEvent>, f32, String)>,
+    /// Write every file in a batch out again with the filtered channels added
+    batch_write_files: bool,
+    /// The file being shown instead of live data
+    loaded_file: Option<ImportReport>,
+    /// Where the loaded file is being played back from
+    playback: Option<Playback>,
+    /// About the live session, or the loaded file
+    metadata: SessionMetadata,
+    /// Where incoming samples are being saved
+    recorder: Option<Recorder>,
 }
 
 impl VisualGraph {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let context = &cc.egui_ctx;
-        context.set_visuals(Visuals::dark());
+        let mut settings = Settings::load(cc.storage);
+        cc.egui_ctx.set_visuals(settings.theme.visuals());
+        let mut channels = Channels::new(Self::history_capacity(settings.history_seconds));
+        channels.remember_colors(settings.channel_colors());
+        let ports = ports::list_ports();
+        Self::pick_port(&mut settings.port_name, &ports);
 
-        let frequency = 1.0;
-        let amplitude = 1.0;
-        let phase = 0.0;
+        Self {
+            channels,
+            normalized: false,
+            split_angles: false,
+            derived: Vec::new(),
+            filter_controls: FilterControls::default(),
+            comparison: FilterComparison::default(),
+            artifacts: ArtifactScan::default(),
+            viewport: TimeViewport::new(10.0),
+            link: LinkedPanes::default(),
+            left_range: AutoRange::default(),
+            right_range: AutoRange::default(),
+            manual_left: ManualRange::default(),
+            manual_right: ManualRange::default(),
+            spectrum: SpectrumView::default(),
+            spectrogram: Spectrogram::default(),
+            histogram: HistogramView::default(),
+            correlation: CorrelationView::default(),
+            contractions: ContractionView::default(),
+            stats: StatsView::default(),
+            selection: None,
+            marker: None,
+            cursor: None,
+            annotations: Vec::new(),
+            show_shortcuts: false,
+            pacing: FramePacing::default(),
+            frame_samples: 0,
+            trigger: Trigger::default(),
+            classification: Classification::default(),
+            noise: NoiseCheck::default(),
+            clip_banner: ClipBanner::default(),
+            dragged_threshold: None,
+            source: Source::Serial,
+            simulator: SimulatorSource::new(42, 500.0),
+            settings,
+            ports,
+            serial: None,
+            devices: Vec::new(),
+            status: ConnectionStatus::Disconnected,
+            measured_rate: RateMeter::default(),
+            gaps: Vec::new(),
+            gap_detector: GapDetector::default(),
+            terminal: Terminal::default(),
+            toasts: Toasts::default(),
+            export: None,
+            export_options: ExportOptions::default(),
+            image_size: (1600, 900),
+            image_export: None,
+            import_preview: None,
+            import: None,
+            batch: None,
+            batch_write_files: false,
+            loaded_file: None,
+            playback: None,
+            metadata: SessionMetadata::default(),
+            recorder: None,
+        }
+    }
 
-        let voltage_data = Self::generate_waveform(frequency, amplitude, phase);
+    /// How many samples fit in `seconds` of history
+    fn history_capacity(seconds: f32) -> usize {
+        (seconds * MAX_SAMPLE_RATE) as usize
+    }
 
-        Self {
-            theme: Theme::Dark,
-            voltage_data,
-            frequency,
-            amplitude,
-            phase,
+    /// The longest history that fits in `MAX_HISTORY_BYTES` with the channels there are now
+    fn history_limit(&self) -> f32 {
+        let channels = self.channels.iter().count().max(1);
+        let bytes_per_second = channels as f32 * MAX_SAMPLE_RATE * size_of::<(f64, f32)>() as f32;
+        (MAX_HISTORY_BYTES as f32 / bytes_per_second).min(MAX_HISTORY_SECONDS)
+    }
+
+    /// Memory the history takes up once it is full, in bytes
+    fn history_bytes(&self) -> usize {
+        let channels = self.channels.iter().count().max(1);
+        channels * Self::history_capacity(self.settings.history_seconds) * size_of::<(f64, f32)>()
+    }
+
+    /// Change how much history is kept. The buffers are resized between frames,
+    /// so samples waiting to be read are still added afterwards.
+    fn set_history(&mut self, seconds: f32) {
+        self.settings.history_seconds = seconds;
+        self.channels
+            .set_capacity(Self::history_capacity(self.settings.history_seconds));
+        self.viewport.width = self
+            .viewport
+            .width
+            .min(self.settings.history_seconds as f64);
+    }
+
+    /// Shorten the history if new channels would take it over the memory limit
+    fn limit_history(&mut self) {
+        let limit = self.history_limit();
+        if self.loaded_file.is_none() && self.settings.history_seconds > limit {
+            self.set_history(limit);
+        }
+    }
+
+    /// Keep the chosen port if it is still plugged in, otherwise pick the most likely Arduino
+    fn pick_port(port_name: &mut String, ports: &[PortEntry]) {
+        if ports.iter().any(|port| port.name == *port_name) {
+            return;
+        }
+        if let Some(port) = ports.first() {
+            *port_name = port.name.clone();
+        }
+    }
+
+    /// Move everything the serial thread has sent into the graph
+    fn read_serial(&mut self) {
+        let Some(serial) = &self.serial else {
+            return;
+        };
+
+        let events: Vec<SerialEvent> = serial.poll().collect();
+        let mut closed = false;
+        for event in events {
+            match event {
+                SerialEvent::Connected => self.status = ConnectionStatus::Connected,
+                SerialEvent::Samples {
+                    time,
+                    values,
+                    timestamped,
+                    sequence,
+                } => {
+                    if let Some(gap) = self.gaps.last_mut()
+                        && gap.back.is_infinite()
+                    {
+                        gap.back = time;
+                    }
+                    if let Some(gap) = self.gap_detector.push(time, timestamped, sequence) {
+                        self.gaps.push(gap);
+                    }
+                    if timestamped {
+                        self.measured_rate.push(time);
+                    }
+                    let values = self.name_main_channels(values);
+                    self.receive(time, &values);
+                }
+                SerialEvent::Text(line) => self.terminal.received(line),
+                SerialEvent::Reconnecting { time, reason } => {
+                    // the reader keeps trying while the port is there but won't open
+                    if !matches!(self.status, ConnectionStatus::Reconnecting(_)) {
+                        self.toasts.error(reason.as_str());
+                        self.gaps.push(Gap {
+                            lost: time,
+                            back: f64::INFINITY,
+                            cause: GapCause::Reconnect,
+                        });
+                        self.measured_rate.clear();
+                        self.gap_detector.reset();
+                    }
+                    self.status = ConnectionStatus::Reconnecting(reason);
+                }
+                SerialEvent::Disconnected(reason) => {
+                    self.toasts.error(reason.as_str());
+                    self.status = ConnectionStatus::Lost(reason);
+                    closed = true;
+                }
+            }
+        }
+
+        if closed {
+            self.serial = None;
+        }
+    }
+
+    /// Move the samples the simulator has made since last frame into the graph
+    fn read_simulator(&mut self, ctx: &egui::Context) {
+        if !self.simulator.is_running() {
+            return;
+        }
+        for (time, value) in self.simulator.tick() {
+            self.receive(time, &[(String::from(SIMULATOR_CHANNEL), value)]);
+        }
+        ctx.request_repaint_after(LIVE_REPAINT_INTERVAL);
+    }
+
+    /// Add a row of live samples to the channels and the recording
+    fn receive(&mut self, time: f64, values: &[(String, f32)]) {
+        self.frame_samples += 1;
+        for (name, value) in values {
+            self.channels.push(name, time, *value);
+            self.trigger.push(name, time, *value);
+        }
+        if let Some(recorder) = &mut self.recorder
+            && let Err(error) = recorder.record(time, values, &self.annotations)
+        {
+            self.toasts.error(format!(
+                "Recording stopped, {} is kept up to its last whole row: {error}",
+                recorder.path().display()
+            ));
+            self.recorder = None;
+        }
+    }
+
+    /// Once other boards are added, the main connection's channels are named after it too
+    fn name_main_channels(&self, values: Vec<(String, f32)>) -> Vec<(String, f32)> {
+        if self.devices.is_empty() {
+            return values;
+        }
+        let device = &self.settings.device_name;
+        values
+            .into_iter()
+            .map(|(name, value)| (format!("{device}/{name}"), value))
+            .collect()
+    }
+
+    /// Move everything the other boards have sent into the graph
+    fn read_devices(&mut self) {
+        let mut events = Vec::new();
+        for device in &mut self.devices {
+            let name = device.name.clone();
+            events.extend(device.poll().into_iter().map(|event| (name.clone(), event)));
+        }
+
+        for (name, event) in events {
+            match event {
+                SerialEvent::Samples { time, values, .. } => self.receive(time, &values),
+                SerialEvent::Text(line) => self.terminal.received(format!("{name}: {line}")),
+                SerialEvent::Reconnecting { reason, .. } | SerialEvent::Disconnected(reason) => {
+                    self.toasts.error(format!("{name}: {reason}"));
+                }
+                SerialEvent::Connected => {}
+            }
+        }
+    }
+
+    /// When the time axis of whichever board is connected starts, so another one can share it
+    fn link_start(&self) -> Option<Instant> {
+        self.serial
+            .as_ref()
+            .map(SerialSource::start)
+            .or_else(|| self.devices.iter().find_map(Device::start))
+    }
+
+    fn source_controls(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Source");
+
+        let file_open = self.loaded_file.is_some() || self.import.is_some();
+        let streaming = self.serial.is_some()
+            || self.simulator.is_running()
+            || self.devices.iter().any(Device::is_connected);
+        ui.add_enabled_ui(!file_open && !streaming, |ui| {
+            let previous = self.source;
+            egui::ComboBox::from_id_salt("source")
+                .selected_text(self.source.name())
+                .show_ui(ui, |ui| {
+                    for source in Source::ALL {
+                        ui.selectable_value(&mut self.source, source, source.name());
+                    }
+                });
+            // the sources each start their own time axis
+            if self.source != previous {
+                self.channels.clear();
+                self.gaps.clear();
+                self.annotations.clear();
+                self.simulator.restart();
+                self.status = ConnectionStatus::Disconnected;
+            }
+        });
+
+        match self.source {
+            Source::Serial => self.connection_controls(ui),
+            Source::Simulator => {
+                let file_open = self.loaded_file.is_some() || self.import.is_some();
+                if self
+                    .simulator
+                    .controls(ui, file_open, &mut self.metadata.sample_rate)
+                {
+                    self.channels.clear();
+                }
+            }
+            Source::Network => self.network_controls(ui),
+            Source::File => self.file_controls(ui),
+        }
+    }
+
+    /// Start reading from `link`, on a fresh time axis unless other boards are still connected
+    fn start_link(&mut self, link: SerialSource) {
+        if !self.devices.iter().any(Device::is_connected) {
+            self.channels.clear();
+            self.gaps.clear();
+            self.annotations.clear();
+        }
+        self.measured_rate.clear();
+        self.gap_detector.reset();
+        self.metadata.sample_rate = self.settings.nominal_sample_rate();
+        self.serial = Some(link);
+        self.status = ConnectionStatus::Connecting;
+    }
+
+    /// The sample rate setting shared by the serial and network sources
+    fn sample_rate_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.fixed_sample_rate, "Sample rate")
+                .on_hover_text(
+                    "Space samples at this rate instead of by when they arrive. \
+                     Timestamps from the firmware are used over either.",
+                );
+            ui.add_enabled(
+                self.settings.fixed_sample_rate,
+                egui::DragValue::new(&mut self.settings.sample_rate)
+                    .range(1.0..=MAX_SAMPLE_RATE)
+                    .suffix(" Hz"),
+            );
+        });
+    }
+
+    fn network_controls(&mut self, ui: &mut egui::Ui) {
+        let listening = self.serial.is_some();
+        let file_open = self.loaded_file.is_some() || self.import.is_some();
+        ui.add_enabled_ui(!listening && !file_open, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("UDP port");
+                ui.add(egui::DragValue::new(&mut self.settings.udp_port).range(1..=u16::MAX));
+            });
+            self.sample_rate_controls(ui);
+        });
+
+        if listening {
+            if ui.button("Stop listening").clicked() {
+                self.serial = None;
+                self.status = ConnectionStatus::Disconnected;
+            }
+        } else if ui
+            .add_enabled(!file_open, egui::Button::new("Listen"))
+            .on_hover_text("Take telemetry lines sent to this port, the same as over serial")
+            .clicked()
+        {
+            let config = UdpConfig {
+                port: self.settings.udp_port,
+                sample_rate: self.settings.nominal_sample_rate(),
+            };
+            self.start_link(SerialSource::listen(config, ui.ctx()));
+        }
+
+        self.link_status(ui);
+    }
+
+    /// What the serial or network source is connected to
+    fn link_description(&self) -> String {
+        match self.source {
+            Source::Network => format!("Listening on UDP port {}", self.settings.udp_port),
+            _ => format!(
+                "Connected to {} at {} baud",
+                self.settings.port_name, self.settings.baud_rate
+            ),
+        }
+    }
+
+    fn connection_controls(&mut self, ui: &mut egui::Ui) {
+        let connected = self.serial.is_some();
+        let file_open = self.loaded_file.is_some() || self.import.is_some();
+        ui.add_enabled_ui(!connected && !file_open, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Port")
+                    .selected_text(self.settings.port_name.as_str())
+                    .show_ui(ui, |ui| {
+                        for port in &self.ports {
+                            let mut text =
+                                egui::RichText::new(format!("{} {}", port.name, port.description));
+                            if port.likely_arduino {
+                                text = text.strong().color(ui.visuals().warn_fg_color);
+                            }
+                            ui.selectable_value(
+                                &mut self.settings.port_name,
+                                port.name.clone(),
+                                text,
+                            );
+                        }
+                    });
+
+                if ui.button("Refresh").clicked() {
+                    self.ports = ports::list_ports();
+                    Self::pick_port(&mut self.settings.port_name, &self.ports);
+                }
+            });
+
+            egui::ComboBox::from_label("Baud")
+                .selected_text(self.settings.baud_rate.to_string())
+                .show_ui(ui, |ui| {
+                    for baud_rate in BAUD_RATES {
+                        ui.selectable_value(
+                            &mut self.settings.baud_rate,
+                            baud_rate,
+                            baud_rate.to_string(),
+                        );
+                    }
+                });
+
+            self.sample_rate_controls(ui);
+
+            ui.checkbox(
+                &mut self.settings.auto_reconnect,
+                "Reconnect if the port drops",
+            )
+            .on_hover_text("Turn off when unplugging on purpose to switch boards");
+        });
+
+        if connected {
+            if ui.button("Disconnect").clicked() {
+                self.serial = None;
+                self.status = ConnectionStatus::Disconnected;
+            }
+        } else if ui
+            .add_enabled(
+                !self.settings.port_name.is_empty() && !file_open,
+                egui::Button::new("Connect"),
+            )
+            .clicked()
+        {
+            let config = PortConfig {
+                port_name: self.settings.port_name.clone(),
+                baud_rate: self.settings.baud_rate,
+                sample_rate: self.settings.nominal_sample_rate(),
+                auto_reconnect: self.settings.auto_reconnect,
+            };
+            let start = self.link_start().unwrap_or_else(Instant::now);
+            self.start_link(SerialSource::open_from(config, start, ui.ctx()));
+        }
+
+        self.link_status(ui);
+
+        ui.separator();
+        self.device_controls(ui);
+    }
+
+    /// Other boards read alongside the main connection, each with its channels named after it
+    fn device_controls(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Other boards");
+
+        // the channel names depend on whether there are other boards, so they only
+        // change while nothing is streaming
+        let streaming = self.serial.is_some() || self.devices.iter().any(Device::is_connected);
+        let file_open = self.loaded_file.is_some() || self.import.is_some();
+        ui.add_enabled_ui(!streaming, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("This board's name");
+                ui.text_edit_singleline(&mut self.settings.device_name);
+            });
+        });
+
+        let start = self.link_start();
+        let shifts = devices::controls(
+            ui,
+            &mut self.devices,
+            &self.ports,
+            self.settings.baud_rate,
+            streaming,
+            file_open,
+            start,
+        );
+        for (name, seconds) in shifts {
+            self.shift_device(&name, seconds);
+        }
+    }
+
+    /// Move every sample the board called `name` has sent by `seconds`
+    fn shift_device(&mut self, name: &str, seconds: f64) {
+        let Some(device) = self.devices.iter().find(|device| device.name == name) else {
+            return;
+        };
+        let owned: Vec<String> = self
+            .channels
+            .iter()
+            .filter(|channel| device.owns(&channel.name))
+            .map(|channel| channel.name.clone())
+            .collect();
+        for channel in &owned {
+            let Some(samples) = self.channels.get(channel).map(|channel| {
+                channel
+                    .samples
+                    .range(..)
+                    .map(|&(time, value)| (time + seconds, value))
+                    .collect()
+            }) else {
+                continue;
+            };
+            self.channels.replace(channel, samples);
+        }
+        // filters of the moved channels start again from the moved samples
+        for derived in &mut self.derived {
+            if owned.contains(&derived.source) {
+                derived.restart();
+            }
+        }
+    }
+
+    /// How the serial or network connection is doing, and the terminal and rate that go with it
+    fn link_status(&mut self, ui: &mut egui::Ui) {
+        let connected = self.serial.is_some();
+        match &self.status {
+            ConnectionStatus::Disconnected
+                if self.source == Source::Serial && self.ports.is_empty() =>
+            {
+                ui.label("No serial ports found");
+            }
+            ConnectionStatus::Disconnected => {
+                ui.label("Not connected");
+            }
+            ConnectionStatus::Connecting => {
+                ui.label("Connecting...");
+            }
+            ConnectionStatus::Connected => {
+                ui.colored_label(egui::Color32::GREEN, self.link_description());
+            }
+            ConnectionStatus::Reconnecting(reason) => {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!("{reason}, waiting for it to come back..."),
+                );
+            }
+            ConnectionStatus::Lost(reason) => {
+                ui.colored_label(egui::Color32::RED, reason.as_str());
+            }
+        }
+
+        if let Some(serial) = &self.serial {
+            let malformed = serial.malformed_lines();
+            if malformed > 0 {
+                ui.label(format!("{malformed} damaged lines"))
+                    .on_hover_text("Telemetry lines that were cut off or garbled, their unreadable values were dropped");
+            }
+        }
+
+        if !self.gaps.is_empty() {
+            let (count, missing) = gaps::within(&self.gaps, f64::NEG_INFINITY, self.newest_time());
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("{count} gaps, {missing:.2} s with no samples"),
+            )
+            .on_hover_text("Listed under Gaps");
+        }
+
+        ui.checkbox(&mut self.terminal.show, "Show terminal");
+
+        if connected && let Some(measured) = self.measured_rate.rate() {
+            match self.settings.nominal_sample_rate() {
+                Some(nominal) if (measured - nominal).abs() > nominal * RATE_TOLERANCE => {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("Measured {measured:.1} Hz, expected {nominal:.0} Hz"),
+                    );
+                }
+                _ => {
+                    ui.label(format!("Measured {measured:.1} Hz"));
+                }
+            }
+        }
+    }
+
+    fn data_controls(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Data");
+
+        if self.export.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Exporting...");
+            });
+        } else {
+            let classified = &self.classification.channel;
+            if self.export_options.controls(ui, &self.channels, classified) {
+                self.start_export(ui.ctx());
+            }
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Export image").clicked() {
+                self.start_image_export();
+            }
+            ui.add(egui::DragValue::new(&mut self.image_size.0).range(200..=8000));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut self.image_size.1).range(200..=8000));
+        });
+        if ui
+            .button("Generate report")
+            .on_hover_text("A Markdown summary of the session, with a plot of each marker")
+            .clicked()
+        {
+            self.generate_report();
+        }
+    }
+
+    /// Open a CSV file and show what was found in it
+    fn file_controls(&mut self, ui: &mut egui::Ui) {
+        if let Some((_, progress)) = &self.import {
+            ui.add(
+                egui::ProgressBar::new(*progress)
+                    .show_percentage()
+                    .text("Loading"),
+            );
+        } else if ui.button("Open CSV").clicked() {
+            self.open_csv(ui.ctx());
+        }
+
+        if let Some(report) = &self.loaded_file {
+            report.summary(ui);
+            if ui.button("Close file").clicked() {
+                self.loaded_file = None;
+                self.playback = None;
+                self.metadata = SessionMetadata::default();
+                self.annotations.clear();
+                self.channels.clear();
+                self.channels
+                    .set_capacity(Self::history_capacity(self.settings.history_seconds));
+                self.viewport.go_live();
+            }
+        }
+
+        ui.separator();
+        if let Some((_, progress, file)) = &self.batch {
+            ui.add(
+                egui::ProgressBar::new(*progress)
+                    .show_percentage()
+                    .text(file.as_str()),
+            );
+        } else if ui
+            .button("Batch process folder")
+            .on_hover_text(
+                "Run the filters and thresholds over every CSV in a folder \
+                 and write a summary row for each, with each filter's lag and ripple",
+            )
+            .clicked()
+        {
+            self.start_batch(ui.ctx());
+        }
+        ui.checkbox(
+            &mut self.batch_write_files,
+            "Save each file with its filters",
+        );
+    }
+
+    /// Ask for a folder and process every recording in it on another thread
+    fn start_batch(&mut self, ctx: &egui::Context) {
+        let Some(folder) = self.file_dialog().pick_folder() else {
+            return;
+        };
+        self.settings.last_directory = Some(folder.clone());
+
+        let config = BatchConfig {
+            folder,
+            filters: self
+                .derived
+                .iter()
+                .map(|derived| (derived.name.clone(), derived.source.clone(), derived.kind))
+                .collect(),
+            thresholds: self.classification.thresholds,
+            classify_channel: self.classification.channel.clone(),
+            write_files: self.batch_write_files,
+        };
+        self.batch = Some((batch::start_batch(config, ctx), 0.0, String::new()));
+    }
+
+    /// Keep track of a running batch and say how it went once it is done
+    fn check_batch(&mut self) {
+        let Some((receiver, progress, current)) = &mut self.batch else {
+            return;
+        };
+
+        let mut result = None;
+        for event in receiver.try_iter() {
+            match event {
+                BatchEvent::Progress { done, total, file } => {
+                    *progress = done as f32 / total.max(1) as f32;
+                    *current = file;
+                }
+                BatchEvent::Done(done) => result = Some(done),
+            }
+        }
+
+        match result {
+            Some(Ok(report)) => {
+                let summary = report.summary.display();
+                self.toasts.info(format!(
+                    "Processed {} files, summary in {summary}",
+                    report.processed
+                ));
+                for (file, error) in &report.failed {
+                    self.toasts.error(format!("Skipped {file}: {error}"));
+                }
+            }
+            Some(Err(error)) => self.toasts.error(error),
+            None => return,
+        }
+        self.batch = None;
+    }
+
+    fn session_controls(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Session");
+
+        // a loaded file's metadata is what was recorded, so it can't be changed
+        let editable = self.loaded_file.is_none() && self.recorder.is_none();
+        self.metadata.controls(ui, editable);
+
+        if let Some(recorder) = &self.recorder {
+            let name = recorder
+                .path()
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            ui.colored_label(egui::Color32::RED, format!("Recording to {name}"));
+            if ui.button("Stop recording").clicked() {
+                self.stop_recording();
+            }
+        } else {
+            self.settings.rotation.controls(ui);
+            if ui
+                .add_enabled(
+                    self.loaded_file.is_none(),
+                    egui::Button::new("Start recording"),
+                )
+                .clicked()
+            {
+                self.start_recording();
+            }
+        }
+    }
+
+    /// A file dialog that starts where the last file was
+    fn file_dialog(&self) -> rfd::FileDialog {
+        let dialog = rfd::FileDialog::new();
+        match &self.settings.last_directory {
+            Some(directory) => dialog.set_directory(directory),
+            None => dialog,
+        }
+    }
+
+    fn remember_directory(&mut self, path: &Path) {
+        self.settings.last_directory = path.parent().map(Path::to_path_buf);
+    }
+
+    /// Ask where to save and start writing incoming samples to a session file
+    fn start_recording(&mut self) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("Session", &["csv"])
+            .set_file_name("session.csv")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        self.metadata.started = chrono::Local::now().to_rfc3339();
+        // filled in from the first samples that arrive, unless other boards are connected,
+        // their samples come separately so the columns are taken from what is streaming
+        self.metadata.channels.clear();
+        self.metadata.devices.clear();
+        if self.devices.iter().any(Device::is_connected) {
+            self.metadata.channels = self
+                .channels
+                .iter()
+                .map(|channel| channel.name.clone())
+                .filter(|name| self.derived.iter().all(|derived| derived.name != *name))
+                .collect();
+            self.metadata.devices.push(DeviceInfo {
+                name: self.settings.device_name.clone(),
+                port_name: self.settings.port_name.clone(),
+                baud_rate: self.settings.baud_rate,
+                offset: 0.0,
+            });
+            self.metadata
+                .devices
+                .extend(self.devices.iter().map(Device::info));
+        }
+
+        match Recorder::create(path, &self.metadata, self.settings.rotation) {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(error) => self
+                .toasts
+                .error(format!("Unable to start recording: {error}")),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        let path = recorder.path().display().to_string();
+        let part = recorder.part();
+        match recorder.finish(&self.annotations) {
+            Ok(samples) => self.toasts.info(match part {
+                Some(parts) => {
+                    format!("Saved {samples} samples in {parts} parts, the last is {path}")
+                }
+                None => format!("Saved {samples} samples to {path}"),
+            }),
+            Err(error) => self.toasts.error(format!("Unable to save {path}: {error}")),
+        }
+    }
+
+    /// Ask for a CSV file, and load it right away if its columns are already known
+    fn open_csv(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.file_dialog().add_filter("CSV", &["csv"]).pick_file() else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        match CsvPreview::read(path) {
+            Ok(preview) if preview.is_known_layout() => self.start_import(&preview, ctx),
+            Ok(preview) => self.import_preview = Some(preview),
+            Err(error) => self.toasts.error(error),
+        }
+    }
+
+    fn start_import(&mut self, preview: &CsvPreview, ctx: &egui::Context) {
+        // a file replaces the live data, so stop reading it
+        self.stop_recording();
+        self.serial = None;
+        self.status = ConnectionStatus::Disconnected;
+        self.simulator.stop();
+        self.source = Source::File;
+        self.import = Some((import::start_import(preview, ctx), 0.0));
+    }
+
+    /// Let the user pick which column holds the time and which to load as channels
+    fn import_mapping_window(&mut self, ctx: &egui::Context) {
+        let Some(preview) = &mut self.import_preview else {
+            return;
+        };
+        match preview.mapping_window(ctx) {
+            Some(true) => {
+                if let Some(preview) = self.import_preview.take() {
+                    self.start_import(&preview, ctx);
+                }
+            }
+            Some(false) => self.import_preview = None,
+            None => {}
+        }
+    }
+
+    /// Show progress of a file being loaded and swap it in once it is done
+    fn check_import(&mut self) {
+        let Some((receiver, progress)) = &mut self.import else {
+            return;
+        };
+
+        let mut result = None;
+        for event in receiver.try_iter() {
+            match event {
+                ImportEvent::Progress(fraction) => *progress = fraction,
+                ImportEvent::Done(done) => result = Some(done),
+            }
+        }
+
+        match result {
+            Some(Ok(imported)) => {
+                let samples: usize = imported
+                    .channels
+                    .iter()
+                    .map(|(_, samples)| samples.len())
+                    .sum();
+                self.toasts.info(format!(
+                    "Loaded {samples} samples in {} channels",
+                    imported.channels.len()
+                ));
+                self.channels.load(imported.channels);
+                self.gaps = imported
+                    .report
+                    .gaps
+                    .iter()
+                    .map(|&(lost, back)| Gap {
+                        lost,
+                        back,
+                        cause: GapCause::Timestamps,
+                    })
+                    .collect();
+                self.metadata = imported.metadata;
+                self.annotations = imported.annotations;
+                self.loaded_file = Some(imported.report);
+                // start showing the whole recording, ready to be played back
+                let (_, last) = self.data_span();
+                self.playback = Some(Playback::new(last));
+                self.viewport.go_live();
+            }
+            Some(Err(error)) => self.toasts.error(error),
+            None => return,
+        }
+        self.import = None;
+    }
+
+    /// Ask where to save and write everything in the history out on another thread.
+    /// Filtered channels are worked out again over the whole history, not just what is on screen.
+    fn start_export(&mut self, ctx: &egui::Context) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("emg.csv")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        let mut channels = Vec::new();
+        let included = self
+            .channels
+            .iter()
+            .filter(|channel| !self.export_options.excluded.contains(&channel.name));
+        for channel in included {
+            let derived = self
+                .derived
+                .iter()
+                .find(|derived| derived.name == channel.name);
+            let source = derived.and_then(|derived| self.channels.get(&derived.source));
+            let samples: Vec<(f64, f32)> = match (derived, source) {
+                (Some(derived), Some(source)) => {
+                    derived.filter_all(source, self.export_options.blank_settling)
+                }
+                _ => channel.samples.range(..).copied().collect(),
+            };
+            if self.export_options.millivolts && channel.axis == Axis::Left {
+                let scale = self.settings.units.millivolt_scale(channel);
+                let millivolts = samples
+                    .iter()
+                    .map(|&(time, value)| (time, scale.apply(value)))
+                    .collect();
+                channels.push((channel.name.clone(), samples));
+                channels.push((format!("{}_mv", channel.name), millivolts));
+            } else {
+                channels.push((channel.name.clone(), samples));
+            }
+        }
+        if let Some(classified) = self.channels.get(&self.classification.channel) {
+            let samples = classified.samples.range(..);
+            if self.export_options.states {
+                channels.push((
+                    format!("{}_state", classified.name),
+                    classify::codes(samples.clone(), self.classification.thresholds),
+                ));
+            }
+            if self.export_options.contractions {
+                let spans = classify::spans(
+                    samples.clone(),
+                    self.classification.thresholds,
+                    f64::NEG_INFINITY,
+                );
+                let found = contractions::detect(&spans, classified, None);
+                channels.push((
+                    format!("{}_contraction", classified.name),
+                    contractions::flags(samples, &found),
+                ));
+            }
+        }
+        let annotations = self
+            .export_options
+            .annotations
+            .then(|| self.annotations.clone());
+        self.export = Some(export::export_csv(path, channels, annotations, ctx));
+    }
+
+    /// Ask where to save an image of the plot, it is drawn along with the next frame
+    fn start_image_export(&mut self) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("PNG", &["png"])
+            .add_filter("SVG", &["svg"])
+            .set_file_name("emg.png")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        self.image_export = Some(ImageExport {
+            path,
+            size: self.image_size,
+            footer: self.image_footer(),
+        });
+    }
+
+    /// The line about the session written under exported images
+    fn image_footer(&self) -> String {
+        let started = if self.metadata.started.is_empty() {
+            chrono::Local::now().to_rfc3339()
+        } else {
+            self.metadata.started.clone()
+        };
+        let mut footer = format!("Recorded {started}");
+        if !self.metadata.firmware_version.is_empty() {
+            footer += &format!(", firmware {}", self.metadata.firmware_version);
+        }
+        footer
+    }
+
+    /// Ask where to save and write a summary of everything in the history,
+    /// with an image of the plot around each marker and the selected region
+    fn generate_report(&mut self) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("Markdown", &["md"])
+            .set_file_name("report.md")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        let (start, end) = self.data_span();
+        let limits = self.settings.clip_limits;
+        let channels = self
+            .channels
+            .iter()
+            .filter_map(|channel| {
+                let scale = self.settings.units.scale(channel);
+                let mut values: Vec<f32> = channel
+                    .between(start, end)
+                    .filter(|&&(time, _)| self.keeps(time))
+                    .map(|&(_, value)| scale.apply(value))
+                    .collect();
+                Some(ChannelSummary {
+                    name: channel.name.clone(),
+                    stats: Stats::of(&mut values)?,
+                    clipped_percent: derived::is_raw_emg(channel, &self.derived)
+                        .then(|| limits.percent(channel.between(start, end)))
+                        .flatten(),
+                })
+            })
+            .collect();
+        // the firmware runs the same classifier on what it sends as `smoothed`, so with
+        // that channel and the board's thresholds these are the states the hand was in
+        let states = self.channels.get(&self.classification.channel).map(|_| {
+            StateSummary::from_spans(
+                &self.classification.channel,
+                self.classification.thresholds,
+                &self.classification.classify(&self.channels, start..end),
+            )
+        });
+
+        // (time, note, what to plot) for every marker and the selected region
+        let mut regions: Vec<(f64, String, Range<f64>)> = self
+            .annotations
+            .iter()
+            .filter(|annotation| (start..=end).contains(&annotation.time))
+            .map(|annotation| {
+                let time = annotation.time;
+                let half = MARKER_IMAGE_SECONDS / 2.0;
+                (time, annotation.note.clone(), time - half..time + half)
+            })
+            .collect();
+        if let Some((from, to)) = self.selected_span() {
+            regions.push((from, String::from("Selected region"), from..to));
+        }
+        let mut images = Vec::new();
+        for (i, (time, note, times)) in regions.into_iter().enumerate() {
+            let image_path = path.with_extension(format!("marker{}.png", i + 1));
+            let file_name = match self.report_image(&image_path, times, &note) {
+                Ok(()) => image_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                Err(error) => {
+                    self.toasts.error(error);
+                    None
+                }
+            };
+            images.push(MarkedImage {
+                time,
+                note,
+                file_name,
+            });
+        }
+
+        let report = SessionReport {
+            metadata: self.metadata.clone(),
+            source: self.caption(),
+            span: (start, end),
+            value_label: self.settings.units.label(),
+            channels,
+            excluded_seconds: self.settings.artifact_limits.exclude.then(|| {
+                self.artifacts
+                    .spans
+                    .iter()
+                    .map(|(span_start, span_end)| span_end.min(end) - span_start.max(start))
+                    .filter(|&seconds| seconds > 0.0)
+                    .sum()
+            }),
+            states,
+            images,
+        };
+        match report.write_markdown(&path) {
+            Ok(()) => self
+                .toasts
+                .info(format!("Saved the report to {}", path.display())),
+            Err(error) => self
+                .toasts
+                .error(format!("Unable to write {}: {error}", path.display())),
+        }
+    }
+
+    /// Save an image of the plot from `times.start` to `times.end` for a report,
+    /// with its own axis ranges so it doesn't depend on what is on screen
+    fn report_image(&self, path: &Path, times: Range<f64>, caption: &str) -> Result<(), String> {
+        let overlay = Overlay {
+            thresholds: self
+                .classification
+                .threshold_values(&self.channels, &self.settings.units),
+            states: self.classification.classify(&self.channels, times.clone()),
+            gaps: self.gaps.clone(),
+            clipped: self.settings.clip_limits.clipped_spans(
+                &self.channels,
+                &self.derived,
+                times.clone(),
+            ),
+            artifacts: self.artifacts.spans.clone(),
+            annotations: self.annotations.clone(),
+            ..Overlay::default()
+        };
+        let (left, right) = plot::data_ranges(
+            &self.channels,
+            times.clone(),
+            &overlay.thresholds,
+            &self.settings.units,
+            ValueAxis::default(),
+        );
+        let axes = Axes {
+            left: AutoRange::default().update(left).unwrap_or(0.0..1023.0),
+            right: AutoRange::default().update(right),
+            caption: caption.to_owned(),
+            value_label: self.settings.units.label(),
+            units: self.settings.units,
+            value_axis: ValueAxis::default(),
+            style: self.settings.theme.chart_style(),
+            only: None,
+        };
+        let image = ImageExport {
+            path: path.to_path_buf(),
+            size: self.image_size,
+            footer: self.image_footer(),
+        };
+        plot::export_image(&image, &self.channels, times, false, &axes, &overlay)
+    }
+
+    /// Report a finished export
+    fn check_export(&mut self) {
+        let Some(export) = &self.export else {
+            return;
+        };
+        match export.try_recv() {
+            Ok(Ok(rows)) => self.toasts.info(format!("Exported {rows} samples")),
+            Ok(Err(error)) => self.toasts.error(error),
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => self.toasts.error("Export stopped unexpectedly"),
+        }
+        self.export = None;
+    }
+
+    /// The time of the newest sample, or 0 if there are none
+    /// When playing back a file this is how far it has played
+    fn newest_time(&self) -> f64 {
+        match &self.playback {
+            Some(playback) => playback.position,
+            None => self.data_span().1,
+        }
+    }
+
+    /// The times of the first and last samples
+    fn data_span(&self) -> (f64, f64) {
+        self.channels.time_span().unwrap_or((0.0, 0.0))
+    }
+
+    /// Move the playback along, and keep repainting while it plays
+    fn update_playback(&mut self, ctx: &egui::Context) {
+        let (_, last) = self.data_span();
+        if let Some(playback) = &mut self.playback {
+            playback.update(last);
+            if playback.is_playing() {
+                ctx.request_repaint_after(LIVE_REPAINT_INTERVAL);
+            }
+        }
+    }
+
+    fn plot(&mut self, ui: &mut egui::Ui) {
+        let newest = self.newest_time();
+        let times = self
+            .trigger
+            .window()
+            .unwrap_or_else(|| self.viewport.range(newest));
+
+        // thresholds are in the channel's own units, so they don't fit a normalized plot
+        let show_states = self.classification.show_states && !self.normalized;
+        self.classification.spans = if show_states {
+            self.classification.classify(&self.channels, times.clone())
+        } else {
+            Vec::new()
+        };
+        let value_axis = self.settings.value_axis;
+        let (onsets, offsets) = if self.settings.layout.shows(View::Contractions) {
+            self.contractions.markers()
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let overlay = Overlay {
+            selection: self.selected_span(),
+            thresholds: if show_states {
+                self.classification
+                    .threshold_values(&self.channels, &self.settings.units)
+                    .into_iter()
+                    .map(|value| value_axis.apply(value))
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            states: self.classification.spans.clone(),
+            trigger: self.trigger.window().and(self.trigger.captured()),
+            gaps: self.gaps.clone(),
+            clipped: self.settings.clip_limits.clipped_spans(
+                &self.channels,
+                &self.derived,
+                times.clone(),
+            ),
+            artifacts: self.artifacts.spans.clone(),
+            onsets,
+            offsets,
+            annotations: self.annotations.clone(),
+        };
+
+        let axes = if self.normalized {
+            Axes {
+                left: 0.0..1.0,
+                right: None,
+                caption: self.caption(),
+                value_label: "normalized",
+                units: self.settings.units,
+                value_axis: ValueAxis::default(),
+                style: self.settings.theme.chart_style(),
+                only: None,
+            }
+        } else {
+            let (left, right) = plot::data_ranges(
+                &self.channels,
+                times.clone(),
+                &overlay.thresholds,
+                &self.settings.units,
+                value_axis,
+            );
+            let left = self.manual_left.or(self.left_range.update(left));
+            // only show the right axis when something is plotted on it
+            let right = self
+                .right_range
+                .update(right)
+                .and_then(|auto| self.manual_right.or(Some(auto)));
+            Axes {
+                left: left.unwrap_or(0.0..1023.0),
+                right,
+                caption: self.caption(),
+                value_label: self.settings.units.label(),
+                units: self.settings.units,
+                value_axis,
+                style: self.settings.theme.chart_style(),
+                only: None,
+            }
+        };
+
+        if let Some(image) = self.image_export.take() {
+            match plot::export_image(
+                &image,
+                &self.channels,
+                times.clone(),
+                self.normalized,
+                &axes,
+                &overlay,
+            ) {
+                Ok(()) => self
+                    .toasts
+                    .info(format!("Saved the plot to {}", image.path.display())),
+                Err(error) => self.toasts.error(error),
+            }
+        }
+
+        let full = ui.max_rect();
+        // the crosshair's time line goes across every pane
+        let cursor_x = ui
+            .ctx()
+            .pointer_hover_pos()
+            .filter(|&pointer| full.contains(pointer))
+            .map(|pointer| pointer.x);
+
+        // the state lane takes a strip off the bottom, under every pane
+        let lanes = if show_states {
+            self.classification.lanes(&self.channels, times.clone())
+        } else {
+            Vec::new()
+        };
+        let lane_height = lanes.len() as f32 * STATE_LANE_ROW_HEIGHT;
+        let (plots, lane) = full.split_top_bottom_at_y(full.bottom() - lane_height);
+
+        let angles = axes.right.clone().filter(|_| self.split_angles);
+        let (emg_rect, emg_axes) = if angles.is_some() {
+            let emg_axes = Axes {
+                right: None,
+                only: Some(Axis::Left),
+                ..axes
+            };
+            (
+                plots.split_top_bottom_at_fraction(EMG_PANE_FRACTION).0,
+                emg_axes,
+            )
+        } else {
+            (plots, axes)
+        };
+        let x_pixels = ui
+            .scope_builder(egui::UiBuilder::new().max_rect(emg_rect), |ui| {
+                let area = plot::draw(
+                    ui,
+                    &self.channels,
+                    times.clone(),
+                    self.normalized,
+                    &emg_axes,
+                    &overlay,
+                );
+                self.handle_plot_input(ui, times.clone(), &area, &overlay.thresholds, cursor_x);
+                area.x_pixels
+            })
+            .inner;
+
+        if !lanes.is_empty() {
+            let x_range = egui::Rangef::new(
+                emg_rect.left() + x_pixels.start as f32,
+                emg_rect.left() + x_pixels.end as f32,
+            );
+            plot::draw_state_lanes(ui, lane, x_range, times.clone(), &lanes);
+        }
+
+        let Some(angles) = angles else {
+            return;
+        };
+        let bottom = plots.split_top_bottom_at_fraction(EMG_PANE_FRACTION).1;
+        let angle_axes = Axes {
+            left: angles,
+            right: None,
+            caption: String::new(),
+            value_label: "degrees",
+            units: self.settings.units,
+            value_axis: ValueAxis::default(),
+            style: self.settings.theme.chart_style(),
+            only: Some(Axis::Right),
+        };
+        let angle_overlay = Overlay {
+            selection: overlay.selection,
+            trigger: overlay.trigger,
+            gaps: overlay.gaps.clone(),
+            annotations: overlay.annotations.clone(),
+            ..Overlay::default()
+        };
+        ui.scope_builder(egui::UiBuilder::new().max_rect(bottom), |ui| {
+            let area = plot::draw(
+                ui,
+                &self.channels,
+                times.clone(),
+                false,
+                &angle_axes,
+                &angle_overlay,
+            );
+            self.handle_plot_input(ui, times, &area, &[], cursor_x);
+        });
+    }
+
+    /// What is being plotted, for the caption
+    fn caption(&self) -> String {
+        if let Some(report) = &self.loaded_file {
+            let name = report
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            return format!("File: {name}");
+        }
+        match self.source {
+            Source::Serial if self.serial.is_some() => format!(
+                "Serial: {} at {} baud",
+                self.settings.port_name, self.settings.baud_rate
+            ),
+            Source::Network if self.serial.is_some() => {
+                format!("Network: UDP port {}", self.settings.udp_port)
+            }
+            Source::Serial | Source::Network => String::from("Not connected"),
+            Source::Simulator => format!("Simulator: {}", self.simulator.profile_name()),
+            Source::File => String::from("No file open"),
+        }
+    }
+
+    /// Ask where to save the filter comparison table and write it as CSV
+    fn export_comparisons(&mut self) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("filter_comparison.csv")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        match tuning::write_csv(&path, &self.comparison.rows) {
+            Ok(()) => self
+                .toasts
+                .info(format!("Saved the comparison to {}", path.display())),
+            Err(error) => self
+                .toasts
+                .error(format!("Unable to save {}: {error}", path.display())),
+        }
+    }
+
+    /// The span of time `window` covers right now, `None` if nothing is selected
+    fn stats_span(&self, window: StatsWindow) -> Option<(f64, f64)> {
+        let newest = self.newest_time();
+        match window {
+            StatsWindow::LastSecond => Some((newest - 1.0, newest)),
+            StatsWindow::LastFiveSeconds => Some((newest - 5.0, newest)),
+            StatsWindow::Buffer => Some((f64::NEG_INFINITY, newest)),
+            StatsWindow::Selection => self.selected_span(),
+        }
+    }
+
+    /// The selected span of time on the plot, earliest first
+    fn selected_span(&self) -> Option<(f64, f64)> {
+        self.selection
+            .map(|(start, end)| (start.min(end), start.max(end)))
+    }
+
+    /// Statistics of `channel` over the chosen window, in the units it is shown in.
+    /// EMG channels are put through the time plot's y-axis scale too.
+    fn channel_stats(&self, channel: &Channel) -> Option<Stats> {
+        let (start, end) = self.stats_span(self.stats.window)?;
+        let scale = self.settings.units.scale(channel);
+        let value_axis = match channel.axis {
+            Axis::Left => self.settings.value_axis,
+            Axis::Right => ValueAxis::default(),
+        };
+        let mut values: Vec<f32> = channel
+            .between(start, end)
+            .filter(|&&(time, _)| self.keeps(time))
+            .map(|&(_, value)| value_axis.apply(scale.apply(value)))
+            .collect();
+        Stats::of(&mut values)
+    }
+
+    /// If a sample at `time` goes into the statistics, it doesn't when it is
+    /// in an artifact and those are being left out
+    fn keeps(&self, time: f64) -> bool {
+        !self.settings.artifact_limits.exclude || !artifacts::contains(&self.artifacts.spans, time)
+    }
+
+    /// A table of the statistics of every channel over the window picked in the side panel
+    fn statistics_view(&self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "Statistics over {}, EMG in {}",
+            self.stats.window.name(),
+            self.settings.value_axis.label(self.settings.units.label())
+        ));
+        if let Some((start, end)) = self.stats_span(self.stats.window)
+            && let Some(warning) = gaps::warning(&self.gaps, start, end)
+        {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+        let rows: Vec<(&Channel, Stats)> = self
+            .channels
+            .iter()
+            .filter_map(|channel| Some((channel, self.channel_stats(channel)?)))
+            .collect();
+        stats::table(ui, &rows);
+    }
+
+    /// The tabs and the views under them, split as chosen
+    fn main_area(&mut self, ui: &mut egui::Ui) {
+        self.settings
+            .layout
+            .tabs(ui, &mut self.link, &mut self.viewport);
+
+        let rect = ui.available_rect_before_wrap();
+        let layout = self.settings.layout;
+        let panes = match layout.split {
+            Split::Single => vec![(layout.first, rect)],
+            Split::SideBySide => {
+                let (left, right) = rect.split_left_right_at_fraction(0.5);
+                vec![(layout.first, left), (layout.second, right)]
+            }
+            Split::Stacked => {
+                let (top, bottom) = rect.split_top_bottom_at_fraction(0.5);
+                vec![(layout.first, top), (layout.second, bottom)]
+            }
+        };
+        // one pane has nothing to be unlinked from, and views without a time axis always
+        // use the shared viewport
+        let views = layout.views();
+        for (pane, view) in views.iter().enumerate() {
+            if views.len() == 1 || !view.has_time_axis() {
+                self.link
+                    .set_linked(pane, true, views.len(), &mut self.viewport);
+            }
+        }
+
+        self.cursor = None;
+        self.link.begin_frame();
+        for (pane, (view, rect)) in panes.into_iter().enumerate() {
+            // an unlinked pane is drawn with its own viewport in place of the shared one
+            self.link.enter(pane, &mut self.viewport);
+            ui.scope_builder(egui::UiBuilder::new().max_rect(rect), |ui| match view {
+                View::Time => self.plot(ui),
+                View::Spectrum => self.spectrum.draw(
+                    ui,
+                    &self.channels,
+                    self.settings.spectrum_axis,
+                    &self.settings.theme.chart_style(),
+                    self.spectrum
+                        .span
+                        .and_then(|(start, end)| gaps::warning(&self.gaps, start, end)),
+                ),
+                View::Spectrogram => self.spectrogram_view(ui),
+                View::Histogram => self.histogram.draw(
+                    ui,
+                    &self.channels,
+                    &self
+                        .classification
+                        .threshold_values(&self.channels, &self.settings.units),
+                    self.settings.units.label(),
+                    &self.settings.theme.chart_style(),
+                ),
+                View::Statistics => self.statistics_view(ui),
+                View::Correlation => {
+                    self.correlation
+                        .draw(ui, &self.channels, &self.settings.theme.chart_style());
+                }
+                View::Contractions => {
+                    if self
+                        .contractions
+                        .table(ui, &self.classification.channel, &mut self.viewport)
+                    {
+                        self.export_contractions();
+                    }
+                }
+            });
+            self.link.leave(&mut self.viewport);
+        }
+    }
+
+    /// The statistics of the channel picked in the side panel
+    fn stats_controls(&mut self, ui: &mut egui::Ui) {
+        self.stats.controls(ui, &self.channels, &mut self.selection);
+
+        let Some(stats) = self
+            .channels
+            .get(&self.stats.channel)
+            .and_then(|channel| self.channel_stats(channel))
+        else {
+            ui.label("No samples");
+            return;
+        };
+        if let Some((start, end)) = self.stats_span(self.stats.window)
+            && let Some(warning) = gaps::warning(&self.gaps, start, end)
+        {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+        stats::grid(ui, &stats);
+    }
+
+    /// Show, hide, color and pick the axis of each channel
+    fn channel_controls(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Channels");
+
+        self.channels.controls(ui, self.settings.units.millivolts);
+
+        ui.checkbox(&mut self.normalized, "Normalize channels");
+        ui.add_enabled(
+            !self.normalized,
+            egui::Checkbox::new(&mut self.split_angles, "Right axis in its own pane"),
+        );
+
+        self.settings.units.controls(ui);
+    }
+
+    /// Send a command to the firmware if connected, and add it to the terminal
+    fn send_command(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        let Some(serial) = &self.serial else {
+            self.toasts.error("Not connected");
+            return;
+        };
+        serial.send(command);
+        self.terminal.sent(command);
+    }
+
+    /// Bring the views that are shown up to date with the samples
+    fn update_views(&mut self, ctx: &egui::Context) {
+        let layout = self.settings.layout;
+        let sample_rate = self.metadata.sample_rate;
+        if layout.shows(View::Spectrum) {
+            let end = self.viewport.range(self.newest_time()).end;
+            self.spectrum.follow(&self.channels, end, sample_rate);
+        }
+        if layout.shows(View::Spectrogram) {
+            self.spectrogram.follow(ctx, &self.channels, sample_rate);
+        }
+        if layout.shows(View::Histogram) {
+            let span = self.stats_span(self.histogram.window);
+            self.histogram
+                .follow(&self.channels, span, &self.settings.units);
+        }
+        if layout.shows(View::Correlation) {
+            let span = self.stats_span(self.correlation.window);
+            self.correlation.follow(&self.channels, span);
+        }
+        if layout.shows(View::Contractions) {
+            self.update_contractions();
+        }
+    }
+
+    /// Find the contractions in the classified channel, with the first channel on
+    /// the right axis as the angle the latency is measured to
+    fn update_contractions(&mut self) {
+        let Some(channel) = self.channels.get(&self.classification.channel) else {
+            self.contractions.contractions.clear();
+            return;
+        };
+        let spans = self
+            .classification
+            .classify(&self.channels, f64::NEG_INFINITY..f64::INFINITY);
+        let angle = self
+            .channels
+            .iter()
+            .find(|channel| channel.axis == Axis::Right);
+        self.contractions.update(&spans, channel, angle);
+    }
+
+    fn export_contractions(&mut self) {
+        let Some(path) = self
+            .file_dialog()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("contractions.csv")
+            .save_file()
+        else {
+            return;
+        };
+        self.remember_directory(&path);
+
+        match self.contractions.write_csv(&path) {
+            Ok(()) => self
+                .toasts
+                .info(format!("Saved the contractions to {}", path.display())),
+            Err(error) => self
+                .toasts
+                .error(format!("Unable to save {}: {error}", path.display())),
+        }
+    }
+
+    /// Zoom with the scroll wheel and pan by dragging on the plot.
+    /// `x_pixels` is where the plotting area is, relative to the left of `ui`.
+    fn handle_plot_input(
+        &mut self,
+        ui: &mut egui::Ui,
+        times: Range<f64>,
+        area: &PlotArea,
+        thresholds: &[f32],
+        cursor_x: Option<f32>,
+    ) {
+        let rect = ui.max_rect();
+        let id = ui.id().with("plot area");
+        let response = ui.interact(rect, id, egui::Sense::click_and_drag());
+        let x_pixels = area.x_pixels.clone();
+
+        let pixel_width = (x_pixels.end - x_pixels.start).max(1) as f32;
+        let seconds_per_pixel = (times.end - times.start) / pixel_width as f64;
+        let newest = self.newest_time();
+
+        let pointer_time = |x: f32| {
+            times.start + (x - rect.left() - x_pixels.start as f32) as f64 * seconds_per_pixel
+        };
+        // shift-dragging selects a region instead of panning
+        let selecting = ui.input(|i| i.modifiers.shift);
+
+        let pixel_height = (area.y_pixels.end - area.y_pixels.start).max(1) as f32;
+        let values_per_pixel = (area.values.end - area.values.start) / pixel_height;
+        // how far up the axis the pointer is, which is a log of the value on a log axis
+        let pointer_height = |y: f32| {
+            area.values.end - (y - rect.top() - area.y_pixels.start as f32) * values_per_pixel
+        };
+        let pointer_value = |y: f32| area.value_axis.invert(pointer_height(y));
+        // the threshold line the pointer is close enough to grab
+        let near_threshold = response.hover_pos().and_then(|pointer| {
+            thresholds.iter().position(|&threshold| {
+                ((pointer_height(pointer.y) - threshold) / values_per_pixel).abs() < GRAB_DISTANCE
+            })
+        });
+        if near_threshold.is_some() || self.dragged_threshold.is_some() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeVertical);
+        }
+
+        if response.drag_started() && !selecting {
+            self.dragged_threshold = near_threshold;
+        }
+        if response.drag_stopped() {
+            self.dragged_threshold = None;
+        }
+
+        if let Some(index) = self.dragged_threshold
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            let counts = self
+                .classification
+                .scale(&self.channels, &self.settings.units)
+                .invert(pointer_value(pointer.y));
+            let value = counts.clamp(0.0, 1023.0) as u16;
+            let thresholds = &mut self.classification.thresholds;
+            match index {
+                0 => thresholds.intermediate = value.min(thresholds.clenched),
+                _ => thresholds.clenched = value.max(thresholds.intermediate),
+            }
+        } else if selecting && let Some(pointer) = response.interact_pointer_pos() {
+            let time = pointer_time(pointer.x);
+            let start = match self.selection {
+                Some((start, _)) if !response.drag_started() => start,
+                _ => time,
+            };
+            self.selection = Some((start, time));
+        } else if response.dragged() {
+            let (oldest, _) = self.data_span();
+            // dragging right moves the view back in time
+            let seconds = -response.drag_delta().x as f64 * seconds_per_pixel;
+            self.viewport.pan(seconds, oldest, newest);
+        }
+
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                let anchor = pointer_time(pointer.x);
+                // scrolling up zooms in
+                let factor = (-scroll / 200.0).exp();
+                self.viewport
+                    .zoom(factor, anchor, newest, self.settings.history_seconds as f64);
+            }
+        }
+
+        // clicking drops a marker to measure from, right clicking takes it away
+        if response.clicked()
+            && !selecting
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            self.marker = Some((pointer_time(pointer.x), pointer_value(pointer.y)));
+        }
+        if response.secondary_clicked() {
+            self.marker = None;
+        }
+
+        let data_rect = egui::Rect::from_x_y_ranges(
+            rect.left() + x_pixels.start as f32..=rect.left() + x_pixels.end as f32,
+            rect.top() + area.y_pixels.start as f32..=rect.top() + area.y_pixels.end as f32,
+        );
+        let painter = ui.painter_at(data_rect);
+        let text_color = ui.visuals().text_color();
+        let stroke = egui::Stroke::new(1.0, text_color.gamma_multiply(0.6));
+
+        if let Some((time, value)) = self.marker {
+            let x = data_rect.left() + ((time - times.start) / seconds_per_pixel) as f32;
+            let y = data_rect.top()
+                + (area.values.end - area.value_axis.apply(value)) / values_per_pixel;
+            painter.vline(x, data_rect.y_range(), stroke);
+            painter.circle_stroke(egui::pos2(x, y), 4.0, stroke);
+        }
+
+        // the pointer's time in another linked pane when it isn't over this one
+        let linked_x = self
+            .link
+            .cursor()
+            .map(|time| data_rect.left() + ((time - times.start) / seconds_per_pixel) as f32);
+        if let Some(x) = cursor_x.or(linked_x)
+            && data_rect.x_range().contains(x)
+        {
+            painter.vline(x, data_rect.y_range(), stroke);
+        }
+        if self.dragged_threshold.is_none()
+            && let Some(pointer) = response.hover_pos()
+            && data_rect.contains(pointer)
+        {
+            painter.hline(data_rect.x_range(), pointer.y, stroke);
+            self.cursor = Some((pointer_time(pointer.x), pointer_value(pointer.y)));
+            self.link.hover(pointer_time(pointer.x));
+
+            let readout = self.readout(pointer_time(pointer.x), pointer_value(pointer.y));
+            plot::draw_hover_label(ui, data_rect, pointer, readout);
         }
     }
 
-    fn generate_waveform(freq: f32, amp: f32, phase: f32) -> Vec<(f32, f32)> {
-        let samples = 200;
-        let mut data = Vec::with_capacity(samples);
-        for i in 0..samples {
-            let x = i as f32 / samples as f32 * std::f32::consts::TAU;
-            let y = amp * (freq * x + phase).sin();
-            data.push((x, y));
+    /// The text next to the crosshair: the time, each visible channel's value there,
+    /// and how far it is from the marker
+    fn readout(&self, time: f64, value: f32) -> String {
+        let mut lines = vec![format!("t = {time:.3} s")];
+        lines.extend(self.channel_readout(time));
+        lines.extend(self.linked_readout(time, View::Time));
+        if let Some((marker_time, marker_value)) = self.marker {
+            lines.push(format!("Δt = {:.3} s", time - marker_time));
+            lines.push(format!("Δvalue = {:.1}", value - marker_value));
         }
-        data
+        lines.join("\n")
+    }
+
+    /// Each visible channel's value at `time`
+    fn channel_readout(&self, time: f64) -> Vec<String> {
+        self.channels
+            .iter()
+            .filter(|channel| channel.visible)
+            .filter_map(|channel| {
+                let value = channel.value_at(time)?;
+                let value = self.settings.units.scale(channel).apply(value);
+                Some(format!("{}: {value:.1}", channel.name))
+            })
+            .collect()
+    }
+
+    /// The loudest frequency in the spectrogram at `time`
+    fn spectrogram_readout(&self, time: f64) -> Option<String> {
+        let (frequency, magnitude) = self.spectrogram.peak_at(time)?;
+        Some(format!(
+            "{} peak: {frequency:.0} Hz, {magnitude:.1} dB",
+            self.spectrogram.channel
+        ))
+    }
+
+    /// Readout lines for `time` from the other linked panes, so the readout in one pane
+    /// covers them all. Panes showing the same view as `from` are left out.
+    fn linked_readout(&self, time: f64, from: View) -> Vec<String> {
+        let views = self.settings.layout.views();
+        let mut lines = Vec::new();
+        for pane in self.link.linked_others(views.len()) {
+            match views[pane] {
+                view if view == from => {}
+                View::Time => lines.extend(self.channel_readout(time)),
+                View::Spectrogram => lines.extend(self.spectrogram_readout(time)),
+                _ => {}
+            }
+        }
+        lines
+    }
+
+    /// The spectrogram on the same time axis as the plot, it can be panned and zoomed the same way
+    fn spectrogram_view(&mut self, ui: &mut egui::Ui) {
+        let times = self
+            .trigger
+            .window()
+            .unwrap_or_else(|| self.viewport.range(self.newest_time()));
+        let rect = ui.available_rect_before_wrap();
+        let heatmap = self
+            .spectrogram
+            .draw(ui, &self.settings.theme.chart_style(), times.clone());
+        let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
+
+        let seconds_per_pixel = (times.end - times.start) / heatmap.width().max(1.0) as f64;
+        let pointer_time = |x: f32| times.start + (x - heatmap.left()) as f64 * seconds_per_pixel;
+        let newest = self.newest_time();
+        if response.dragged() {
+            let (oldest, _) = self.data_span();
+            let seconds = -response.drag_delta().x as f64 * seconds_per_pixel;
+            self.viewport.pan(seconds, oldest, newest);
+        }
+        let hovered = response
+            .hover_pos()
+            .filter(|&pointer| heatmap.contains(pointer));
+        if let Some(pointer) = hovered {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                let factor = (-scroll / 200.0).exp();
+                self.viewport.zoom(
+                    factor,
+                    pointer_time(pointer.x),
+                    newest,
+                    self.settings.history_seconds as f64,
+                );
+            }
+        }
+
+        let painter = ui.painter_at(heatmap);
+        let stroke = egui::Stroke::new(1.0, egui::Color32::WHITE.gamma_multiply(0.6));
+        let cursor = hovered
+            .map(|pointer| pointer_time(pointer.x))
+            .or(self.link.cursor());
+        if let Some(time) = cursor {
+            let x = heatmap.left() + ((time - times.start) / seconds_per_pixel) as f32;
+            painter.vline(x, heatmap.y_range(), stroke);
+        }
+
+        let Some(pointer) = hovered else {
+            return;
+        };
+        let time = pointer_time(pointer.x);
+        self.link.hover(time);
+        let mut lines = vec![format!("t = {time:.3} s")];
+        lines.extend(self.spectrogram_readout(time));
+        lines.extend(self.linked_readout(time, View::Spectrogram));
+        plot::draw_hover_label(ui, heatmap, pointer, lines.join("\n"));
+    }
+
+    /// The newest sample, or where the playback is, `None` before there are any samples
+    fn marker_time(&self) -> Option<f64> {
+        self.channels.time_span().map(|_| self.newest_time())
+    }
+
+    /// Raw EMG channels that look like an electrode has come off, going by the
+    /// firmware's detector run over the last few seconds
+    fn lead_off_channels(&self) -> Vec<String> {
+        let newest = self.newest_time();
+        self.channels
+            .iter()
+            .filter(|channel| derived::is_raw_emg(channel, &self.derived))
+            .filter(|channel| {
+                let mut detector = LeadOffDetector::new(LeadOffLimits::default());
+                channel
+                    .between(newest - LEAD_OFF_WINDOW, newest)
+                    .fold(false, |_, &(_, value)| {
+                        detector.update(value.clamp(0.0, u16::MAX as f32) as u16)
+                    })
+            })
+            .map(|channel| channel.name.clone())
+            .collect()
+    }
+
+    /// A warning across the top while an electrode is off. It goes away by itself
+    /// once the signal comes back.
+    fn lead_off_banner(&self, ctx: &egui::Context) {
+        let channels = self.lead_off_channels();
+        if channels.is_empty() {
+            return;
+        }
+        egui::TopBottomPanel::top(Id::new("lead off warning")).show(ctx, |ui| {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!(
+                    "Electrode off on {}: the signal is stuck or flat. Check the leads.",
+                    channels.join(", ")
+                ),
+            );
+        });
+    }
+
+    /// Act on the keyboard shortcuts, unless something is being typed
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        use egui::Key;
+        let pressed = |key| ctx.input(|i| i.key_pressed(key));
+
+        let newest = self.newest_time();
+        if pressed(Key::Space) {
+            self.viewport.toggle_pause(newest);
+        }
+        if pressed(Key::L) {
+            self.viewport.go_live();
+        }
+        for (key, direction) in [(Key::ArrowLeft, -1.0), (Key::ArrowRight, 1.0)] {
+            if pressed(key) {
+                let (oldest, _) = self.data_span();
+                let seconds = direction * self.viewport.width * PAN_STEP;
+                self.viewport.pan(seconds, oldest, newest);
+            }
+        }
+        for (key, factor) in [
+            (Key::Plus, 1.0 / ZOOM_STEP),
+            (Key::Equals, 1.0 / ZOOM_STEP),
+            (Key::Minus, ZOOM_STEP),
+        ] {
+            if pressed(key) {
+                let times = self.viewport.range(newest);
+                let middle = (times.start + times.end) / 2.0;
+                self.viewport
+                    .zoom(factor, middle, newest, self.settings.history_seconds as f64);
+            }
+        }
+        if pressed(Key::M) && self.cursor.is_some() {
+            self.marker = self.cursor;
+        }
+        if pressed(Key::N)
+            && let Some(time) = self.marker_time()
+        {
+            session::add_annotation(&mut self.annotations, time);
+        }
+
+        let number_keys = [
+            Key::Num1,
+            Key::Num2,
+            Key::Num3,
+            Key::Num4,
+            Key::Num5,
+            Key::Num6,
+            Key::Num7,
+            Key::Num8,
+            Key::Num9,
+        ];
+        for (key, channel) in number_keys.into_iter().zip(self.channels.iter_mut()) {
+            if pressed(key) {
+                channel.visible = !channel.visible;
+            }
+        }
+
+        if pressed(Key::S) && self.export.is_none() {
+            self.start_export(ctx);
+        }
+        if pressed(Key::R) {
+            if self.recorder.is_some() {
+                self.stop_recording();
+            } else if self.loaded_file.is_none() {
+                self.start_recording();
+            }
+        }
+        if pressed(Key::Questionmark) {
+            self.show_shortcuts = !self.show_shortcuts;
+        }
+        if pressed(Key::F12) {
+            self.pacing.show = !self.pacing.show;
+        }
+    }
+
+    fn shortcuts_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut self.show_shortcuts)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").num_columns(2).show(ui, |ui| {
+                    for (key, action) in SHORTCUTS {
+                        ui.monospace(key);
+                        ui.label(action);
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    /// Everything in the side panel, top to bottom
+    fn side_panel(&mut self, ui: &mut egui::Ui) {
+        self.source_controls(ui);
+
+        ui.separator();
+
+        ui.heading("Plot");
+
+        ui.horizontal(|ui| {
+            ui.label("Theme");
+            let mut changed = false;
+            for theme in Theme::ALL {
+                changed |= ui
+                    .selectable_value(&mut self.settings.theme, theme, theme.name())
+                    .changed();
+            }
+            if changed {
+                ui.ctx().set_visuals(self.settings.theme.visuals());
+            }
+            if ui.button("Shortcuts").clicked() {
+                self.show_shortcuts = true;
+            }
+        });
+
+        ui.add(
+            egui::Slider::new(
+                &mut self.viewport.width,
+                0.05..=self.settings.history_seconds as f64,
+            )
+            .logarithmic(true)
+            .text("Window (s)"),
+        );
+
+        ui.horizontal(|ui| {
+            ui.label("Click the plot to drop a marker");
+            if ui
+                .add_enabled(self.marker.is_some(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.marker = None;
+            }
+        });
+
+        let newest = self.newest_time();
+        ui.horizontal(|ui| {
+            let pause_text = if self.viewport.is_live() {
+                "Pause"
+            } else {
+                "Resume"
+            };
+            if ui.button(pause_text).clicked() {
+                self.viewport.toggle_pause(newest);
+            }
+            if ui
+                .add_enabled(!self.viewport.is_live(), egui::Button::new("Back to live"))
+                .clicked()
+            {
+                self.viewport.go_live();
+            }
+        });
+        self.history_controls(ui);
+
+        ui.horizontal(|ui| {
+            ui.label("Left axis");
+            self.settings.value_axis.controls(ui, "value axis");
+        });
+        for (label, manual) in [
+            ("Fixed left axis", &mut self.manual_left),
+            ("Fixed right axis", &mut self.manual_right),
+        ] {
+            ui.checkbox(&mut manual.enabled, label);
+            if manual.enabled {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut manual.min).prefix("From "));
+                    ui.add(egui::DragValue::new(&mut manual.max).prefix("To "));
+                });
+            }
+        }
+
+        ui.separator();
+
+        self.channel_controls(ui);
+
+        ui.separator();
+
+        let sample_rate = self
+            .metadata
+            .sample_rate
+            .unwrap_or(self.settings.sample_rate);
+        if let Some(command) =
+            self.filter_controls
+                .controls(ui, &mut self.derived, &mut self.channels, sample_rate)
+        {
+            self.send_command(&command);
+        }
+        if self.comparison.table(ui, self.selected_span().is_some()) {
+            self.export_comparisons();
+        }
+        self.filter_controls.preset_controls(
+            ui,
+            &mut self.derived,
+            &mut self.channels,
+            &mut self.settings.filter_presets,
+        );
+
+        ui.separator();
+
+        self.stats_controls(ui);
+
+        ui.separator();
+
+        let clipped = self.settings.clip_limits.clipped_percentages(
+            &self.channels,
+            &self.derived,
+            self.newest_time(),
+        );
+        self.settings.clip_limits.controls(ui, &clipped);
+
+        ui.separator();
+
+        self.artifacts
+            .controls(ui, &mut self.settings.artifact_limits, &mut self.viewport);
+
+        ui.separator();
+
+        gaps::controls(ui, &self.gaps, &mut self.viewport);
+
+        ui.separator();
+
+        let editable = self.loaded_file.is_none() && self.recorder.is_none();
+        if self.noise.controls(
+            ui,
+            &self.channels,
+            &self.derived,
+            self.newest_time(),
+            self.settings.reference_rms,
+            editable,
+        ) {
+            self.metadata.noise_check = self.noise.report.clone();
+        }
+
+        ui.separator();
+
+        self.classification
+            .controls(ui, &self.channels, &mut self.viewport);
+
+        ui.separator();
+
+        self.trigger
+            .controls(ui, &self.channels, self.settings.history_seconds);
+
+        ui.separator();
+
+        let newest = self.marker_time();
+        session::annotation_controls(ui, &mut self.annotations, newest, &mut self.viewport);
+
+        ui.separator();
+
+        self.spectrum.controls(
+            ui,
+            &self.channels,
+            &mut self.settings.spectrum_axis,
+            self.settings.layout.shows(View::Spectrum),
+        );
+
+        ui.separator();
+
+        self.spectrogram.controls(ui, &self.channels);
+
+        ui.separator();
+
+        self.histogram.controls(
+            ui,
+            &self.channels,
+            self.settings.layout.shows(View::Histogram),
+        );
+
+        ui.separator();
+
+        self.correlation.controls(
+            ui,
+            &self.channels,
+            self.settings.layout.shows(View::Correlation),
+        );
+
+        ui.separator();
+
+        self.data_controls(ui);
+
+        ui.separator();
+
+        let (first, last) = self.data_span();
+        if let Some(playback) = &mut self.playback {
+            playback.controls(ui, first, last, &mut self.viewport);
+
+            ui.separator();
+        }
+
+        self.session_controls(ui);
+
+        ui.separator();
+
+        if ui
+            .button("Reset settings")
+            .on_hover_text("Put every saved setting and the panel layout back to the defaults")
+            .clicked()
+        {
+            self.reset_settings(ui.ctx());
+        }
+    }
+
+    /// How many seconds of samples each channel keeps, and the memory that takes
+    fn history_controls(&mut self, ui: &mut egui::Ui) {
+        let limit = self.history_limit();
+        let mut seconds = self.settings.history_seconds;
+        // a loaded file keeps all of its samples
+        if ui
+            .add_enabled(
+                self.loaded_file.is_none(),
+                egui::Slider::new(&mut seconds, 10.0..=MAX_HISTORY_SECONDS)
+                    .logarithmic(true)
+                    .custom_formatter(|seconds, _| format_duration(seconds as f32))
+                    .text("History"),
+            )
+            .changed()
+        {
+            self.set_history(seconds.min(limit));
+        }
+        if self.loaded_file.is_some() {
+            return;
+        }
+
+        let channels = self.channels.iter().count().max(1);
+        ui.label(format!(
+            "About {:.0} MB for {channels} channel{} at up to {MAX_SAMPLE_RATE:.0} Hz",
+            self.history_bytes() as f32 / (1024.0 * 1024.0),
+            if channels == 1 { "" } else { "s" },
+        ));
+        if limit < MAX_HISTORY_SECONDS && self.settings.history_seconds >= limit {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "Limited to {} so the history stays under {} MB",
+                    format_duration(limit),
+                    MAX_HISTORY_BYTES / (1024 * 1024)
+                ),
+            );
+        }
+    }
+
+    fn reset_settings(&mut self, ctx: &egui::Context) {
+        self.settings = Settings::default();
+        Self::pick_port(&mut self.settings.port_name, &self.ports);
+        // only the panel sizes are ours, the rest of egui's memory is left alone
+        ctx.data_mut(|data| {
+            for panel in [SOURCES_PANEL, TERMINAL_PANEL] {
+                data.remove::<egui::panel::PanelState>(Id::new(panel));
+            }
+        });
+        ctx.set_visuals(self.settings.theme.visuals());
+        self.channels.remember_colors(Vec::new());
+        self.set_history(self.settings.history_seconds);
     }
 }
 
 impl eframe::App for VisualGraph {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        SidePanel::new(egui::panel::Side::Left, Id::new("Graph sources"))
+        let frame_start = Instant::now();
+        self.read_serial();
+        self.read_devices();
+        self.read_simulator(ctx);
+        self.check_export();
+        self.check_import();
+        self.check_batch();
+        for derived in &mut self.derived {
+            derived.update(&mut self.channels);
+        }
+        let span = self.selected_span();
+        self.comparison.update(&self.derived, &self.channels, span);
+        self.artifacts.update(
+            &self.settings.artifact_limits,
+            &self.settings.clip_limits,
+            &self.channels,
+            &self.derived,
+        );
+        let newest = self.newest_time();
+        if self
+            .noise
+            .update(&self.channels, newest, &mut self.settings.reference_rms)
+        {
+            self.toasts.info("Saved the reference contraction");
+        }
+        self.update_playback(ctx);
+        self.limit_history();
+
+        SidePanel::new(egui::panel::Side::Left, Id::new(SOURCES_PANEL))
             .resizable(true)
             .show(ctx, |ui| {
-                ui.heading("EMG Waveform Controls");
+                egui::ScrollArea::vertical().show(ui, |ui| self.side_panel(ui));
+            });
+        self.handle_shortcuts(ctx);
+        self.shortcuts_window(ctx);
 
-                ui.add(egui::Slider::new(&mut self.frequency, 0.5..=10.0).text("Frequency"));
-                ui.add(egui::Slider::new(&mut self.amplitude, 0.0..=5.0).text("Amplitude"));
-                ui.add(
-                    egui::Slider::new(&mut self.phase, 0.0..=std::f32::consts::TAU).text("Phase"),
-                );
+        self.lead_off_banner(ctx);
+        let clipped = self.settings.clip_limits.clipped_percentages(
+            &self.channels,
+            &self.derived,
+            self.newest_time(),
+        );
+        self.clip_banner
+            .show(ctx, &clipped, self.settings.clip_limits.warn_percent);
+        if let Some(command) = self.terminal.panel(ctx) {
+            self.send_command(&command);
+        }
+        self.update_views(ctx);
+        CentralPanel::default().show(ctx, |ui| self.main_area(ui));
 
-                if ui.button("Regenerate Waveform").clicked() {
-                    self.voltage_data =
-                        Self::generate_waveform(self.frequency, self.amplitude, self.phase);
-                }
-            });
-        CentralPanel::default().show(ctx, |ui| {
-            let root = EguiBackend::new(ui).into_drawing_area();
-            root.fill(&WHITE).unwrap();
-            let mut chart = ChartBuilder::on(&root)
-                .caption("y=x^2", ("sans-serif", 50).into_font())
-                .margin(5)
-                .x_label_area_size(30)
-                .y_label_area_size(30)
-                .build_cartesian_2d(-1f32..1f32, -0.1f32..1f32)
-                .unwrap();
-
-            chart.configure_mesh().draw().unwrap();
-
-            chart
-                .draw_series(LineSeries::new(self.voltage_data.clone(), &RED))
-                .unwrap()
-                .label("Simulated EMG Voltage")
-                .legend(|(x, y)| PathElement::new(vec![(x, y), (x, y)], &RED));
-
-            chart
-                .configure_series_labels()
-                .background_style(&WHITE.mix(0.8))
-                .border_style(&BLACK)
-                .draw()
-                .unwrap();
-
-            root.present().unwrap();
-        });
+        self.import_mapping_window(ctx);
+        self.toasts.show(ctx);
+        self.pacing.overlay(ctx);
+
+        self.pacing
+            .push(frame_start, frame_start.elapsed(), self.frame_samples);
+        self.frame_samples = 0;
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.settings.set_channel_colors(self.channels.colors());
+        self.settings.save(storage);
     }
 }
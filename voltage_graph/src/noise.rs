@@ -0,0 +1,252 @@
+use std::f32::consts::TAU;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::Channels;
+use crate::derived::{DerivedChannel, is_raw_emg};
+
+/// Seconds of signal captured for a noise check or a reference contraction
+pub const CAPTURE_SECONDS: f64 = 3.0;
+/// Resting noise above this many ADC counts RMS means the electrodes aren't making good contact
+const HIGH_NOISE_RMS: f32 = 5.0;
+/// Mains hum with an amplitude above this fraction of the noise RMS is most of the noise
+const HIGH_MAINS_FRACTION: f32 = 0.7;
+/// A contraction less than this far above the noise is hard to tell apart from it
+const LOW_SNR_DB: f32 = 10.0;
+/// Mains frequencies in Europe and the Americas
+const MAINS_FREQUENCIES: [f32; 2] = [50.0, 60.0];
+
+/// What is being captured
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    /// The relaxed muscle, to measure the noise
+    Relaxed,
+    /// A steady contraction, kept as the reference the noise is compared with
+    Contraction,
+}
+
+/// How clean the relaxed signal was, in ADC counts
+#[derive(Clone, Deserialize, Serialize)]
+pub struct NoiseReport {
+    pub channel: String,
+    /// RMS of the relaxed signal with its average taken off
+    pub rms: f32,
+    /// Amplitude of the 50 Hz and 60 Hz hum, `None` if the sample rate is too low to see it
+    pub mains: Option<(f32, f32)>,
+    /// How far the reference contraction is above the noise, `None` without a reference
+    pub snr_db: Option<f32>,
+    /// What to do about it, in plain words
+    pub verdict: String,
+}
+
+impl NoiseReport {
+    /// Measure the noise in the relaxed `samples`, as (time, value).
+    /// `reference_rms` is the RMS of a contraction to compare with.
+    pub fn analyze(channel: &str, samples: &[(f64, f32)], reference_rms: Option<f32>) -> Self {
+        let rms = ac_rms(samples);
+        let mains = sample_rate(samples)
+            // the hum has to be under half the sample rate to show up
+            .filter(|&rate| rate > 2.0 * MAINS_FREQUENCIES[1])
+            .map(|rate| {
+                let [fifty, sixty] = MAINS_FREQUENCIES.map(|hz| tone_amplitude(samples, hz, rate));
+                (fifty, sixty)
+            });
+        let snr_db =
+            reference_rms.map(|reference| 20.0 * (reference / rms.max(f32::EPSILON)).log10());
+
+        let loudest_mains = mains.map_or(0.0, |(fifty, sixty)| fifty.max(sixty));
+        let verdict = if rms > HIGH_NOISE_RMS && loudest_mains > rms * HIGH_MAINS_FRACTION {
+            "Mains interference high, check the ground electrode"
+        } else if rms > HIGH_NOISE_RMS {
+            "Noise floor high, check the electrodes are stuck down"
+        } else if snr_db.is_some_and(|snr| snr < LOW_SNR_DB) {
+            "The contraction is weak next to the noise, move the electrodes over the muscle"
+        } else {
+            "Clean enough to record"
+        };
+
+        Self {
+            channel: channel.to_owned(),
+            rms,
+            mains,
+            snr_db,
+            verdict: verdict.to_owned(),
+        }
+    }
+}
+
+/// A noise check on one raw EMG channel
+#[derive(Default)]
+pub struct NoiseCheck {
+    /// Name of the channel the check is done on
+    pub channel: String,
+    /// A capture under way, with the time it started
+    capture: Option<(Capture, f64)>,
+    pub report: Option<NoiseReport>,
+}
+
+impl NoiseCheck {
+    /// Capture a few seconds of the relaxed signal to measure its noise, or of a
+    /// contraction to compare the noise with. `newest` is when a capture starts, and
+    /// the report can only be attached while the session is `editable`.
+    /// Returns whether the report should be attached to the session.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        channels: &Channels,
+        derived: &[DerivedChannel],
+        newest: f64,
+        reference_rms: Option<f32>,
+        editable: bool,
+    ) -> bool {
+        ui.heading("Noise check");
+
+        if channels.get(&self.channel).is_none()
+            && let Some(channel) = channels.iter().find(|channel| is_raw_emg(channel, derived))
+        {
+            self.channel = channel.name.clone();
+        }
+        channels.picker(
+            ui,
+            egui::ComboBox::from_id_salt("noise channel"),
+            &mut self.channel,
+        );
+
+        if let Some((capture, _)) = self.capture {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(match capture {
+                    Capture::Relaxed => format!("Relax for {CAPTURE_SECONDS} s..."),
+                    Capture::Contraction => {
+                        format!("Hold a steady contraction for {CAPTURE_SECONDS} s...")
+                    }
+                });
+                if ui.small_button("Cancel").clicked() {
+                    self.capture = None;
+                }
+            });
+        } else {
+            let has_data = channels.get(&self.channel).is_some();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(has_data, egui::Button::new("Analyze noise"))
+                    .on_hover_text("Measure the noise while the muscle is relaxed")
+                    .clicked()
+                {
+                    self.capture = Some((Capture::Relaxed, newest));
+                }
+                if ui
+                    .add_enabled(has_data, egui::Button::new("Record contraction"))
+                    .on_hover_text("Keep a contraction to work out the signal to noise ratio")
+                    .clicked()
+                {
+                    self.capture = Some((Capture::Contraction, newest));
+                }
+            });
+        }
+        if let Some(reference) = reference_rms {
+            ui.label(format!("Reference contraction {reference:.1} RMS"));
+        }
+
+        let Some(report) = &self.report else {
+            return false;
+        };
+        egui::Grid::new("noise report")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Noise floor");
+                ui.monospace(format!("{:.2} RMS", report.rms));
+                ui.end_row();
+                if let Some((fifty, sixty)) = report.mains {
+                    ui.label("50 Hz");
+                    ui.monospace(format!("{fifty:.2}"));
+                    ui.end_row();
+                    ui.label("60 Hz");
+                    ui.monospace(format!("{sixty:.2}"));
+                    ui.end_row();
+                }
+                if let Some(snr) = report.snr_db {
+                    ui.label("SNR");
+                    ui.monospace(format!("{snr:.1} dB"));
+                    ui.end_row();
+                }
+            });
+        ui.label(report.verdict.as_str());
+
+        ui.add_enabled(editable, egui::Button::new("Attach to session"))
+            .on_hover_text("Save this check in the session file's metadata")
+            .clicked()
+    }
+
+    /// Finish a capture once enough samples have come in up to `newest`. A contraction
+    /// is kept in `reference_rms`, and this returns whether one was.
+    pub fn update(
+        &mut self,
+        channels: &Channels,
+        newest: f64,
+        reference_rms: &mut Option<f32>,
+    ) -> bool {
+        let Some((capture, start)) = self.capture else {
+            return false;
+        };
+        if newest < start + CAPTURE_SECONDS {
+            return false;
+        }
+        self.capture = None;
+
+        let Some(channel) = channels.get(&self.channel) else {
+            return false;
+        };
+        let samples: Vec<(f64, f32)> = channel.between(start, newest).copied().collect();
+        match capture {
+            Capture::Relaxed => {
+                self.report = Some(NoiseReport::analyze(
+                    &self.channel,
+                    &samples,
+                    *reference_rms,
+                ));
+                false
+            }
+            Capture::Contraction => {
+                *reference_rms = Some(ac_rms(&samples));
+                true
+            }
+        }
+    }
+}
+
+/// RMS of `samples` around their average
+pub fn ac_rms(samples: &[(f64, f32)]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let mean = samples.iter().map(|&(_, value)| value).sum::<f32>() / samples.len() as f32;
+    let squares: f32 = samples
+        .iter()
+        .map(|&(_, value)| (value - mean) * (value - mean))
+        .sum();
+    (squares / samples.len() as f32).sqrt()
+}
+
+/// Samples per second, measured from the first and last sample times
+fn sample_rate(samples: &[(f64, f32)]) -> Option<f32> {
+    let (first, _) = samples.first()?;
+    let (last, _) = samples.last()?;
+    (last > first).then(|| ((samples.len() - 1) as f64 / (last - first)) as f32)
+}
+
+/// Amplitude of the sine wave at `frequency` in `samples`, with the Goertzel algorithm
+fn tone_amplitude(samples: &[(f64, f32)], frequency: f32, sample_rate: f32) -> f32 {
+    let mean = samples.iter().map(|&(_, value)| value).sum::<f32>() / samples.len() as f32;
+    let coefficient = 2.0 * (TAU * frequency / sample_rate).cos();
+    let (mut previous, mut before_previous) = (0.0f32, 0.0f32);
+    for &(_, value) in samples {
+        let current = value - mean + coefficient * previous - before_previous;
+        before_previous = previous;
+        previous = current;
+    }
+    let power = previous * previous + before_previous * before_previous
+        - coefficient * previous * before_previous;
+    2.0 * power.max(0.0).sqrt() / samples.len() as f32
+}
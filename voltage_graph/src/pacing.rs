@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Id};
+
+/// How often the plot is redrawn while live data is coming in, about 30 Hz.
+/// Samples that arrive in between wait for the next frame instead of each asking for one.
+/// Dragging and zooming still repaint right away, egui does that for any input.
+pub const LIVE_REPAINT_INTERVAL: Duration = Duration::from_millis(33);
+/// How many seconds of frames the stats are worked out over
+const WINDOW_SECONDS: f32 = 2.0;
+
+/// How the last few seconds of frames went
+pub struct PacingStats {
+    pub frames_per_second: f32,
+    pub mean_interval_ms: f32,
+    /// The longest wait between two frames, a stutter shows up here
+    pub max_interval_ms: f32,
+    /// Time spent in the app's update each frame, drawing included
+    pub mean_update_ms: f32,
+    pub samples_per_frame: f32,
+}
+
+/// Keeps the timing of recent frames, for the debug overlay
+#[derive(Default)]
+pub struct FramePacing {
+    /// (when the frame started, seconds its update took, samples it took in)
+    frames: VecDeque<(Instant, f32, usize)>,
+    pub show: bool,
+}
+
+impl FramePacing {
+    pub fn push(&mut self, start: Instant, update: Duration, samples: usize) {
+        self.frames
+            .push_back((start, update.as_secs_f32(), samples));
+        while self.frames.front().is_some_and(|&(first, _, _)| {
+            start.duration_since(first).as_secs_f32() > WINDOW_SECONDS
+        }) {
+            self.frames.pop_front();
+        }
+    }
+
+    /// `None` until there are two frames to measure between
+    pub fn stats(&self) -> Option<PacingStats> {
+        let (&(first, _, _), &(last, _, _)) = (self.frames.front()?, self.frames.back()?);
+        let span = last.duration_since(first).as_secs_f32();
+        if self.frames.len() < 2 || span <= 0.0 {
+            return None;
+        }
+
+        let intervals = self.frames.len() - 1;
+        let max_interval = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|(&(a, _, _), &(b, _, _))| b.duration_since(a).as_secs_f32())
+            .fold(0.0, f32::max);
+        let update: f32 = self.frames.iter().map(|&(_, update, _)| update).sum();
+        let samples: usize = self.frames.iter().map(|&(_, _, samples)| samples).sum();
+        let frames = self.frames.len() as f32;
+        Some(PacingStats {
+            frames_per_second: intervals as f32 / span,
+            mean_interval_ms: span * 1000.0 / intervals as f32,
+            max_interval_ms: max_interval * 1000.0,
+            mean_update_ms: update * 1000.0 / frames,
+            samples_per_frame: samples as f32 / frames,
+        })
+    }
+
+    /// Frame timing in the corner of the window, to check the repaint pacing
+    pub fn overlay(&self, ctx: &egui::Context) {
+        if !self.show {
+            return;
+        }
+        egui::Area::new(Id::new("frame pacing"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let Some(stats) = self.stats() else {
+                        ui.label("No frames yet");
+                        return;
+                    };
+                    egui::Grid::new("pacing stats")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for (label, value) in [
+                                ("Frames", format!("{:.1} /s", stats.frames_per_second)),
+                                ("Interval", format!("{:.1} ms", stats.mean_interval_ms)),
+                                ("Longest", format!("{:.1} ms", stats.max_interval_ms)),
+                                ("Update", format!("{:.2} ms", stats.mean_update_ms)),
+                                ("Samples", format!("{:.1} /frame", stats.samples_per_frame)),
+                            ] {
+                                ui.label(label);
+                                ui.monospace(value);
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Instant;
+
+use eframe::egui;
+
+use crate::viewport::TimeViewport;
+
+/// Replays a loaded recording through the plot as if it were arriving live
+pub struct Playback {
+    /// The time in the recording that has been played up to
+    pub position: f64,
+    /// How many seconds of recording play per real second
+    pub speed: f32,
+    playing: bool,
+    last_update: Instant,
+}
+
+impl Playback {
+    /// Start paused at `position`
+    pub fn new(position: f64) -> Self {
+        Self {
+            position,
+            speed: 1.0,
+            playing: false,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Play from the current position, or from `start` if it is already at `end`
+    pub fn play(&mut self, start: f64, end: f64) {
+        if self.position >= end {
+            self.position = start;
+        }
+        self.playing = true;
+        self.last_update = Instant::now();
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Move forward by the real time since the last update, stopping at `end`
+    pub fn update(&mut self, end: f64) {
+        let now = Instant::now();
+        if self.playing {
+            let elapsed = now.duration_since(self.last_update).as_secs_f64();
+            self.position = (self.position + elapsed * self.speed as f64).min(end);
+            if self.position >= end {
+                self.playing = false;
+            }
+        }
+        self.last_update = now;
+    }
+
+    /// Play and pause, the speed and a slider for the position between `first` and `last`.
+    /// Playing makes `viewport` follow the playhead.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        first: f64,
+        last: f64,
+        viewport: &mut TimeViewport,
+    ) {
+        ui.heading("Playback");
+
+        ui.horizontal(|ui| {
+            if self.is_playing() {
+                if ui.button("Pause").clicked() {
+                    self.pause();
+                }
+            } else if ui.button("Play").clicked() {
+                self.play(first, last);
+                // follow the playhead
+                viewport.go_live();
+            }
+
+            egui::ComboBox::from_label("Speed")
+                .selected_text(format!("{}x", self.speed))
+                .show_ui(ui, |ui| {
+                    for speed in [0.25, 0.5, 1.0, 2.0, 4.0, 8.0] {
+                        ui.selectable_value(&mut self.speed, speed, format!("{speed}x"));
+                    }
+                });
+        });
+
+        ui.add(egui::Slider::new(&mut self.position, first..=last).text("Position (s)"));
+    }
+}
@@ -0,0 +1,957 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use eframe::egui;
+use egui_plotter::EguiBackend;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use hand_core::EmgState;
+
+use crate::channel::{Axis, Channels};
+use crate::classify::StateSpan;
+use crate::gaps::Gap;
+use crate::histogram::HistogramView;
+use crate::session::Annotation;
+use crate::theme::ChartStyle;
+use crate::units::{AxisScale, Units, ValueAxis};
+
+/// How far below the loudest frequency the spectrum plot goes
+const SPECTRUM_RANGE_DB: f32 = 100.0;
+/// Pixels at the bottom of an exported image for the footer
+const FOOTER_HEIGHT: u32 = 24;
+/// Channels with more samples than this per pixel across are drawn as a min/max envelope
+const MAX_POINTS_PER_PIXEL: usize = 2;
+
+/// Where the chart's data area ended up, in pixels from the top left of the `Ui`
+pub struct PlotArea {
+    pub x_pixels: Range<i32>,
+    pub y_pixels: Range<i32>,
+    /// The values on the left axis, from the bottom of the area to the top
+    pub values: Range<f32>,
+    /// How the values are spread up the left axis
+    pub value_axis: ValueAxis,
+}
+
+/// Things drawn on the plot along with the channels
+#[derive(Default)]
+pub struct Overlay {
+    /// A span of time to shade
+    pub selection: Option<(f64, f64)>,
+    /// Values on the left axis to draw lines across at
+    pub thresholds: Vec<f32>,
+    /// Spans of time, (start, end, state), to shade by the state the classifier picked
+    pub states: Vec<(f64, f64, EmgState)>,
+    /// Time the trigger fired, drawn as a line down the plot
+    pub trigger: Option<f64>,
+    /// Stretches with no samples. The lines aren't joined across them.
+    pub gaps: Vec<Gap>,
+    /// Spans of (start, end) where an EMG channel is stuck against the ADC's rails
+    pub clipped: Vec<(f64, f64)>,
+    /// Spans of (start, end) that look like motion artifacts
+    pub artifacts: Vec<(f64, f64)>,
+    /// Times contractions start and end, drawn as lines down the plot
+    pub onsets: Vec<f64>,
+    pub offsets: Vec<f64>,
+    /// Markers on the timeline, drawn as lines down the plot with their notes
+    pub annotations: Vec<Annotation>,
+}
+
+/// A pixel column being filled by [`decimate`], with its lowest and highest points
+struct Column {
+    index: i64,
+    low: (f64, f32),
+    high: (f64, f32),
+}
+
+/// Cut `points` down to the lowest and highest value in each pixel column across `width` pixels,
+/// so a long recording draws quickly and spikes still show.
+/// When zoomed in far enough the points are kept as they are.
+fn decimate<'a>(
+    points: impl ExactSizeIterator<Item = &'a (f64, f32)>,
+    times: &Range<f64>,
+    width: u32,
+) -> Vec<(f64, f32)> {
+    let width = width.max(1) as usize;
+    if points.len() <= width * MAX_POINTS_PER_PIXEL {
+        return points.copied().collect();
+    }
+
+    let seconds_per_pixel = (times.end - times.start) / width as f64;
+    let mut decimated = Vec::with_capacity(width * 2);
+    let mut column: Option<Column> = None;
+    let mut finish = |Column { low, high, .. }: Column| {
+        // keep them in time order so the line doesn't double back
+        let (first, second) = if low.0 <= high.0 {
+            (low, high)
+        } else {
+            (high, low)
+        };
+        decimated.push(first);
+        if second != first {
+            decimated.push(second);
+        }
+    };
+
+    for &(time, value) in points {
+        let index = ((time - times.start) / seconds_per_pixel).floor() as i64;
+        match &mut column {
+            Some(current) if current.index == index => {
+                if value < current.low.1 {
+                    current.low = (time, value);
+                }
+                if value > current.high.1 {
+                    current.high = (time, value);
+                }
+            }
+            _ => {
+                if let Some(done) = column.take() {
+                    finish(done);
+                }
+                column = Some(Column {
+                    index,
+                    low: (time, value),
+                    high: (time, value),
+                });
+            }
+        }
+    }
+    if let Some(done) = column {
+        finish(done);
+    }
+    decimated
+}
+
+/// Break `points` into runs that don't cross any of the `gaps`, so no line is drawn over them
+fn split_at_gaps(points: Vec<(f64, f32)>, gaps: &[Gap]) -> Vec<Vec<(f64, f32)>> {
+    let mut segments = vec![Vec::new()];
+    let mut last_time: Option<f64> = None;
+    for (time, value) in points {
+        if let Some(last_time) = last_time
+            && gaps
+                .iter()
+                .any(|gap| last_time <= gap.lost && time >= gap.back)
+        {
+            segments.push(Vec::new());
+        }
+        last_time = Some(time);
+        if let Some(segment) = segments.last_mut() {
+            segment.push((time, value));
+        }
+    }
+    segments
+}
+
+/// The background color for each classified state
+fn state_color(state: EmgState) -> RGBAColor {
+    match state {
+        EmgState::Relaxed => GREEN.mix(0.12),
+        EmgState::Intermediate => YELLOW.mix(0.2),
+        EmgState::Clenched => RED.mix(0.12),
+    }
+}
+
+/// The color of each classified state in the lane under the plot
+fn lane_color(state: EmgState) -> egui::Color32 {
+    match state {
+        EmgState::Relaxed => egui::Color32::from_rgb(70, 170, 70),
+        EmgState::Intermediate => egui::Color32::from_rgb(220, 190, 40),
+        EmgState::Clenched => egui::Color32::from_rgb(210, 60, 60),
+    }
+}
+
+/// Draw each lane's states as a row of colored segments in `rect`, with `times` spread
+/// across `x_range` so it lines up with the plot above, and the lane's name to the left
+pub fn draw_state_lanes(
+    ui: &egui::Ui,
+    rect: egui::Rect,
+    x_range: egui::Rangef,
+    times: Range<f64>,
+    lanes: &[(&str, Vec<StateSpan>)],
+) {
+    let painter = ui.painter_at(rect);
+    let row_height = rect.height() / lanes.len().max(1) as f32;
+    let seconds = (times.end - times.start).max(f64::EPSILON);
+    let x = |time: f64| {
+        let along = (time.clamp(times.start, times.end) - times.start) / seconds;
+        x_range.min + along as f32 * x_range.span()
+    };
+    for (row, (name, spans)) in lanes.iter().enumerate() {
+        let top = rect.top() + row as f32 * row_height;
+        let y_range = egui::Rangef::new(top + 1.0, top + row_height - 1.0);
+        for &(start, end, state) in spans {
+            // a span of one sample still gets a pixel
+            let (left, right) = (x(start), x(end));
+            let segment = egui::Rect::from_x_y_ranges(left..=right.max(left + 1.0), y_range);
+            painter.rect_filled(segment, 0.0, lane_color(state));
+        }
+        painter.text(
+            egui::pos2(x_range.min - 4.0, y_range.center()),
+            egui::Align2::RIGHT_CENTER,
+            *name,
+            egui::FontId::proportional(11.0),
+            ui.visuals().text_color(),
+        );
+    }
+}
+
+/// Draw `text` in a box next to the pointer, flipped to its other side near the edges of `rect`
+pub fn draw_hover_label(ui: &egui::Ui, rect: egui::Rect, pointer: egui::Pos2, text: String) {
+    let painter = ui.painter_at(rect);
+    let text_color = ui.visuals().text_color();
+    let galley = painter.layout_no_wrap(text, egui::FontId::monospace(12.0), text_color);
+    let mut corner = pointer + egui::vec2(12.0, 12.0);
+    if corner.x + galley.size().x > rect.right() {
+        corner.x = pointer.x - 12.0 - galley.size().x;
+    }
+    if corner.y + galley.size().y > rect.bottom() {
+        corner.y = pointer.y - 12.0 - galley.size().y;
+    }
+    let background = egui::Rect::from_min_size(corner, galley.size()).expand(4.0);
+    painter.rect_filled(background, 2.0, ui.visuals().extreme_bg_color);
+    painter.galley(corner, galley, text_color);
+}
+
+/// Convert an egui color to one plotters can draw with
+pub fn plotters_color(color: egui::Color32) -> RGBColor {
+    RGBColor(color.r(), color.g(), color.b())
+}
+
+/// The ranges of values of the visible channels on the left and right axes as shown in `units`,
+/// from `times.start` to `times.end` seconds. The left one is spread by `value_axis`
+/// and also covers `thresholds`.
+pub fn data_ranges(
+    channels: &Channels,
+    times: Range<f64>,
+    thresholds: &[f32],
+    units: &Units,
+    value_axis: ValueAxis,
+) -> (Option<Range<f32>>, Option<Range<f32>>) {
+    let on_axis = |axis: Axis| {
+        channels
+            .iter()
+            .filter(|channel| channel.visible && channel.axis == axis)
+            .filter_map(|channel| {
+                let (low, high) = channel.value_range(times.start, times.end)?;
+                let (low, high) = units.scale(channel).range(low, high);
+                Some(match axis {
+                    Axis::Left => (value_axis.apply(low), value_axis.apply(high)),
+                    Axis::Right => (low, high),
+                })
+            })
+            .reduce(|(low, high), (start, end)| (low.min(start), high.max(end)))
+            .map(|(low, high)| low..high)
+    };
+
+    // keep the thresholds on screen even when the signal is nowhere near them
+    let left = thresholds
+        .iter()
+        .fold(on_axis(Axis::Left), |range, &value| {
+            Some(match range {
+                Some(range) => range.start.min(value)..range.end.max(value),
+                None => value..value,
+            })
+        });
+    (left, on_axis(Axis::Right))
+}
+
+/// The scales, labels and colors of the plot
+pub struct Axes {
+    /// Values on the left axis
+    pub left: Range<f32>,
+    /// Values on the right axis, `None` to leave it off
+    pub right: Option<Range<f32>>,
+    /// What the data is, shown above the plot
+    pub caption: String,
+    /// The units of the values
+    pub value_label: &'static str,
+    /// What the channels are converted to before they are drawn, unless normalized
+    pub units: Units,
+    /// How the values are spread up the left axis, unless normalized
+    pub value_axis: ValueAxis,
+    pub style: ChartStyle,
+    /// Only draw the channels on this axis, for a pane of their own
+    pub only: Option<Axis>,
+}
+
+/// An image of the plot to be saved, PNG unless the path ends in `.svg`
+pub struct ImageExport {
+    pub path: PathBuf,
+    /// Width and height in pixels
+    pub size: (u32, u32),
+    /// A line about the session written under the plot
+    pub footer: String,
+}
+
+/// Draw every visible channel from `times.start` to `times.end` seconds.
+/// When `normalized` each channel is scaled so its visible values fill 0 to 1.
+pub fn draw(
+    ui: &egui::Ui,
+    channels: &Channels,
+    times: Range<f64>,
+    normalized: bool,
+    axes: &Axes,
+    overlay: &Overlay,
+) -> PlotArea {
+    let root = EguiBackend::new(ui).into_drawing_area();
+    let area = draw_chart(&root, channels, times, normalized, axes, overlay).unwrap();
+    root.present().unwrap();
+    area
+}
+
+/// Draw the plot the same as [`draw`] does into an image file, no matter the size of the window
+pub fn export_image(
+    image: &ImageExport,
+    channels: &Channels,
+    times: Range<f64>,
+    normalized: bool,
+    axes: &Axes,
+    overlay: &Overlay,
+) -> Result<(), String> {
+    let svg = image
+        .path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    let result = if svg {
+        let root = SVGBackend::new(&image.path, image.size).into_drawing_area();
+        draw_image(
+            &root,
+            &image.footer,
+            channels,
+            times,
+            normalized,
+            axes,
+            overlay,
+        )
+        .map_err(|error| error.to_string())
+    } else {
+        let root = BitMapBackend::new(&image.path, image.size).into_drawing_area();
+        draw_image(
+            &root,
+            &image.footer,
+            channels,
+            times,
+            normalized,
+            axes,
+            overlay,
+        )
+        .map_err(|error| error.to_string())
+    };
+    // the two backends have their own error types, so both are turned into text
+    result.map_err(|error| format!("Unable to write {}: {error}", image.path.display()))
+}
+
+/// The chart with `footer` under it
+fn draw_image<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    footer: &str,
+    channels: &Channels,
+    times: Range<f64>,
+    normalized: bool,
+    axes: &Axes,
+    overlay: &Overlay,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&axes.style.background)?;
+    let height = root.dim_in_pixel().1.saturating_sub(FOOTER_HEIGHT);
+    let (chart, bottom) = root.split_vertically(height as i32);
+    draw_chart(&chart, channels, times, normalized, axes, overlay)?;
+    bottom.draw_text(
+        footer,
+        &("sans-serif", axes.style.label_size)
+            .into_font()
+            .color(&axes.style.text),
+        (10, 4),
+    )?;
+    root.present()
+}
+
+fn draw_chart<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    channels: &Channels,
+    times: Range<f64>,
+    normalized: bool,
+    axes: &Axes,
+    overlay: &Overlay,
+) -> Result<PlotArea, DrawingAreaErrorKind<DB::ErrorType>> {
+    let style = &axes.style;
+    root.fill(&style.background)?;
+
+    let values = axes.left.clone();
+    let right = axes.right.clone();
+
+    let mut builder = ChartBuilder::on(root);
+    if !axes.caption.is_empty() {
+        builder.caption(
+            axes.caption.as_str(),
+            ("sans-serif", style.caption_size)
+                .into_font()
+                .color(&style.text),
+        );
+    }
+    let mut chart = builder
+        .margin(5)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .right_y_label_area_size(if right.is_some() { 50 } else { 0 })
+        .build_cartesian_2d(times.clone(), values.clone())?
+        .set_secondary_coord(times.clone(), right.clone().unwrap_or(0.0..1.0));
+
+    chart
+        .configure_mesh()
+        .x_desc("time (s)")
+        .y_desc(axes.value_axis.label(axes.value_label))
+        .y_label_formatter(&|&value| axes.value_axis.tick(value))
+        .axis_style(style.text)
+        .label_style(
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .axis_desc_style(
+            ("sans-serif", style.axis_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .light_line_style(style.mesh)
+        .bold_line_style(style.bold_mesh)
+        .draw()?;
+    if right.is_some() {
+        chart
+            .configure_secondary_axes()
+            .y_desc(axes.value_label)
+            .axis_style(style.text)
+            .label_style(
+                ("sans-serif", style.label_size)
+                    .into_font()
+                    .color(&style.text),
+            )
+            .axis_desc_style(
+                ("sans-serif", style.axis_size)
+                    .into_font()
+                    .color(&style.text),
+            )
+            .draw()?;
+    }
+
+    chart.draw_series(overlay.states.iter().map(|&(start, end, state)| {
+        Rectangle::new(
+            [(start, values.start), (end, values.end)],
+            state_color(state).filled(),
+        )
+    }))?;
+
+    chart.draw_series(overlay.clipped.iter().map(|&(start, end)| {
+        Rectangle::new(
+            [(start, values.start), (end, values.end)],
+            style.warning.mix(0.3).filled(),
+        )
+    }))?;
+
+    chart.draw_series(overlay.artifacts.iter().map(|&(start, end)| {
+        Rectangle::new(
+            [(start, values.start), (end, values.end)],
+            style.trigger.mix(0.25).filled(),
+        )
+    }))?;
+
+    let visible_gaps = overlay
+        .gaps
+        .iter()
+        .filter(|gap| gap.lost <= times.end && gap.back >= times.start);
+    for gap in visible_gaps {
+        let (lost, back) = (gap.lost.max(times.start), gap.back.min(times.end));
+        chart.draw_series(std::iter::once(Rectangle::new(
+            [(lost, values.start), (back, values.end)],
+            style.mesh.filled(),
+        )))?;
+        chart.draw_series(std::iter::once(Text::new(
+            gap.cause.describe(),
+            (lost, values.end),
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )))?;
+    }
+
+    if let Some((start, end)) = overlay.selection {
+        chart.draw_series(std::iter::once(Rectangle::new(
+            [(start, values.start), (end, values.end)],
+            style.selection.mix(0.15).filled(),
+        )))?;
+    }
+
+    let width = chart.plotting_area().dim_in_pixel().0;
+    let shown = channels
+        .iter()
+        .filter(|channel| channel.visible && axes.only.is_none_or(|axis| channel.axis == axis));
+    for channel in shown {
+        let color = plotters_color(channel.color);
+        let points = decimate(channel.between(times.start, times.end), &times, width);
+
+        let points: Vec<(f64, f32)> = if normalized {
+            let (low, high) = channel
+                .value_range(times.start, times.end)
+                .unwrap_or((0.0, 1.0));
+            let span = (high - low).max(f32::EPSILON);
+            points
+                .into_iter()
+                .map(|(time, value)| (time, (value - low) / span))
+                .collect()
+        } else {
+            let scale = axes.units.scale(channel);
+            // channels drawn against the right axis stay linear
+            let value_axis = if channel.axis == Axis::Right && right.is_some() {
+                ValueAxis::default()
+            } else {
+                axes.value_axis
+            };
+            points
+                .into_iter()
+                .map(|(time, value)| (time, value_axis.apply(scale.apply(value))))
+                .collect()
+        };
+        let secondary = !normalized && channel.axis == Axis::Right && right.is_some();
+
+        for (i, segment) in split_at_gaps(points, &overlay.gaps).into_iter().enumerate() {
+            let series = if secondary {
+                chart.draw_secondary_series(LineSeries::new(
+                    segment,
+                    color.stroke_width(style.line_width),
+                ))?
+            } else {
+                chart.draw_series(LineSeries::new(
+                    segment,
+                    color.stroke_width(style.line_width),
+                ))?
+            };
+            // one legend entry for the whole channel
+            if i == 0 {
+                series
+                    .label(channel.name.as_str())
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            }
+        }
+    }
+
+    if let Some(time) = overlay.trigger {
+        chart.draw_series(LineSeries::new(
+            [(time, values.start), (time, values.end)],
+            style.trigger.stroke_width(style.marker_width),
+        ))?;
+    }
+
+    for (times_of, color) in [
+        (&overlay.onsets, style.onset),
+        (&overlay.offsets, style.warning),
+    ] {
+        for &time in times_of.iter().filter(|time| times.contains(time)) {
+            chart.draw_series(LineSeries::new(
+                [(time, values.start), (time, values.end)],
+                color.mix(0.7).stroke_width(style.marker_width),
+            ))?;
+        }
+    }
+
+    let visible_annotations = overlay
+        .annotations
+        .iter()
+        .filter(|annotation| times.contains(&annotation.time));
+    for annotation in visible_annotations {
+        chart.draw_series(LineSeries::new(
+            [
+                (annotation.time, values.start),
+                (annotation.time, values.end),
+            ],
+            style.text.mix(0.6).stroke_width(style.marker_width),
+        ))?;
+        chart.draw_series(std::iter::once(Text::new(
+            annotation.note.clone(),
+            (annotation.time, values.end),
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )))?;
+    }
+
+    for &threshold in &overlay.thresholds {
+        chart.draw_series(LineSeries::new(
+            [(times.start, threshold), (times.end, threshold)],
+            style.text.stroke_width(style.marker_width * 2),
+        ))?;
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(style.background.mix(0.8))
+        .border_style(style.text)
+        .label_font(
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .draw()?;
+
+    let (x_pixels, y_pixels) = chart.plotting_area().get_pixel_range();
+    Ok(PlotArea {
+        x_pixels,
+        y_pixels,
+        values,
+        value_axis: axes.value_axis,
+    })
+}
+
+/// Draw a spectrum as magnitude against frequency in Hz. `bins` are in dB relative to 1,
+/// and are shown spread by `value_axis`.
+pub fn draw_spectrum(
+    ui: &egui::Ui,
+    bins: &[(f32, f32)],
+    value_axis: ValueAxis,
+    color: egui::Color32,
+    style: &ChartStyle,
+) {
+    let root = EguiBackend::new(ui).into_drawing_area();
+    root.fill(&style.background).unwrap();
+
+    let max_frequency = bins
+        .last()
+        .map_or(1.0, |&(frequency, _)| frequency.max(1.0));
+    let points: Vec<(f32, f32)> = bins
+        .iter()
+        .map(|&(frequency, db)| (frequency, value_axis.apply(10f32.powf(db / 20.0))))
+        .collect();
+    let loudest = points
+        .iter()
+        .map(|&(_, magnitude)| magnitude)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let loudest = if loudest.is_finite() { loudest } else { 0.0 };
+    // anything much quieter than the loudest peak is just noise floor
+    let magnitudes = match value_axis.scale {
+        AxisScale::Linear => 0.0..loudest.max(f32::EPSILON) * 1.05,
+        AxisScale::Log10 => loudest - SPECTRUM_RANGE_DB / 20.0..loudest + 0.25,
+        AxisScale::Decibels => loudest - SPECTRUM_RANGE_DB..loudest + 5.0,
+    };
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(0.0..max_frequency, magnitudes.clone())
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (Hz)")
+        .y_desc(format!("Magnitude, {}", value_axis.label("ADC counts")))
+        .y_label_formatter(&|&value| value_axis.tick(value))
+        .axis_style(style.text)
+        .label_style(
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .axis_desc_style(
+            ("sans-serif", style.axis_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .light_line_style(style.mesh)
+        .bold_line_style(style.bold_mesh)
+        .draw()
+        .unwrap();
+
+    chart
+        .draw_series(LineSeries::new(
+            points
+                .into_iter()
+                .map(|(frequency, magnitude)| (frequency, magnitude.max(magnitudes.start))),
+            plotters_color(color).stroke_width(style.line_width),
+        ))
+        .unwrap();
+
+    root.present().unwrap();
+}
+
+/// Draw a histogram as bars of how many samples fell in each bin, with lines up it at
+/// `thresholds`. Returns where the chart's data area is across, in pixels from the left of the `Ui`.
+pub fn draw_histogram(
+    ui: &egui::Ui,
+    histogram: &HistogramView,
+    thresholds: &[f32],
+    color: egui::Color32,
+    value_label: &str,
+    style: &ChartStyle,
+) -> Range<i32> {
+    let root = EguiBackend::new(ui).into_drawing_area();
+    root.fill(&style.background).unwrap();
+    if histogram.counts.is_empty() {
+        root.present().unwrap();
+        return 0..0;
+    }
+
+    let log_scale = histogram.log_scale;
+    // on a log scale a bar of one count still has to show, so one is added to each
+    let heights: Vec<f32> = histogram
+        .counts
+        .iter()
+        .map(|&count| {
+            if log_scale {
+                (count as f32 + 1.0).log10()
+            } else {
+                count as f32
+            }
+        })
+        .collect();
+    let top = heights.iter().copied().fold(0.0, f32::max).max(1.0) * 1.05;
+    let values = histogram.values();
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(values.clone(), 0.0..top)
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_desc(value_label)
+        .y_desc(if log_scale {
+            "Samples (log)"
+        } else {
+            "Samples"
+        })
+        .y_label_formatter(&|&height| {
+            let count = if log_scale {
+                10f32.powf(height) - 1.0
+            } else {
+                height
+            };
+            format!("{count:.0}")
+        })
+        .axis_style(style.text)
+        .label_style(
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .axis_desc_style(
+            ("sans-serif", style.axis_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .light_line_style(style.mesh)
+        .bold_line_style(style.bold_mesh)
+        .draw()
+        .unwrap();
+
+    let color = plotters_color(color);
+    chart
+        .draw_series(heights.iter().enumerate().map(|(bin, &height)| {
+            let (start, end) = histogram.bin_range(bin);
+            let fill = if histogram.selected == Some(bin) {
+                style.text.filled()
+            } else {
+                color.filled()
+            };
+            Rectangle::new([(start, 0.0), (end, height)], fill)
+        }))
+        .unwrap();
+
+    for &threshold in thresholds
+        .iter()
+        .filter(|threshold| values.contains(threshold))
+    {
+        chart
+            .draw_series(LineSeries::new(
+                [(threshold, 0.0), (threshold, top)],
+                style.text.stroke_width(style.marker_width * 2),
+            ))
+            .unwrap();
+    }
+
+    let (x_pixels, _) = chart.plotting_area().get_pixel_range();
+    root.present().unwrap();
+    x_pixels
+}
+
+/// Draw a cross-correlation as correlation against lag in ms, with a line at the peak
+pub fn draw_correlation(
+    ui: &egui::Ui,
+    points: &[(f32, f32)],
+    peak: Option<(f32, f32)>,
+    color: egui::Color32,
+    style: &ChartStyle,
+) {
+    let root = EguiBackend::new(ui).into_drawing_area();
+    root.fill(&style.background).unwrap();
+
+    let max_lag = points.iter().map(|&(lag, _)| lag.abs()).fold(1.0, f32::max);
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(-max_lag..max_lag, -1.0f32..1.0)
+        .unwrap();
+
+    chart
+        .configure_mesh()
+        .x_desc("Lag (ms)")
+        .y_desc("Correlation")
+        .axis_style(style.text)
+        .label_style(
+            ("sans-serif", style.label_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .axis_desc_style(
+            ("sans-serif", style.axis_size)
+                .into_font()
+                .color(&style.text),
+        )
+        .light_line_style(style.mesh)
+        .bold_line_style(style.bold_mesh)
+        .draw()
+        .unwrap();
+
+    chart
+        .draw_series(LineSeries::new(
+            points.iter().copied(),
+            plotters_color(color).stroke_width(style.line_width),
+        ))
+        .unwrap();
+
+    if let Some((lag, _)) = peak {
+        chart
+            .draw_series(LineSeries::new(
+                [(lag, -1.0), (lag, 1.0)],
+                style.text.stroke_width(style.marker_width),
+            ))
+            .unwrap();
+    }
+
+    root.present().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaps::GapCause;
+
+    /// `count` points a millisecond apart, all at `value`
+    fn flat(count: usize, value: f32) -> Vec<(f64, f32)> {
+        (0..count)
+            .map(|index| (index as f64 / 1000.0, value))
+            .collect()
+    }
+
+    #[test]
+    fn decimate_keeps_fewer_points_than_columns() {
+        let points: Vec<(f64, f32)> = (0..50).map(|index| (index as f64, index as f32)).collect();
+        assert_eq!(decimate(points.iter(), &(0.0..50.0), 100), points);
+        // up to two a column are still drawn as they are
+        assert_eq!(decimate(points.iter(), &(0.0..50.0), 25), points);
+    }
+
+    #[test]
+    fn decimate_of_nothing_is_nothing() {
+        assert!(decimate([].iter(), &(0.0..1.0), 100).is_empty());
+        assert!(decimate([].iter(), &(0.0..1.0), 0).is_empty());
+    }
+
+    #[test]
+    fn decimate_keeps_a_spike_in_one_column() {
+        // ten seconds across 100 columns is 100 points a column
+        let mut points = flat(10_000, 500.0);
+        points[4321].1 = 1000.0;
+        points[4350].1 = 0.0;
+        let decimated = decimate(points.iter(), &(0.0..10.0), 100);
+
+        assert!(decimated.len() <= 200, "{} points", decimated.len());
+        assert!(decimated.contains(&points[4321]));
+        assert!(decimated.contains(&points[4350]));
+        // the spike's column is the only one that isn't flat
+        let column = |time: f64| (time * 10.0).floor() as i64;
+        for &(time, value) in &decimated {
+            if column(time) != 43 {
+                assert_eq!(value, 500.0, "at {time}");
+            }
+        }
+        // and its points stay in time order, the high came first
+        assert!(decimated.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn decimate_puts_a_point_on_a_column_edge_in_the_next_column() {
+        // two columns half a second wide, the point at 0.5 s starts the second
+        let points: Vec<(f64, f32)> = (0..10)
+            .map(|index| (index as f64 / 10.0, index as f32))
+            .collect();
+        assert_eq!(
+            decimate(points.iter(), &(0.0..1.0), 2),
+            [(0.0, 0.0), (0.4, 4.0), (0.5, 5.0), (0.9, 9.0)]
+        );
+    }
+
+    /// A dropout from `lost` to `back`
+    fn gap(lost: f64, back: f64) -> Gap {
+        Gap {
+            lost,
+            back,
+            cause: GapCause::Reconnect,
+        }
+    }
+
+    /// The times in each segment `split_at_gaps` makes of points at `times`
+    fn split_times(times: &[f64], gaps: &[Gap]) -> Vec<Vec<f64>> {
+        let points = times.iter().map(|&time| (time, 0.0)).collect();
+        split_at_gaps(points, gaps)
+            .into_iter()
+            .map(|segment| segment.into_iter().map(|(time, _)| time).collect())
+            .collect()
+    }
+
+    #[test]
+    fn split_at_a_gap_in_the_middle() {
+        assert_eq!(
+            split_times(&[0.0, 1.0, 4.0, 5.0], &[gap(1.0, 4.0)]),
+            [vec![0.0, 1.0], vec![4.0, 5.0]]
+        );
+    }
+
+    #[test]
+    fn gaps_at_the_ends_dont_split() {
+        // nothing before the first point or after the last to join to
+        let gaps = [gap(-5.0, 0.0), gap(5.0, f64::INFINITY)];
+        assert_eq!(
+            split_times(&[0.0, 1.0, 4.0, 5.0], &gaps),
+            [vec![0.0, 1.0, 4.0, 5.0]]
+        );
+        // a gap at the very start of the points splits off the first one
+        assert_eq!(
+            split_times(&[0.0, 3.0, 4.0], &[gap(0.0, 3.0)]),
+            [vec![0.0], vec![3.0, 4.0]]
+        );
+        // and one at the very end the last one
+        assert_eq!(
+            split_times(&[0.0, 1.0, 4.0], &[gap(1.0, 4.0)]),
+            [vec![0.0, 1.0], vec![4.0]]
+        );
+    }
+
+    #[test]
+    fn back_to_back_gaps_leave_the_point_between_on_its_own() {
+        assert_eq!(
+            split_times(&[0.0, 1.0, 2.0, 3.0, 4.0], &[gap(1.0, 2.0), gap(2.0, 3.0)]),
+            [vec![0.0, 1.0], vec![2.0], vec![3.0, 4.0]]
+        );
+    }
+
+    #[test]
+    fn a_gap_that_doesnt_cover_a_step_doesnt_split() {
+        // the gap is inside the step from 1 to 4, but there are points in it
+        assert_eq!(
+            split_times(&[0.0, 1.0, 2.0, 4.0], &[gap(1.5, 3.0)]),
+            [vec![0.0, 1.0, 2.0, 4.0]]
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use serialport::SerialPortType;
+
+/// USB vendor IDs used by Arduino boards and the USB-serial chips on Nano clones
+const ARDUINO_VENDOR_IDS: [u16; 5] = [
+    0x2341, // Arduino
+    0x2A03, // Arduino.org
+    0x1A86, // WCH CH340, used on most Nano clones
+    0x0403, // FTDI FT232R, used on the original Nano
+    0x10C4, // Silicon Labs CP210x
+];
+
+/// Baud rates offered in the picker, the firmware uses 57600
+pub const BAUD_RATES: [u32; 7] = [9600, 19200, 38400, 57600, 115200, 230400, 250000];
+
+/// A serial port that can be connected to
+pub struct PortEntry {
+    pub name: String,
+    /// What the OS says is on the other end of the port
+    pub description: String,
+    /// If the USB IDs match a board the hand could be running on
+    pub likely_arduino: bool,
+}
+
+/// List the serial ports on this machine, likely Arduinos first
+pub fn list_ports() -> Vec<PortEntry> {
+    let Ok(ports) = serialport::available_ports() else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<PortEntry> = ports
+        .into_iter()
+        .map(|port| match port.port_type {
+            SerialPortType::UsbPort(usb) => PortEntry {
+                name: port.port_name,
+                description: usb
+                    .product
+                    .unwrap_or_else(|| format!("USB {:04x}:{:04x}", usb.vid, usb.pid)),
+                likely_arduino: ARDUINO_VENDOR_IDS.contains(&usb.vid),
+            },
+            SerialPortType::BluetoothPort => PortEntry {
+                name: port.port_name,
+                description: String::from("Bluetooth"),
+                likely_arduino: false,
+            },
+            SerialPortType::PciPort | SerialPortType::Unknown => PortEntry {
+                name: port.port_name,
+                description: String::new(),
+                likely_arduino: false,
+            },
+        })
+        .collect();
+
+    entries.sort_by_key(|entry| !entry.likely_arduino);
+    entries
+}
@@ -0,0 +1,38 @@
+use std::collections::VecDeque;
+
+/// How many seconds of sample times the rate is worked out over
+const WINDOW_SECONDS: f64 = 2.0;
+
+/// Works out how many samples a second are really coming in, from their times
+#[derive(Default)]
+pub struct RateMeter {
+    times: VecDeque<f64>,
+}
+
+impl RateMeter {
+    pub fn push(&mut self, time: f64) {
+        // the time went backwards, so a new stream started
+        if self.times.back().is_some_and(|&last| time < last) {
+            self.clear();
+        }
+        self.times.push_back(time);
+        while self
+            .times
+            .front()
+            .is_some_and(|&first| time - first > WINDOW_SECONDS)
+        {
+            self.times.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.times.clear();
+    }
+
+    /// Samples per second, `None` until there are enough to tell
+    pub fn rate(&self) -> Option<f32> {
+        let (&first, &last) = (self.times.front()?, self.times.back()?);
+        let span = last - first;
+        (self.times.len() >= 2 && span > 0.0).then(|| ((self.times.len() - 1) as f64 / span) as f32)
+    }
+}
@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use hand_core::{EmgState, Thresholds};
+
+use crate::noise::NoiseReport;
+use crate::session::SessionMetadata;
+use crate::stats::Stats;
+
+/// Seconds of plot shown around each marker in a report
+pub const MARKER_IMAGE_SECONDS: f64 = 5.0;
+
+/// The numbers for one channel over the whole session
+pub struct ChannelSummary {
+    pub name: String,
+    pub stats: Stats,
+    /// How much of a raw EMG channel was clipped, `None` for other channels
+    pub clipped_percent: Option<f32>,
+}
+
+/// What the classifier made of the session
+pub struct StateSummary {
+    /// The channel that was classified
+    pub channel: String,
+    pub thresholds: Thresholds,
+    /// (start, end) of every contraction, from leaving Relaxed to getting back to it
+    pub contractions: Vec<(f64, f64)>,
+    /// Seconds spent in Relaxed, Intermediate and Clenched
+    pub time_in_state: [(EmgState, f64); 3],
+}
+
+impl StateSummary {
+    /// Sum up the (start, end, state) spans the classifier found on `channel`
+    pub fn from_spans(
+        channel: &str,
+        thresholds: Thresholds,
+        spans: &[(f64, f64, EmgState)],
+    ) -> Self {
+        let mut time_in_state = [
+            (EmgState::Relaxed, 0.0),
+            (EmgState::Intermediate, 0.0),
+            (EmgState::Clenched, 0.0),
+        ];
+        let mut contractions: Vec<(f64, f64)> = Vec::new();
+        let mut contracted = false;
+        for &(start, end, state) in spans {
+            if let Some((_, seconds)) = time_in_state.iter_mut().find(|(known, _)| *known == state)
+            {
+                *seconds += end - start;
+            }
+            match (state, contractions.last_mut()) {
+                (EmgState::Relaxed, _) => contracted = false,
+                // Intermediate and Clenched next to each other are the same contraction
+                (_, Some((_, contraction_end))) if contracted => *contraction_end = end,
+                _ => {
+                    contractions.push((start, end));
+                    contracted = true;
+                }
+            }
+        }
+
+        Self {
+            channel: channel.to_owned(),
+            thresholds,
+            contractions,
+            time_in_state,
+        }
+    }
+}
+
+/// A plot image of a marked part of the session
+pub struct MarkedImage {
+    pub time: f64,
+    pub note: String,
+    /// The image's file name, next to the report. `None` if it couldn't be saved.
+    pub file_name: Option<String>,
+}
+
+/// A one page summary of a session
+pub struct SessionReport {
+    pub metadata: SessionMetadata,
+    /// Where the samples came from
+    pub source: String,
+    /// Times of the first and last samples
+    pub span: (f64, f64),
+    /// What the values are in
+    pub value_label: &'static str,
+    pub channels: Vec<ChannelSummary>,
+    /// Seconds of artifacts left out of the channel numbers, `None` if they were kept in
+    pub excluded_seconds: Option<f64>,
+    pub states: Option<StateSummary>,
+    pub images: Vec<MarkedImage>,
+}
+
+impl SessionReport {
+    /// Write the report to `path` as Markdown
+    pub fn write_markdown(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let metadata = &self.metadata;
+
+        writeln!(writer, "# Session report\n")?;
+        writeln!(writer, "| | |\n|---|---|")?;
+        let (start, end) = self.span;
+        let mut rows = vec![
+            ("Source", self.source.clone()),
+            ("Duration", format!("{:.1} s", end - start)),
+        ];
+        if !metadata.started.is_empty() {
+            rows.push(("Started", metadata.started.clone()));
+        }
+        if let Some(sample_rate) = metadata.sample_rate {
+            rows.push(("Sample rate", format!("{sample_rate} Hz")));
+        }
+        for (label, text) in [
+            ("Firmware", &metadata.firmware_version),
+            ("Filter", &metadata.filter_config),
+            ("Electrodes", &metadata.electrode_placement),
+        ] {
+            if !text.is_empty() {
+                rows.push((label, text.clone()));
+            }
+        }
+        for (label, text) in rows {
+            writeln!(writer, "| {label} | {} |", cell(&text))?;
+        }
+        if !metadata.notes.is_empty() {
+            writeln!(writer, "\n{}", metadata.notes)?;
+        }
+
+        writeln!(writer, "\n## Channels\n")?;
+        writeln!(writer, "Values in {}.\n", self.value_label)?;
+        if let Some(seconds) = self.excluded_seconds {
+            writeln!(
+                writer,
+                "{seconds:.2} s marked as motion artifacts are left out.\n"
+            )?;
+        }
+        writeln!(
+            writer,
+            "| Channel | Min | Max | Mean | RMS | Std dev | Samples | Clipped |"
+        )?;
+        writeln!(writer, "|---|---|---|---|---|---|---|---|")?;
+        for channel in &self.channels {
+            let stats = &channel.stats;
+            let clipped = channel
+                .clipped_percent
+                .map_or(String::from("-"), |percent| format!("{percent:.2} %"));
+            writeln!(
+                writer,
+                "| {} | {:.3} | {:.3} | {:.3} | {:.3} | {:.3} | {} | {clipped} |",
+                cell(&channel.name),
+                stats.min,
+                stats.max,
+                stats.mean,
+                stats.rms,
+                stats.std_dev,
+                stats.count,
+            )?;
+        }
+
+        if let Some(states) = &self.states {
+            write_states(&mut writer, states)?;
+        }
+        if let Some(noise) = &metadata.noise_check {
+            write_noise(&mut writer, noise)?;
+        }
+
+        if !self.images.is_empty() {
+            writeln!(writer, "\n## Marked regions")?;
+        }
+        for image in &self.images {
+            writeln!(writer, "\n### {:.1} s: {}\n", image.time, image.note)?;
+            match &image.file_name {
+                Some(file_name) => writeln!(writer, "![{}]({file_name})", image.note)?,
+                None => writeln!(writer, "The plot image couldn't be saved.")?,
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+fn write_states(writer: &mut impl Write, states: &StateSummary) -> io::Result<()> {
+    let thresholds = &states.thresholds;
+    writeln!(writer, "\n## Contractions\n")?;
+    writeln!(
+        writer,
+        "Classified on {} with the firmware's classifier, thresholds {} and {} with {} hysteresis.\n",
+        states.channel, thresholds.intermediate, thresholds.clenched, thresholds.hysteresis
+    )?;
+
+    let durations: Vec<f64> = states
+        .contractions
+        .iter()
+        .map(|(start, end)| end - start)
+        .collect();
+    let total: f64 = durations.iter().sum();
+    writeln!(writer, "| | |\n|---|---|")?;
+    writeln!(writer, "| Contractions | {} |", durations.len())?;
+    if !durations.is_empty() {
+        let longest = durations.iter().copied().fold(0.0, f64::max);
+        writeln!(writer, "| Total | {total:.2} s |")?;
+        writeln!(
+            writer,
+            "| Average | {:.2} s |",
+            total / durations.len() as f64
+        )?;
+        writeln!(writer, "| Longest | {longest:.2} s |")?;
+    }
+
+    let classified: f64 = states
+        .time_in_state
+        .iter()
+        .map(|(_, seconds)| seconds)
+        .sum();
+    writeln!(writer, "\n| State | Time | Share |\n|---|---|---|")?;
+    for (state, seconds) in states.time_in_state {
+        writeln!(
+            writer,
+            "| {state:?} | {seconds:.2} s | {:.1} % |",
+            seconds * 100.0 / classified.max(f64::EPSILON)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_noise(writer: &mut impl Write, noise: &NoiseReport) -> io::Result<()> {
+    writeln!(writer, "\n## Noise check\n")?;
+    writeln!(writer, "| | |\n|---|---|")?;
+    writeln!(writer, "| Channel | {} |", cell(&noise.channel))?;
+    writeln!(writer, "| Noise RMS | {:.2} ADC counts |", noise.rms)?;
+    if let Some((fifty, sixty)) = noise.mains {
+        writeln!(writer, "| 50 Hz hum | {fifty:.2} ADC counts |")?;
+        writeln!(writer, "| 60 Hz hum | {sixty:.2} ADC counts |")?;
+    }
+    if let Some(snr) = noise.snr_db {
+        writeln!(writer, "| Signal to noise | {snr:.1} dB |")?;
+    }
+    writeln!(writer, "| Verdict | {} |", cell(&noise.verdict))
+}
+
+/// `text` made safe for a Markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
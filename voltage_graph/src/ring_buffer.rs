@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::collections::vec_deque::Iter;
+use std::ops::RangeBounds;
+
+/// The fewest items room is made for at once
+const MIN_GROWTH: usize = 64;
+
+/// A buffer that holds up to a fixed number of items, dropping the oldest when it is full.
+/// It only grows as items are added, so a long capacity costs nothing until it fills,
+/// and it never allocates room for more than its capacity.
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            items: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Add an item to the end, returning the oldest item if it had to be dropped
+    pub fn push(&mut self, item: T) -> Option<T> {
+        let dropped = if self.items.len() == self.capacity {
+            self.items.pop_front()
+        } else {
+            None
+        };
+        if self.items.len() == self.items.capacity() {
+            // double like a VecDeque would, but stop at the capacity
+            let grow = self.items.len().max(MIN_GROWTH);
+            self.items
+                .reserve_exact(grow.min(self.capacity - self.items.len()));
+        }
+        self.items.push_back(item);
+        dropped
+    }
+
+    /// Change how many items are kept, dropping the oldest ones if it shrinks
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.items.len() > self.capacity {
+            self.items.pop_front();
+        }
+        self.items.shrink_to(self.capacity);
+    }
+
+    /// The newest item
+    pub fn back(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    /// The oldest item
+    pub fn front(&self) -> Option<&T> {
+        self.items.front()
+    }
+
+    /// Items in a range of positions, where 0 is the oldest
+    pub fn range(&self, range: impl RangeBounds<usize>) -> Iter<'_, T> {
+        self.items.range(range)
+    }
+
+    /// The position of the first item that `pred` is false for,
+    /// the items must already be sorted so `pred` is true for a prefix of them
+    pub fn partition_point(&self, pred: impl FnMut(&T) -> bool) -> usize {
+        self.items.partition_point(pred)
+    }
+}
+
+impl<T> From<Vec<T>> for RingBuffer<T> {
+    /// Make a buffer that is exactly big enough for `items`
+    fn from(items: Vec<T>) -> Self {
+        Self {
+            capacity: items.len().max(1),
+            items: VecDeque::from(items),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest() {
+        let mut buffer = RingBuffer::new(3);
+        for item in 0..3 {
+            assert_eq!(buffer.push(item), None);
+        }
+        assert_eq!(buffer.push(3), Some(0));
+        assert_eq!(buffer.range(..).copied().collect::<Vec<_>>(), [1, 2, 3]);
+
+        buffer.set_capacity(2);
+        assert_eq!((buffer.front(), buffer.back()), (Some(&2), Some(&3)));
+    }
+
+    #[test]
+    fn room_stops_at_the_capacity() {
+        // just past a power of two, where a VecDeque doubling would nearly double the memory
+        let capacity = 1025;
+        let mut buffer = RingBuffer::new(capacity);
+        for item in 0..capacity * 2 {
+            buffer.push(item);
+            assert!(
+                buffer.items.capacity() <= capacity,
+                "{}",
+                buffer.items.capacity()
+            );
+        }
+    }
+}
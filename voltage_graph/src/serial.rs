@@ -0,0 +1,773 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::pacing::LIVE_REPAINT_INTERVAL;
+
+/// How long a read waits before checking if the reader should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// How often to look for a dropped port coming back
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+/// How long without a UDP packet before the sender is treated as dropped
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Bigger than any UDP packet
+const MAX_PACKET: usize = 65536;
+/// Fields the firmware can put on a line with the time it took the sample,
+/// with how many of their units make a second
+const TIMESTAMP_FIELDS: [(&str, f64); 2] = [("t_us", 1e6), ("t_ms", 1e3)];
+/// A field the firmware can count up by one on every line, so lost lines can be noticed
+const SEQUENCE_FIELD: &str = "seq";
+
+/// Messages sent from the reader thread to the app
+pub enum SerialEvent {
+    /// The port was opened and data is being read
+    Connected,
+    /// Named values read from one line, `time` is seconds since connecting
+    Samples {
+        time: f64,
+        values: Vec<(String, f32)>,
+        /// If `time` came from the firmware's own timestamp
+        timestamped: bool,
+        /// The line's sequence number, if the firmware sends one
+        sequence: Option<u64>,
+    },
+    /// A line that wasn't telemetry, like the firmware's reply to a command
+    Text(String),
+    /// The port dropped at `time` and the reader is waiting for it to come back
+    Reconnecting { time: f64, reason: String },
+    /// The port was closed or could not be read, with the reason
+    Disconnected(String),
+}
+
+/// State of a connection to a board
+pub enum ConnectionStatus {
+    Disconnected,
+    Connecting,
+    Connected,
+    /// The connection dropped and the port is being watched for coming back, with the reason
+    Reconnecting(String),
+    /// The connection failed or dropped, with the reason
+    Lost(String),
+}
+
+/// Which port to read and how
+pub struct PortConfig {
+    pub port_name: String,
+    pub baud_rate: u32,
+    /// Lines without a timestamp are spaced by this if it is given,
+    /// otherwise they get the time they arrived
+    pub sample_rate: Option<f32>,
+    /// Wait for the port to come back after it drops, instead of giving up
+    pub auto_reconnect: bool,
+}
+
+/// Which UDP port to listen on for telemetry, for boards on a wireless bridge
+pub struct UdpConfig {
+    pub port: u16,
+    /// Lines without a timestamp are spaced by this if it is given,
+    /// otherwise they get the time they arrived
+    pub sample_rate: Option<f32>,
+}
+
+/// State the app and the reader thread both see
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool,
+    /// Telemetry lines with a field on them that couldn't be read, like ones cut off or garbled
+    malformed_lines: AtomicUsize,
+}
+
+/// A serial port, or a UDP port getting the same lines, being read on a background thread
+pub struct SerialSource {
+    receiver: Receiver<SerialEvent>,
+    /// Lines for the reader thread to send to the firmware
+    commands: Sender<String>,
+    shared: Arc<Shared>,
+    /// Sample times are seconds since this
+    start: Instant,
+}
+
+impl SerialSource {
+    /// Start reading the port with times counted from `start`,
+    /// so another board that is already connected shares the time axis
+    pub fn open_from(config: PortConfig, start: Instant, ctx: &egui::Context) -> Self {
+        Self::spawn(ctx, start, move |link| Reader { config, link }.run())
+    }
+
+    /// Start listening for telemetry lines on a UDP port, repainting `ctx` when new data arrives.
+    /// Commands go back to whoever sent the last packet.
+    pub fn listen(config: UdpConfig, ctx: &egui::Context) -> Self {
+        Self::spawn(ctx, Instant::now(), move |link| {
+            UdpReader { config, link }.run()
+        })
+    }
+
+    fn spawn(ctx: &egui::Context, start: Instant, run: impl FnOnce(Link) + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (commands, command_receiver) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+
+        let link = Link {
+            sender,
+            commands: command_receiver,
+            shared: shared.clone(),
+            ctx: ctx.clone(),
+            start,
+        };
+        thread::spawn(move || run(link));
+
+        Self {
+            receiver,
+            commands,
+            shared,
+            start,
+        }
+    }
+
+    /// When the time axis of this connection starts
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Take every event that has arrived since the last call
+    pub fn poll(&self) -> impl Iterator<Item = SerialEvent> + '_ {
+        self.receiver.try_iter()
+    }
+
+    /// How many telemetry lines had a field that couldn't be read, like ones cut off or garbled
+    pub fn malformed_lines(&self) -> usize {
+        self.shared.malformed_lines.load(Ordering::Relaxed)
+    }
+
+    /// Send a line to the firmware. The reader thread writes it between reads,
+    /// so it goes out whole.
+    pub fn send(&self, line: &str) {
+        let _ = self.commands.send(line.to_owned());
+    }
+}
+
+impl Drop for SerialSource {
+    fn drop(&mut self) {
+        // the thread is left to finish on its own, waiting for it could hold up the UI for a
+        // whole read timeout or a stuck port write
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// What a reader thread uses to talk to the app
+struct Link {
+    sender: Sender<SerialEvent>,
+    commands: Receiver<String>,
+    shared: Arc<Shared>,
+    ctx: egui::Context,
+    /// When the connection was first made, times carry on from it through reconnects
+    start: Instant,
+}
+
+impl Link {
+    /// Send an event to the app, false if the app is gone. Samples are drawn with the
+    /// next paced frame, anything else is shown right away.
+    fn send(&self, event: SerialEvent) -> bool {
+        let samples = matches!(event, SerialEvent::Samples { .. });
+        let sent = self.sender.send(event).is_ok();
+        if samples {
+            // repeated requests keep the earliest, so this is a frame at most this long after
+            // the first sample since the last one
+            self.ctx.request_repaint_after(LIVE_REPAINT_INTERVAL);
+        } else {
+            self.ctx.request_repaint();
+        }
+        sent
+    }
+
+    fn stopped(&self) -> bool {
+        self.shared.stop.load(Ordering::Relaxed)
+    }
+
+    /// Seconds since the connection was first made
+    fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+/// Turns telemetry lines into events, keeping track of the time between them
+struct LineDecoder {
+    /// Seconds since the start the first line is at, so times after a reconnect leave a gap
+    offset: f64,
+    start: Instant,
+    sample_rate: Option<f32>,
+    first_timestamp: Option<f64>,
+    count: u64,
+    /// Every channel name read so far, for spotting the end of a name cut off the start of a line
+    names: Vec<String>,
+}
+
+impl LineDecoder {
+    /// Start timing lines from now
+    fn new(link: &Link, sample_rate: Option<f32>) -> Self {
+        Self {
+            offset: link.start.elapsed().as_secs_f64(),
+            start: link.start,
+            sample_rate,
+            first_timestamp: None,
+            count: 0,
+            names: Vec::new(),
+        }
+    }
+
+    /// The samples on `text`, or the text itself if it isn't telemetry.
+    /// `None` for a blank line.
+    fn decode(&mut self, text: &str, shared: &Shared) -> Option<SerialEvent> {
+        let timestamp = find_timestamp(text);
+        let (mut values, mut malformed) = parse_line(text);
+        if drop_cut_off_name(&mut values, &self.names) {
+            malformed = true;
+        }
+        for (name, _) in &values {
+            if !self.names.contains(name) {
+                self.names.push(name.clone());
+            }
+        }
+        if malformed {
+            shared.malformed_lines.fetch_add(1, Ordering::Relaxed);
+        }
+        if values.is_empty() {
+            let text = text.trim();
+            return (!text.is_empty()).then(|| SerialEvent::Text(text.to_owned()));
+        }
+
+        let time = match (timestamp, self.sample_rate) {
+            (Some(timestamp), _) => {
+                self.offset + timestamp - *self.first_timestamp.get_or_insert(timestamp)
+            }
+            (None, Some(rate)) => self.offset + self.count as f64 / rate as f64,
+            (None, None) => self.start.elapsed().as_secs_f64(),
+        };
+        self.count += 1;
+        Some(SerialEvent::Samples {
+            time,
+            values,
+            timestamped: timestamp.is_some(),
+            sequence: find_sequence(text),
+        })
+    }
+}
+
+/// Reads a serial port on its own thread
+struct Reader {
+    config: PortConfig,
+    link: Link,
+}
+
+impl Reader {
+    fn run(self) {
+        let reason = loop {
+            match self.read_port() {
+                Ok(()) => break String::from("Disconnected"),
+                Err(error) if self.config.auto_reconnect => {
+                    self.link.send(SerialEvent::Reconnecting {
+                        time: self.link.elapsed(),
+                        reason: error,
+                    });
+                    if !self.wait_for_port() {
+                        break String::from("Disconnected");
+                    }
+                }
+                Err(error) => break error,
+            }
+        };
+        self.link.send(SerialEvent::Disconnected(reason));
+    }
+
+    /// Wait until the port shows up again, false if told to stop first
+    fn wait_for_port(&self) -> bool {
+        while !self.link.stopped() {
+            thread::sleep(RECONNECT_INTERVAL);
+            let ports = serialport::available_ports().unwrap_or_default();
+            if ports
+                .iter()
+                .any(|port| port.port_name == self.config.port_name)
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Read lines from the port until told to stop or the port fails
+    fn read_port(&self) -> Result<(), String> {
+        let PortConfig {
+            port_name,
+            baud_rate,
+            sample_rate,
+            ..
+        } = &self.config;
+        let port = serialport::new(port_name, *baud_rate)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|error| format!("Unable to open {port_name}: {error}"))?;
+        let mut reader = BufReader::new(port);
+
+        self.link.send(SerialEvent::Connected);
+
+        // after a reconnect the times pick up from now, leaving a gap for the time lost
+        let mut decoder = LineDecoder::new(&self.link, *sample_rate);
+        let mut line = Vec::new();
+        // the port may have been opened part way through a line
+        let mut first_line = true;
+
+        while !self.link.stopped() {
+            for command in self.link.commands.try_iter() {
+                let mut bytes = command.into_bytes();
+                bytes.push(b'\n');
+                reader
+                    .get_mut()
+                    .write_all(&bytes)
+                    .map_err(|error| format!("Unable to write to {port_name}: {error}"))?;
+            }
+
+            match reader.read_until(b'\n', &mut line) {
+                // the port only reports end of file when the device is gone
+                Ok(0) => return Err(format!("{port_name} closed")),
+                Ok(_) if first_line && starts_cut_off(&String::from_utf8_lossy(&line)) => {
+                    first_line = false;
+                    line.clear();
+                }
+                Ok(_) => {
+                    first_line = false;
+                    let text = String::from_utf8_lossy(&line);
+                    if let Some(event) = decoder.decode(&text, &self.link.shared)
+                        && !self.link.send(event)
+                    {
+                        // the app is gone, nobody is listening
+                        return Ok(());
+                    }
+                    line.clear();
+                }
+                // keep the partial line and try again
+                Err(error) if error.kind() == ErrorKind::TimedOut => {}
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(format!("Lost {port_name}: {error}")),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Listens for telemetry on a UDP port on its own thread.
+/// Each packet holds one or more whole lines.
+struct UdpReader {
+    config: UdpConfig,
+    link: Link,
+}
+
+impl UdpReader {
+    fn run(self) {
+        let reason = match self.listen() {
+            Ok(()) => String::from("Disconnected"),
+            Err(error) => error,
+        };
+        self.link.send(SerialEvent::Disconnected(reason));
+    }
+
+    /// Read packets until told to stop or the socket fails
+    fn listen(&self) -> Result<(), String> {
+        let port = self.config.port;
+        let socket = UdpSocket::bind(("0.0.0.0", port))
+            .map_err(|error| format!("Unable to listen on UDP port {port}: {error}"))?;
+        socket
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(|error| format!("Unable to listen on UDP port {port}: {error}"))?;
+
+        self.link.send(SerialEvent::Connected);
+
+        let mut decoder = LineDecoder::new(&self.link, self.config.sample_rate);
+        let mut buffer = vec![0; MAX_PACKET];
+        // where the packets come from, commands are sent back there
+        let mut sender = None;
+        let mut last_packet = Instant::now();
+        // packets stopped coming, like a serial port dropping
+        let mut dropped = false;
+
+        while !self.link.stopped() {
+            for command in self.link.commands.try_iter() {
+                if let Some(address) = sender {
+                    let line = format!("{command}\n");
+                    socket
+                        .send_to(line.as_bytes(), address)
+                        .map_err(|error| format!("Unable to send to {address}: {error}"))?;
+                }
+            }
+
+            match socket.recv_from(&mut buffer) {
+                Ok((length, address)) => {
+                    sender = Some(address);
+                    last_packet = Instant::now();
+                    if dropped {
+                        // pick the times up from now, leaving a gap for the time lost
+                        decoder = LineDecoder::new(&self.link, self.config.sample_rate);
+                        dropped = false;
+                        self.link.send(SerialEvent::Connected);
+                    }
+                    let text = String::from_utf8_lossy(&buffer[..length]);
+                    for line in text.lines() {
+                        if let Some(event) = decoder.decode(line, &self.link.shared)
+                            && !self.link.send(event)
+                        {
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    if sender.is_some() && !dropped && last_packet.elapsed() > NETWORK_TIMEOUT {
+                        dropped = true;
+                        self.link.send(SerialEvent::Reconnecting {
+                            time: self.link.elapsed(),
+                            reason: format!("No packets on UDP port {port}"),
+                        });
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(format!("Lost UDP port {port}: {error}")),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The firmware's timestamp on a line, in seconds.
+/// Read as f64 since a count of microseconds is too big for an f32 to keep exact.
+fn find_timestamp(line: &str) -> Option<f64> {
+    line.trim().split(',').find_map(|field| {
+        let (name, value) = field.split_once(':')?;
+        let &(_, per_second) = TIMESTAMP_FIELDS
+            .iter()
+            .find(|(timestamp, _)| *timestamp == name.trim())?;
+        let value: f64 = value.trim().parse().ok()?;
+        Some(value / per_second)
+    })
+}
+
+/// The sequence number on a line, if it has one
+fn find_sequence(line: &str) -> Option<u64> {
+    line.trim().split(',').find_map(|field| {
+        let (name, value) = field.split_once(':')?;
+        if name.trim() != SEQUENCE_FIELD {
+            return None;
+        }
+        value.trim().parse().ok()
+    })
+}
+
+/// Get the named values on a line like `raw:512, smoothed:498`, and if anything on it couldn't
+/// be read. A line that is just a number is called `value`. Timestamps and sequence numbers
+/// are left out. A line with nothing readable on it, like the firmware's `board:` line,
+/// is text rather than malformed telemetry. Values that aren't finite are malformed.
+fn parse_line(line: &str) -> (Vec<(String, f32)>, bool) {
+    let line = line.trim();
+    if line.is_empty() {
+        return (Vec::new(), false);
+    }
+    if let Ok(value) = line.parse::<f32>() {
+        return if value.is_finite() {
+            (vec![(String::from("value"), value)], false)
+        } else {
+            (Vec::new(), true)
+        };
+    }
+
+    let mut values = Vec::new();
+    let mut malformed = false;
+    for field in line
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+    {
+        let Some((name, value)) = field.split_once(':') else {
+            malformed = true;
+            continue;
+        };
+        let name = name.trim();
+        if name == SEQUENCE_FIELD
+            || TIMESTAMP_FIELDS
+                .iter()
+                .any(|(timestamp, _)| *timestamp == name)
+        {
+            continue;
+        }
+        let is_name =
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        // `parse` takes `nan` and `inf`, which no sensor sends
+        match value.trim().parse::<f32>() {
+            Ok(value) if is_name && value.is_finite() => values.push((name.to_owned(), value)),
+            _ => malformed = true,
+        }
+    }
+    let malformed = malformed && !values.is_empty();
+    (values, malformed)
+}
+
+/// If the first field on `line` has no name, like the `98` left of `smoothed:498` when the
+/// port was opened part way through it. A whole first line, like the `board:` line a board
+/// sends when opening the port resets it, is kept.
+fn starts_cut_off(line: &str) -> bool {
+    let first = line.trim().split(',').next().unwrap_or_default();
+    first
+        .split_once(':')
+        .is_none_or(|(name, _)| name.trim().is_empty())
+}
+
+/// Take off the first value if its name is the end of a known name, so a line that lost
+/// its start, like `ed:498` from `smoothed:498`, doesn't make a new channel.
+/// Returns if it was taken off.
+fn drop_cut_off_name(values: &mut Vec<(String, f32)>, names: &[String]) -> bool {
+    let cut_off = values.first().is_some_and(|(first, _)| {
+        !names.contains(first)
+            && names
+                .iter()
+                .any(|name| name.len() > first.len() && name.ends_with(first.as_str()))
+    });
+    if cut_off {
+        values.remove(0);
+    }
+    cut_off
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What debouncer_rust sends after a reset with the default sweep loop.
+    /// `uwriteln!` ends lines with a bare `\n`.
+    const SWEEP_SESSION: &str = "board:prototype 1 (Nano, servo on D3)\n\
+        u8_value:0\n\
+        u8_value:1\n\
+        u8_value:2\n";
+    /// The same with the `emg-sim` feature, following the simulated EMG
+    const EMG_SIM_SESSION: &str = "board:prototype 1 (Nano, servo on D3)\n\
+        raw:517, smoothed:509, slope_x100:38, motor:44, motor_range:90\n\
+        raw:488, smoothed:505, slope_x100:-61, motor:44, motor_range:90\n\
+        raw:530, smoothed:509, slope_x100:52, motor:44, motor_range:90\n";
+    /// debounce.ino's `Serial.print` lines, which `println` ends with CRLF
+    const ARDUINO_TELEMETRY: &str = "raw:205, smoothed:201, motor:17\r\n";
+    const TELEMETRY: &str = "raw:517, smoothed:509, slope_x100:38, motor:44, motor_range:90\n";
+    const BOOT: &str = "board:prototype 1 (Nano, servo on D3)\n";
+    const PANIC: &str = "Panic at src/main.rs:120:5\n";
+
+    fn decoder() -> LineDecoder {
+        LineDecoder {
+            offset: 0.0,
+            start: Instant::now(),
+            sample_rate: Some(100.0),
+            first_timestamp: None,
+            count: 0,
+            names: Vec::new(),
+        }
+    }
+
+    /// The values on a decoded line, or its text if it wasn't telemetry
+    type Decoded = (Vec<(String, f32)>, Option<String>);
+
+    /// Every line of `session` through one decoder
+    fn decode_all(session: &str, shared: &Shared) -> Vec<Decoded> {
+        let mut decoder = decoder();
+        session
+            .split_inclusive('\n')
+            .filter_map(|line| match decoder.decode(line, shared)? {
+                SerialEvent::Samples { values, .. } => Some((values, None)),
+                SerialEvent::Text(text) => Some((Vec::new(), Some(text))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn named(values: &[(&str, f32)]) -> Vec<(String, f32)> {
+        values
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    #[test]
+    fn firmware_telemetry() {
+        assert_eq!(
+            parse_line(TELEMETRY),
+            (
+                named(&[
+                    ("raw", 517.0),
+                    ("smoothed", 509.0),
+                    ("slope_x100", 38.0),
+                    ("motor", 44.0),
+                    ("motor_range", 90.0),
+                ]),
+                false
+            )
+        );
+        assert_eq!(
+            parse_line(ARDUINO_TELEMETRY),
+            (
+                named(&[("raw", 205.0), ("smoothed", 201.0), ("motor", 17.0)]),
+                false
+            )
+        );
+        for line in [TELEMETRY, ARDUINO_TELEMETRY] {
+            assert_eq!(find_timestamp(line), None);
+            assert_eq!(find_sequence(line), None);
+        }
+    }
+
+    #[test]
+    fn text_lines_are_not_malformed() {
+        for line in [BOOT, PANIC] {
+            assert_eq!(parse_line(line), (Vec::new(), false), "{line}");
+            assert_eq!(find_timestamp(line), None);
+        }
+    }
+
+    #[test]
+    fn sweep_session() {
+        let shared = Shared::default();
+        let lines = decode_all(SWEEP_SESSION, &shared);
+
+        assert_eq!(lines[0], (Vec::new(), Some(BOOT.trim().to_owned())));
+        for (i, line) in lines[1..].iter().enumerate() {
+            assert_eq!(*line, (named(&[("u8_value", i as f32)]), None));
+        }
+        assert_eq!(shared.malformed_lines.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn emg_sim_session() {
+        let shared = Shared::default();
+        let lines = decode_all(EMG_SIM_SESSION, &shared);
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].1.is_some());
+        for (values, text) in &lines[1..] {
+            let names: Vec<&str> = values.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(
+                names,
+                ["raw", "smoothed", "slope_x100", "motor", "motor_range"]
+            );
+            assert_eq!(*text, None);
+        }
+        assert_eq!(lines[2].0[2].1, -61.0);
+        assert_eq!(shared.malformed_lines.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn line_that_lost_its_start() {
+        // bytes dropped between two lines leave the end of `smoothed:509` starting the next
+        let session = format!("{EMG_SIM_SESSION}ed:509, slope_x100:38, motor:44, motor_range:90\n");
+        let shared = Shared::default();
+        let lines = decode_all(&session, &shared);
+
+        assert_eq!(
+            lines[4].0,
+            named(&[("slope_x100", 38.0), ("motor", 44.0), ("motor_range", 90.0)])
+        );
+        assert_eq!(shared.malformed_lines.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn first_line_after_opening() {
+        for line in [BOOT, TELEMETRY, PANIC] {
+            assert!(!starts_cut_off(line), "{line}");
+        }
+        for line in [
+            "98, motor:43, motor_range:90\n",
+            ":498, motor:43\n",
+            "\r\n",
+            "12\n",
+        ] {
+            assert!(starts_cut_off(line), "{line}");
+        }
+    }
+
+    #[test]
+    fn new_channel_that_is_not_a_cut_off_name() {
+        let mut values = named(&[("angle", 12.0), ("raw", 517.0)]);
+        let names = [String::from("raw"), String::from("smoothed")];
+
+        assert!(!drop_cut_off_name(&mut values, &names));
+        assert_eq!(values.len(), 2);
+        // a known name is kept even if it is also the end of another one
+        let mut values = named(&[("raw", 517.0)]);
+        assert!(!drop_cut_off_name(
+            &mut values,
+            &[String::from("emg_raw"), String::from("raw")]
+        ));
+        assert_eq!(values.len(), 1);
+    }
+
+    #[test]
+    fn blank_lines() {
+        assert_eq!(parse_line(""), (Vec::new(), false));
+        assert_eq!(parse_line("\r\n"), (Vec::new(), false));
+        assert_eq!(parse_line("  , ,\r\n"), (Vec::new(), false));
+    }
+
+    #[test]
+    fn lone_number() {
+        assert_eq!(parse_line("1023\r\n"), (named(&[("value", 1023.0)]), false));
+        assert_eq!(parse_line("-0.5"), (named(&[("value", -0.5)]), false));
+    }
+
+    #[test]
+    fn timestamps_and_sequence_numbers() {
+        let line = "t_us:4294967295, seq:41, raw:512, smoothed:498\r\n";
+        assert_eq!(
+            parse_line(line),
+            (named(&[("raw", 512.0), ("smoothed", 498.0)]), false)
+        );
+        // the whole count of microseconds is kept, which an f32 couldn't
+        assert_eq!(find_timestamp(line), Some(4294.967295));
+        assert_eq!(find_sequence(line), Some(41));
+
+        let line = "raw:512, t_ms:1500";
+        assert_eq!(parse_line(line), (named(&[("raw", 512.0)]), false));
+        assert_eq!(find_timestamp(line), Some(1.5));
+    }
+
+    #[test]
+    fn bad_fields_are_skipped() {
+        // the end of a line cut off when the port was opened part way through it
+        assert_eq!(
+            parse_line("98, motor:43, motor_range:90"),
+            (named(&[("motor", 43.0), ("motor_range", 90.0)]), true)
+        );
+        // a value garbled by the wrong baud rate
+        assert_eq!(
+            parse_line("raw:5\u{fffd}2, smoothed:498"),
+            (named(&[("smoothed", 498.0)]), true)
+        );
+        // so is a name
+        assert_eq!(
+            parse_line("r\u{fffd}w:512, smoothed:498"),
+            (named(&[("smoothed", 498.0)]), true)
+        );
+        // not a number, or an infinite one
+        for bad in ["nan", "NaN", "inf", "-inf", "infinity"] {
+            assert_eq!(
+                parse_line(&format!("raw:{bad}, smoothed:498")),
+                (named(&[("smoothed", 498.0)]), true),
+                "{bad}"
+            );
+            assert_eq!(parse_line(bad), (Vec::new(), true), "{bad}");
+        }
+        // nothing readable at all is text
+        assert_eq!(parse_line("raw:, :498"), (Vec::new(), false));
+        assert_eq!(find_timestamp("t_us:soon, raw:1"), None);
+        assert_eq!(find_sequence("seq:-1, raw:1"), None);
+    }
+}
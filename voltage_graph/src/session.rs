@@ -0,0 +1,563 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::devices::DeviceInfo;
+use crate::noise::NoiseReport;
+use crate::viewport::TimeViewport;
+
+/// Lines starting with this hold the session metadata, so CSV readers can skip them as comments
+const METADATA_PREFIX: &str = "# ";
+/// Lines starting with this hold a marker on the timeline
+const ANNOTATION_PREFIX: &str = "# marker ";
+/// How much is written at once. A failed write is cut back off the file,
+/// so a full disk leaves it ending on a whole row.
+const WRITE_CHUNK: usize = 64 * 1024;
+/// How long rows are held to be put in order of time. With more than one board
+/// their rows arrive a little out of order, and the loader wants time to only go forward.
+const REORDER_SECONDS: f64 = 1.0;
+
+/// Everything about a recording that isn't the samples
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SessionMetadata {
+    /// When the recording started, as RFC 3339
+    pub started: String,
+    /// The version string the firmware reported, empty if it didn't report one
+    pub firmware_version: String,
+    /// Nominal samples per second, if known
+    pub sample_rate: Option<f32>,
+    pub channels: Vec<String>,
+    /// The filter settings the device was using
+    pub filter_config: String,
+    /// Where the electrodes were placed
+    pub electrode_placement: String,
+    pub notes: String,
+    /// The noise check done before recording
+    pub noise_check: Option<NoiseReport>,
+    /// Shared by every part of a recording split over several files, empty for one file
+    pub session_id: String,
+    /// Which part of the session this file is, counting from 1
+    pub part: Option<usize>,
+    /// Every board recorded when there was more than one, the first is the main connection
+    pub devices: Vec<DeviceInfo>,
+}
+
+impl SessionMetadata {
+    /// Read the metadata from a `# {...}` line, `None` if it isn't one
+    pub fn from_line(line: &str) -> Option<Self> {
+        let json = line.trim().strip_prefix('#')?;
+        serde_json::from_str(json.trim()).ok()
+    }
+
+    /// If this file carries on from an earlier part, so loading it on its own is a repeat
+    pub fn is_later_part(&self) -> bool {
+        self.part.is_some_and(|part| part > 1)
+    }
+
+    /// A grid of the metadata, the text in it can only be changed while `editable`
+    pub fn controls(&mut self, ui: &mut egui::Ui, editable: bool) {
+        egui::Grid::new("session metadata")
+            .num_columns(2)
+            .show(ui, |ui| {
+                if !self.started.is_empty() {
+                    ui.label("Started");
+                    ui.label(self.started.as_str());
+                    ui.end_row();
+                }
+                if let Some(sample_rate) = self.sample_rate {
+                    ui.label("Sample rate");
+                    ui.label(format!("{sample_rate} Hz"));
+                    ui.end_row();
+                }
+                for (label, text) in [
+                    ("Firmware", &mut self.firmware_version),
+                    ("Filter", &mut self.filter_config),
+                    ("Electrodes", &mut self.electrode_placement),
+                ] {
+                    ui.label(label);
+                    ui.add_enabled(editable, egui::TextEdit::singleline(text));
+                    ui.end_row();
+                }
+                ui.label("Notes");
+                ui.add_enabled(
+                    editable,
+                    egui::TextEdit::multiline(&mut self.notes).desired_rows(2),
+                );
+                ui.end_row();
+                if let Some(report) = &self.noise_check {
+                    ui.label("Noise check");
+                    ui.label(format!("{:.1} RMS, {}", report.rms, report.verdict));
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+/// When a long recording moves on to a new file
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rotation {
+    pub enabled: bool,
+    /// Start a new part after this many minutes of samples
+    pub minutes: f32,
+    /// Start a new part once a file is this many megabytes
+    pub megabytes: f32,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            minutes: 60.0,
+            megabytes: 100.0,
+        }
+    }
+}
+
+impl Rotation {
+    /// Whether to split and the limits that start a new part
+    pub fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Split into parts")
+            .on_hover_text(
+                "Start a new file when either limit is reached. \
+                 Opening any part loads the whole session.",
+            );
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.minutes)
+                        .range(1.0..=1440.0)
+                        .suffix(" min"),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.megabytes)
+                        .range(1.0..=10_000.0)
+                        .suffix(" MB"),
+                );
+            });
+        });
+    }
+}
+
+/// A note pinned to a moment of a session, shown as a line on the plot
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Annotation {
+    /// Seconds on the same time axis as the samples
+    pub time: f64,
+    pub note: String,
+}
+
+impl Annotation {
+    /// Read a marker from a `# marker {...}` line, `None` if it isn't one
+    pub fn from_line(line: &str) -> Option<Self> {
+        let json = line.trim().strip_prefix(ANNOTATION_PREFIX.trim_end())?;
+        serde_json::from_str(json.trim()).ok()
+    }
+}
+
+/// Put a marker at `time`, keeping them in order
+pub fn add_annotation(annotations: &mut Vec<Annotation>, time: f64) {
+    let index = annotations.partition_point(|annotation| annotation.time <= time);
+    annotations.insert(
+        index,
+        Annotation {
+            time,
+            note: String::new(),
+        },
+    );
+}
+
+/// The markers with their notes, and a button to add one at `newest`.
+/// Clicking a marker's time centres `viewport` on it.
+pub fn annotation_controls(
+    ui: &mut egui::Ui,
+    annotations: &mut Vec<Annotation>,
+    newest: Option<f64>,
+    viewport: &mut TimeViewport,
+) {
+    ui.heading("Timeline markers");
+
+    if ui
+        .add_enabled(newest.is_some(), egui::Button::new("Add marker"))
+        .on_hover_text("Mark the newest sample, or where the playback is")
+        .clicked()
+        && let Some(time) = newest
+    {
+        add_annotation(annotations, time);
+    }
+
+    let mut removed = None;
+    for (i, annotation) in annotations.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui
+                .small_button(format!("{:.2} s", annotation.time))
+                .on_hover_text("Show on the plot")
+                .clicked()
+            {
+                viewport.center_on(annotation.time);
+            }
+            ui.add(
+                egui::TextEdit::singleline(&mut annotation.note)
+                    .hint_text("Note")
+                    .desired_width(120.0),
+            );
+            if ui.small_button("Remove").clicked() {
+                removed = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = removed {
+        annotations.remove(i);
+    }
+}
+
+/// Writes samples to a session file as they arrive, moving on to a new part
+/// when the rotation limits are reached
+pub struct Recorder {
+    file: File,
+    /// Rows waiting to be put in order, as (time, values), sorted by time
+    pending: Vec<(f64, Vec<(String, f32)>)>,
+    /// Rows not written to the file yet
+    buffer: String,
+    /// The length of the file up to its last whole row
+    written: u64,
+    /// The path picked for the recording, parts are numbered next to it
+    base_path: PathBuf,
+    path: PathBuf,
+    /// Written at the top of each part along with its first samples
+    metadata: SessionMetadata,
+    rotation: Rotation,
+    header_written: bool,
+    /// Samples in the finished parts and in this one
+    samples: usize,
+    /// Time of the first sample in this part, markers before it aren't part of the file
+    first_time: Option<f64>,
+}
+
+impl Recorder {
+    /// Start a session file at `path`. The metadata is written with the first samples,
+    /// so any channels it doesn't list can be filled in from them.
+    /// With rotation on, the parts are `<name>.part1.csv`, `<name>.part2.csv`...
+    pub fn create(
+        path: PathBuf,
+        metadata: &SessionMetadata,
+        rotation: Rotation,
+    ) -> io::Result<Self> {
+        let mut metadata = metadata.clone();
+        let first_path = if rotation.enabled {
+            metadata.session_id = chrono::Local::now().format("%Y%m%d-%H%M%S%3f").to_string();
+            metadata.part = Some(1);
+            part_path(&path, 1)
+        } else {
+            metadata.session_id.clear();
+            metadata.part = None;
+            path.clone()
+        };
+
+        Ok(Self {
+            file: File::create(&first_path)?,
+            pending: Vec::new(),
+            buffer: String::new(),
+            written: 0,
+            base_path: path,
+            path: first_path,
+            metadata,
+            rotation,
+            header_written: false,
+            samples: 0,
+            first_time: None,
+        })
+    }
+
+    /// Add one row, with a blank for any channel missing from `values`. Rows are written
+    /// once they are `REORDER_SECONDS` old, and rows from different boards at the same
+    /// time are joined into one.
+    pub fn record(
+        &mut self,
+        time: f64,
+        values: &[(String, f32)],
+        annotations: &[Annotation],
+    ) -> io::Result<()> {
+        match self
+            .pending
+            .binary_search_by(|(pending, _)| pending.total_cmp(&time))
+        {
+            Ok(i) => self.pending[i].1.extend_from_slice(values),
+            Err(i) => self.pending.insert(i, (time, values.to_vec())),
+        }
+
+        let newest = self.pending.last().map_or(time, |&(newest, _)| newest);
+        let ready = self
+            .pending
+            .partition_point(|&(pending, _)| pending < newest - REORDER_SECONDS);
+        let rows: Vec<_> = self.pending.drain(..ready).collect();
+        for (time, values) in rows {
+            self.write_row(time, &values, annotations)?;
+        }
+        Ok(())
+    }
+
+    /// Write every row still waiting to be put in order
+    fn write_pending(&mut self, annotations: &[Annotation]) -> io::Result<()> {
+        for (time, values) in std::mem::take(&mut self.pending) {
+            self.write_row(time, &values, annotations)?;
+        }
+        Ok(())
+    }
+
+    /// Write one row. When the part is full it is finished first, so the row starts the next one.
+    fn write_row(
+        &mut self,
+        time: f64,
+        values: &[(String, f32)],
+        annotations: &[Annotation],
+    ) -> io::Result<()> {
+        if self.part_full(time) {
+            self.next_part(annotations)?;
+        }
+        if !self.header_written {
+            self.write_header(values)?;
+        }
+
+        let _ = write!(self.buffer, "{time}");
+        for channel in &self.metadata.channels {
+            match values.iter().find(|(name, _)| name == channel) {
+                Some((_, value)) => {
+                    let _ = write!(self.buffer, ",{value}");
+                }
+                None => self.buffer.push(','),
+            }
+        }
+        self.buffer.push('\n');
+        self.samples += 1;
+        self.first_time.get_or_insert(time);
+
+        if self.buffer.len() >= WRITE_CHUNK {
+            self.write_buffer()?;
+        }
+        Ok(())
+    }
+
+    /// If this part has reached either rotation limit
+    fn part_full(&self, time: f64) -> bool {
+        let Some(first_time) = self.first_time else {
+            return false;
+        };
+        let bytes = self.written + self.buffer.len() as u64;
+        self.rotation.enabled
+            && (time - first_time >= self.rotation.minutes as f64 * 60.0
+                || bytes as f32 >= self.rotation.megabytes * 1_000_000.0)
+    }
+
+    /// Finish this part and open the next, with the same channels so the parts line up
+    fn next_part(&mut self, annotations: &[Annotation]) -> io::Result<()> {
+        self.finish_part(annotations)?;
+
+        let part = self.metadata.part.unwrap_or(1) + 1;
+        let path = part_path(&self.base_path, part);
+        self.file = File::create(&path)?;
+        self.path = path;
+        self.metadata.part = Some(part);
+        self.written = 0;
+        self.header_written = false;
+        self.first_time = None;
+        Ok(())
+    }
+
+    fn write_header(&mut self, values: &[(String, f32)]) -> io::Result<()> {
+        if self.metadata.channels.is_empty() {
+            self.metadata.channels = values.iter().map(|(name, _)| name.clone()).collect();
+        }
+
+        let _ = writeln!(
+            self.buffer,
+            "{METADATA_PREFIX}{}",
+            serde_json::to_string(&self.metadata).map_err(io::Error::other)?
+        );
+
+        let mut headers = vec![String::from("time_s")];
+        headers.extend(self.metadata.channels.iter().cloned());
+        let _ = writeln!(self.buffer, "{}", headers.join(","));
+
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// Write out the buffered rows. If that fails, for example on a full disk,
+    /// whatever part of them made it is cut back off so the file ends on a whole row.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if let Err(error) = self.file.write_all(self.buffer.as_bytes()) {
+            let _ = self.file.set_len(self.written);
+            self.buffer.clear();
+            return Err(error);
+        }
+        self.written += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// The file being written to now
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Which part is being written, `None` without rotation
+    pub fn part(&self) -> Option<usize> {
+        self.metadata.part
+    }
+
+    /// Add the markers from this part of the session and write out what is left
+    fn finish_part(&mut self, annotations: &[Annotation]) -> io::Result<()> {
+        if let Some(first_time) = self.first_time {
+            for annotation in annotations
+                .iter()
+                .filter(|marker| marker.time >= first_time)
+            {
+                let _ = writeln!(
+                    self.buffer,
+                    "{ANNOTATION_PREFIX}{}",
+                    serde_json::to_string(annotation).map_err(io::Error::other)?
+                );
+            }
+        }
+        self.write_buffer()?;
+        self.file.flush()
+    }
+
+    /// Write the markers from the recorded part of the session and finish the file,
+    /// returning how many samples are in the whole session.
+    /// The markers go at the end so notes typed during the recording are kept.
+    pub fn finish(mut self, annotations: &[Annotation]) -> io::Result<usize> {
+        self.write_pending(annotations)?;
+        self.finish_part(annotations)?;
+        Ok(self.samples)
+    }
+}
+
+impl Drop for Recorder {
+    /// Like a `BufWriter`, rows still buffered are written out when it is dropped
+    fn drop(&mut self) {
+        let _ = self.write_pending(&[]);
+        let _ = self.write_buffer();
+    }
+}
+
+/// Where part `part` of a rotating recording picked to go at `path` is written
+fn part_path(path: &Path, part: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.part{part}.csv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[(&str, f32)]) -> Vec<(String, f32)> {
+        values
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    /// A folder of its own for each test, so the parts of one can't be mixed up with another
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("voltage_graph_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn metadata_and_markers_read_back() {
+        let metadata = SessionMetadata {
+            firmware_version: String::from("0.1.0"),
+            part: Some(2),
+            ..Default::default()
+        };
+        let line = format!(
+            "{METADATA_PREFIX}{}",
+            serde_json::to_string(&metadata).unwrap()
+        );
+        let read = SessionMetadata::from_line(&line).unwrap();
+        assert_eq!(read.firmware_version, "0.1.0");
+        assert!(read.is_later_part());
+
+        let marker = Annotation::from_line("# marker {\"time\":2.5,\"note\":\"grip\"}").unwrap();
+        assert_eq!((marker.time, marker.note.as_str()), (2.5, "grip"));
+
+        assert!(SessionMetadata::from_line("time_s,raw").is_none());
+        assert!(Annotation::from_line("# just a comment").is_none());
+    }
+
+    #[test]
+    fn rows_are_put_in_order_and_joined() {
+        let folder = temp_folder("recorder");
+        let path = folder.join("session.csv");
+
+        let mut recorder = Recorder::create(
+            path.clone(),
+            &SessionMetadata::default(),
+            Rotation::default(),
+        )
+        .unwrap();
+        recorder.record(0.0, &values(&[("a", 1.0)]), &[]).unwrap();
+        // a second board's row for the same time, and one arriving late
+        recorder.record(0.2, &values(&[("a", 3.0)]), &[]).unwrap();
+        recorder.record(0.0, &values(&[("b", 2.0)]), &[]).unwrap();
+        recorder.record(0.1, &values(&[("a", 2.0)]), &[]).unwrap();
+        let marker = Annotation {
+            time: 0.1,
+            note: String::from("grip"),
+        };
+        assert_eq!(recorder.finish(std::slice::from_ref(&marker)).unwrap(), 3);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        let metadata = SessionMetadata::from_line(lines[0]).unwrap();
+        assert_eq!(metadata.channels, ["a", "b"]);
+        assert_eq!(lines[1..5], ["time_s,a,b", "0,1,2", "0.1,2,", "0.2,3,"]);
+        assert_eq!(Annotation::from_line(lines[5]).unwrap().note, "grip");
+    }
+
+    #[test]
+    fn rotation_starts_new_parts() {
+        let folder = temp_folder("rotation");
+        let rotation = Rotation {
+            enabled: true,
+            minutes: 1.0,
+            megabytes: 100.0,
+        };
+
+        let mut recorder = Recorder::create(
+            folder.join("long.csv"),
+            &SessionMetadata::default(),
+            rotation,
+        )
+        .unwrap();
+        for second in 0..150 {
+            recorder
+                .record(second as f64, &values(&[("raw", second as f32)]), &[])
+                .unwrap();
+        }
+        assert_eq!(recorder.part(), Some(3));
+        assert_eq!(recorder.finish(&[]).unwrap(), 150);
+
+        let first = std::fs::read_to_string(folder.join("long.part1.csv")).unwrap();
+        let third = std::fs::read_to_string(folder.join("long.part3.csv")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+        let first = SessionMetadata::from_line(first.lines().next().unwrap()).unwrap();
+        let third = SessionMetadata::from_line(third.lines().next().unwrap()).unwrap();
+        assert_eq!(first.part, Some(1));
+        assert_eq!(third.part, Some(3));
+        assert!(!first.session_id.is_empty());
+        assert_eq!(first.session_id, third.session_id);
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+
+use crate::artifacts::ArtifactLimits;
+use crate::clipping::ClipLimits;
+use crate::derived::FilterPreset;
+use crate::layout::Layout;
+use crate::session::Rotation;
+use crate::theme::Theme;
+use crate::units::{Units, ValueAxis};
+
+/// Everything that is remembered between launches of the app
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The last port that was connected to
+    pub port_name: String,
+    pub baud_rate: u32,
+    /// Space serial samples by `sample_rate` instead of when they arrive
+    pub fixed_sample_rate: bool,
+    /// The rate the firmware is set to sample at, in Hz
+    pub sample_rate: f32,
+    /// Wait for a dropped port to come back and carry on
+    pub auto_reconnect: bool,
+    /// Names the main connection's channels when other boards are read alongside it
+    pub device_name: String,
+    /// The UDP port network telemetry is listened for on
+    pub udp_port: u16,
+    pub filter_presets: Vec<FilterPreset>,
+    pub theme: Theme,
+    pub units: Units,
+    /// How many seconds of samples are kept
+    pub history_seconds: f32,
+    /// The color picked for each channel name, as RGB
+    pub channel_colors: Vec<(String, [u8; 3])>,
+    /// Where the last file was saved or opened, file dialogs start there
+    pub last_directory: Option<PathBuf>,
+    /// When EMG samples count as clipped
+    pub clip_limits: ClipLimits,
+    /// When a stretch of EMG is marked as an artifact
+    pub artifact_limits: ArtifactLimits,
+    /// When long recordings move on to a new file
+    pub rotation: Rotation,
+    /// RMS of the last reference contraction, in ADC counts, for the noise check
+    pub reference_rms: Option<f32>,
+    /// The views in the main area
+    pub layout: Layout,
+    /// The y-axis scale of the time plot, also used for the statistics.
+    /// It was saved as `time_axis` before.
+    #[serde(alias = "time_axis")]
+    pub value_axis: ValueAxis,
+    /// The y-axis scale of the spectrum
+    pub spectrum_axis: ValueAxis,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            port_name: String::new(),
+            baud_rate: 57600,
+            fixed_sample_rate: false,
+            sample_rate: 1000.0,
+            auto_reconnect: true,
+            device_name: String::from("devA"),
+            udp_port: 5005,
+            filter_presets: Vec::new(),
+            theme: Theme::Dark,
+            units: Units::default(),
+            history_seconds: 60.0,
+            channel_colors: Vec::new(),
+            last_directory: None,
+            clip_limits: ClipLimits::default(),
+            artifact_limits: ArtifactLimits::default(),
+            rotation: Rotation::default(),
+            reference_rms: None,
+            layout: Layout::default(),
+            value_axis: ValueAxis::default(),
+            spectrum_axis: ValueAxis::DECIBELS,
+        }
+    }
+}
+
+impl Settings {
+    /// The sample rate to use for serial data, if one is set
+    pub fn nominal_sample_rate(&self) -> Option<f32> {
+        self.fixed_sample_rate.then_some(self.sample_rate)
+    }
+
+    /// Load the saved settings, or the defaults if there are none
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default()
+    }
+
+    pub fn channel_colors(&self) -> Vec<(String, Color32)> {
+        self.channel_colors
+            .iter()
+            .map(|(name, [r, g, b])| (name.clone(), Color32::from_rgb(*r, *g, *b)))
+            .collect()
+    }
+
+    pub fn set_channel_colors(&mut self, colors: Vec<(String, Color32)>) {
+        self.channel_colors = colors
+            .into_iter()
+            .map(|(name, color)| (name, [color.r(), color.g(), color.b()]))
+            .collect();
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, self);
+    }
+}
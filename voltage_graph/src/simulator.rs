@@ -0,0 +1,186 @@
+use std::f32::consts::TAU;
+use std::time::Instant;
+
+use eframe::egui;
+use hand_core::{EmgSimulator, EmgState, LcgRng, SimProfile};
+
+use crate::MAX_SAMPLE_RATE;
+
+/// The channel the simulator feeds
+pub const SIMULATOR_CHANNEL: &str = "raw";
+/// Frequency of the mains interference that can be added, to test the spectrum with
+const MAINS_FREQUENCY: f32 = 60.0;
+/// The most samples made in one frame, so a long stall doesn't freeze the app catching up
+const MAX_SAMPLES_PER_TICK: usize = 10_000;
+
+/// Profiles offered in the picker
+pub const PROFILES: [(SimProfile, &str); 5] = [
+    (SimProfile::Random, "Random"),
+    (SimProfile::Hold(EmgState::Relaxed), "Relaxed"),
+    (SimProfile::Hold(EmgState::Intermediate), "Intermediate"),
+    (SimProfile::Hold(EmgState::Clenched), "Clenched"),
+    (SimProfile::LeadOff, "Electrode off"),
+];
+
+/// Runs the firmware's `EmgSimulator` in real time, for when there is no hand plugged in
+pub struct SimulatorSource {
+    simulator: EmgSimulator,
+    rng: LcgRng,
+    pub seed: u32,
+    pub sample_rate: f32,
+    /// Amplitude of the mains interference added on top, in ADC counts
+    pub hum: f32,
+    running: bool,
+    /// Time of the next sample, in seconds since the simulator started.
+    /// Kept as f64 so adding up small steps doesn't drift.
+    time: f64,
+    /// When samples were last made, while running
+    last_tick: Instant,
+    /// Samples owed from the last tick that didn't make a whole one
+    owed: f32,
+}
+
+impl SimulatorSource {
+    pub fn new(seed: u32, sample_rate: f32) -> Self {
+        Self {
+            simulator: EmgSimulator::new(),
+            rng: LcgRng::new(seed),
+            seed,
+            sample_rate,
+            hum: 0.0,
+            running: false,
+            time: 0.0,
+            last_tick: Instant::now(),
+            owed: 0.0,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn profile(&self) -> SimProfile {
+        self.simulator.profile
+    }
+
+    pub fn profile_name(&self) -> &'static str {
+        PROFILES
+            .iter()
+            .find(|(profile, _)| *profile == self.simulator.profile)
+            .map_or("", |(_, name)| name)
+    }
+
+    /// Switch profile, the time carries on from where it is
+    pub fn set_profile(&mut self, profile: SimProfile) {
+        self.simulator.profile = profile;
+    }
+
+    pub fn start(&mut self) {
+        self.running = true;
+        // the time picks up where it stopped rather than jumping over the pause
+        self.last_tick = Instant::now();
+        self.owed = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Start again from time 0 with the current seed
+    pub fn restart(&mut self) {
+        let profile = self.simulator.profile;
+        *self = Self {
+            hum: self.hum,
+            running: self.running,
+            ..Self::new(self.seed, self.sample_rate)
+        };
+        self.simulator.profile = profile;
+    }
+
+    /// Make the samples due since the last tick, as (time, value)
+    pub fn tick(&mut self) -> Vec<(f64, f32)> {
+        if !self.running {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        self.owed += now.duration_since(self.last_tick).as_secs_f32() * self.sample_rate;
+        self.last_tick = now;
+        let count = (self.owed as usize).min(MAX_SAMPLES_PER_TICK);
+        self.owed -= self.owed.floor();
+
+        (0..count)
+            .map(|_| {
+                let noise = self.rng.rand_bounded_u32(1023) as u16;
+                let time = self.time;
+                let phase = (MAINS_FREQUENCY as f64 * time).fract() as f32;
+                let hum = self.hum * (TAU * phase).sin();
+                let sample = (time, self.simulator.next(noise) as f32 + hum);
+                self.time += 1.0 / self.sample_rate as f64;
+                sample
+            })
+            .collect()
+    }
+    /// The profile, rate, hum and seed, with buttons to run it. It can't start while
+    /// `file_open`, and `sample_rate` is kept at its rate while it runs.
+    /// Returns whether it was restarted, so the old samples can be cleared.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        file_open: bool,
+        sample_rate: &mut Option<f32>,
+    ) -> bool {
+        let mut restarted = false;
+        let profile = self.profile();
+        egui::ComboBox::from_label("Profile")
+            .selected_text(self.profile_name())
+            .show_ui(ui, |ui| {
+                for (option, name) in PROFILES {
+                    if ui.selectable_label(option == profile, name).clicked() {
+                        self.set_profile(option);
+                    }
+                }
+            });
+
+        if ui
+            .add(
+                egui::Slider::new(&mut self.sample_rate, 10.0..=MAX_SAMPLE_RATE)
+                    .logarithmic(true)
+                    .text("Sample rate (Hz)"),
+            )
+            .changed()
+            && self.is_running()
+        {
+            *sample_rate = Some(self.sample_rate);
+        }
+        ui.add(egui::Slider::new(&mut self.hum, 0.0..=100.0).text("Mains hum"));
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.add(egui::DragValue::new(&mut self.seed));
+        });
+
+        ui.horizontal(|ui| {
+            if self.is_running() {
+                if ui.button("Pause").clicked() {
+                    self.stop();
+                }
+            } else if ui
+                .add_enabled(!file_open, egui::Button::new("Start"))
+                .clicked()
+            {
+                self.start();
+                *sample_rate = Some(self.sample_rate);
+            }
+
+            if ui
+                .button("Restart")
+                .on_hover_text("Start again from 0 s with the seed")
+                .clicked()
+            {
+                self.restart();
+                restarted = true;
+            }
+        });
+        restarted
+    }
+}
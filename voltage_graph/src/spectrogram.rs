@@ -0,0 +1,481 @@
+use std::ops::Range;
+
+use eframe::egui::{self, Color32, ColorImage, Rect, TextureHandle, TextureOptions, pos2};
+
+use crate::channel::{Channel, Channels};
+use crate::ring_buffer::RingBuffer;
+use crate::spectrum::{self, WINDOW_LENGTHS, WindowFunction};
+use crate::theme::ChartStyle;
+
+/// How many columns of history the heatmap holds
+const MAX_COLUMNS: usize = 600;
+/// Width of the color map legend in points
+const LEGEND_WIDTH: f32 = 60.0;
+/// Hop sizes offered in the picker, as a fraction of the window length
+const HOP_FRACTIONS: [(usize, &str); 3] = [(1, "Full"), (2, "1/2"), (4, "1/4")];
+
+/// Stops of the color map, from quiet to loud
+const COLOR_MAP: [Color32; 5] = [
+    Color32::from_rgb(0, 0, 4),
+    Color32::from_rgb(87, 16, 110),
+    Color32::from_rgb(188, 55, 84),
+    Color32::from_rgb(249, 142, 9),
+    Color32::from_rgb(252, 255, 164),
+];
+
+/// The color of `fraction` of the way from the quietest to the loudest
+fn color_map(fraction: f32) -> Color32 {
+    let position = fraction.clamp(0.0, 1.0) * (COLOR_MAP.len() - 1) as f32;
+    let index = (position as usize).min(COLOR_MAP.len() - 2);
+    let t = position - index as f32;
+    let (low, high) = (COLOR_MAP[index], COLOR_MAP[index + 1]);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+    Color32::from_rgb(
+        mix(low.r(), high.r()),
+        mix(low.g(), high.g()),
+        mix(low.b(), high.b()),
+    )
+}
+
+/// A heatmap of how the spectrum of one channel changes over time
+pub struct Spectrogram {
+    /// Name of the channel to analyse
+    pub channel: String,
+    pub window: WindowFunction,
+    /// How many samples go into each column
+    pub length: usize,
+    /// The window moves on by `length / hop_fraction` samples between columns
+    pub hop_fraction: usize,
+    /// Magnitudes at or below this are the quietest color
+    pub min_db: f32,
+    /// Magnitudes at or above this are the loudest color
+    pub max_db: f32,
+    /// Samples per second the columns were computed with
+    pub sample_rate: f32,
+    /// Magnitudes in dB of each column, lowest frequency first
+    columns: RingBuffer<Vec<f32>>,
+    /// Time at the middle of each column's window
+    column_times: RingBuffer<f64>,
+    /// How many columns have been made, to know where the next one goes in the texture
+    columns_made: usize,
+    /// Samples that haven't made it into a full window yet
+    pending: Vec<(f64, f32)>,
+    /// Time of the newest sample taken from the channel
+    last_time: Option<f64>,
+    texture: Option<TextureHandle>,
+}
+
+impl Default for Spectrogram {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            window: WindowFunction::Hann,
+            length: 256,
+            hop_fraction: 2,
+            min_db: -80.0,
+            max_db: 0.0,
+            sample_rate: 0.0,
+            columns: RingBuffer::new(MAX_COLUMNS),
+            column_times: RingBuffer::new(MAX_COLUMNS),
+            columns_made: 0,
+            pending: Vec::new(),
+            last_time: None,
+            texture: None,
+        }
+    }
+}
+
+impl Spectrogram {
+    /// Start again from nothing, after the channel or the STFT settings changed
+    pub fn reset(&mut self) {
+        self.columns = RingBuffer::new(MAX_COLUMNS);
+        self.column_times = RingBuffer::new(MAX_COLUMNS);
+        self.columns_made = 0;
+        self.pending.clear();
+        self.last_time = None;
+        self.texture = None;
+    }
+
+    /// Run the STFT over the samples of `channel` that arrived since the last update,
+    /// adding a column to the heatmap for every hop.
+    /// Without a `sample_rate` it is measured from the sample times.
+    pub fn update(&mut self, ctx: &egui::Context, channel: &Channel, sample_rate: Option<f32>) {
+        // the channel was cleared or a file was loaded, so the old columns don't follow on
+        let newest = channel.samples.back().map(|&(time, _)| time);
+        if newest < self.last_time {
+            self.reset();
+        }
+
+        let after = self.last_time.unwrap_or(f64::NEG_INFINITY);
+        let first_new = channel.samples.partition_point(|&(time, _)| time <= after);
+        let last = channel.samples.range(..).len();
+        if first_new == last {
+            return;
+        }
+
+        // only what still fits in the history is worth computing
+        let hop = (self.length / self.hop_fraction).max(1);
+        let keep = MAX_COLUMNS * hop + self.length;
+        let first = first_new.max(last.saturating_sub(keep));
+        let new: Vec<(f64, f32)> = channel.samples.range(first..last).copied().collect();
+
+        self.sample_rate = sample_rate.unwrap_or_else(|| {
+            let seconds = new[new.len() - 1].0 - new[0].0;
+            if new.len() > 1 && seconds > 0.0 {
+                ((new.len() - 1) as f64 / seconds) as f32
+            } else {
+                self.sample_rate
+            }
+        });
+        self.last_time = newest;
+        self.pending.extend(new);
+
+        let mut added = 0;
+        while self.pending.len() >= self.length {
+            let window: Vec<f32> = self.pending[..self.length]
+                .iter()
+                .map(|&(_, value)| value)
+                .collect();
+            let bins = spectrum::spectrum(&window, self.sample_rate, self.window);
+            let column: Vec<f32> = bins.into_iter().map(|(_, magnitude)| magnitude).collect();
+            added += 1;
+            self.columns.push(column);
+            self.column_times.push(self.pending[self.length / 2].0);
+            self.columns_made += 1;
+            self.pending.drain(..hop);
+        }
+        if self.pending.len() > keep {
+            self.pending.drain(..self.pending.len() - keep);
+        }
+
+        self.draw_columns(ctx, added);
+    }
+
+    /// Add the samples of the chosen channel that arrived since the last update,
+    /// starting over on the first channel when the chosen one isn't there
+    pub fn follow(&mut self, ctx: &egui::Context, channels: &Channels, sample_rate: Option<f32>) {
+        if channels.fall_back(&mut self.channel) {
+            self.reset();
+        }
+        if let Some(channel) = channels.get(&self.channel) {
+            self.update(ctx, channel, sample_rate);
+        }
+    }
+
+    /// The channel and STFT settings, which start the heatmap over, and its dB range
+    pub fn controls(&mut self, ui: &mut egui::Ui, channels: &Channels) {
+        ui.heading("Spectrogram");
+
+        let mut changed = channels.picker(
+            ui,
+            egui::ComboBox::from_id_salt("spectrogram channel"),
+            &mut self.channel,
+        );
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("spectrogram window")
+                .selected_text(self.window.name())
+                .show_ui(ui, |ui| {
+                    for window in WindowFunction::ALL {
+                        changed |= ui
+                            .selectable_value(&mut self.window, window, window.name())
+                            .changed();
+                    }
+                });
+            egui::ComboBox::from_id_salt("spectrogram length")
+                .selected_text(self.length.to_string())
+                .show_ui(ui, |ui| {
+                    for length in WINDOW_LENGTHS {
+                        changed |= ui
+                            .selectable_value(&mut self.length, length, length.to_string())
+                            .changed();
+                    }
+                });
+            egui::ComboBox::from_label("Hop")
+                .selected_text(
+                    HOP_FRACTIONS
+                        .iter()
+                        .find(|(fraction, _)| *fraction == self.hop_fraction)
+                        .map_or("", |(_, name)| name),
+                )
+                .show_ui(ui, |ui| {
+                    for (fraction, name) in HOP_FRACTIONS {
+                        changed |= ui
+                            .selectable_value(&mut self.hop_fraction, fraction, name)
+                            .changed();
+                    }
+                });
+        });
+        if changed {
+            self.reset();
+        }
+
+        let min_db = ui.add(
+            egui::DragValue::new(&mut self.min_db)
+                .range(-200.0..=self.max_db - 1.0)
+                .suffix(" dB")
+                .prefix("From "),
+        );
+        let max_db = ui.add(
+            egui::DragValue::new(&mut self.max_db)
+                .range(self.min_db + 1.0..=100.0)
+                .suffix(" dB")
+                .prefix("To "),
+        );
+        if min_db.changed() || max_db.changed() {
+            self.redraw(ui.ctx());
+        }
+    }
+
+    /// Color in the newest `count` columns, only sending those to the GPU
+    fn draw_columns(&mut self, ctx: &egui::Context, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let Some(texture) = &mut self.texture else {
+            self.redraw(ctx);
+            return;
+        };
+
+        let total = self.columns_made;
+        let stored = self.columns.range(..).len();
+        for (offset, column) in self.columns.range(stored - count.min(stored)..).enumerate() {
+            let x = (total - count.min(stored) + offset) % MAX_COLUMNS;
+            let image = column_image(column, self.min_db, self.max_db);
+            texture.set_partial([x, 0], image, TextureOptions::LINEAR);
+        }
+    }
+
+    /// Color in every column again, after the dB range changed
+    pub fn redraw(&mut self, ctx: &egui::Context) {
+        let height = self.length / 2 + 1;
+        let mut pixels = vec![color_map(0.0); MAX_COLUMNS * height];
+
+        let stored = self.columns.range(..).len();
+        let first = self.columns_made - stored;
+        for (offset, column) in self.columns.range(..).enumerate() {
+            let x = (first + offset) % MAX_COLUMNS;
+            let colors = column_image(column, self.min_db, self.max_db);
+            for (y, color) in colors.pixels.into_iter().enumerate() {
+                pixels[y * MAX_COLUMNS + x] = color;
+            }
+        }
+
+        let bytes: Vec<u8> = pixels.iter().flat_map(|color| color.to_array()).collect();
+        let image = ColorImage::from_rgba_unmultiplied([MAX_COLUMNS, height], &bytes);
+        self.texture = Some(ctx.load_texture("spectrogram", image, TextureOptions::LINEAR));
+    }
+
+    /// Seconds between the middles of two columns
+    fn column_seconds(&self) -> f64 {
+        let hop = (self.length / self.hop_fraction).max(1);
+        if self.sample_rate > 0.0 {
+            hop as f64 / self.sample_rate as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// The loudest frequency above 0 Hz and its magnitude in dB, in the column at `time`
+    pub fn peak_at(&self, time: f64) -> Option<(f32, f32)> {
+        let half = self.column_seconds() / 2.0;
+        let index = self
+            .column_times
+            .partition_point(|&middle| middle + half < time);
+        let &middle = self.column_times.range(index..).next()?;
+        if middle - half > time {
+            return None;
+        }
+        let column = self.columns.range(index..).next()?;
+        // 0 Hz is the offset of the signal, which would always be the loudest
+        let (bin, &magnitude) = column
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        Some((
+            bin as f32 * self.sample_rate / self.length as f32,
+            magnitude,
+        ))
+    }
+
+    /// Draw the columns between `times` on the heatmap and the color map legend next to it.
+    /// Returns where the heatmap is, to line the pointer up with a time.
+    pub fn draw(&self, ui: &mut egui::Ui, style: &ChartStyle, times: Range<f64>) -> Rect {
+        let rect = ui.available_rect_before_wrap();
+        let heatmap = Rect::from_min_max(rect.min, pos2(rect.max.x - LEGEND_WIDTH, rect.max.y));
+        let painter = ui.painter_at(rect);
+        let text_color = style.text_color();
+        let font = egui::FontId::monospace(style.label_size as f32 * 0.85);
+
+        painter.rect_filled(heatmap, 0.0, color_map(0.0));
+        if let Some(texture) = &self.texture
+            && times.end > times.start
+        {
+            let heatmap_painter = painter.with_clip_rect(heatmap);
+            let x = |time: f64| {
+                let fraction = (time - times.start) / (times.end - times.start);
+                heatmap.left() + fraction as f32 * heatmap.width()
+            };
+            let half = self.column_seconds() / 2.0;
+            let first = self
+                .column_times
+                .partition_point(|&middle| middle + half < times.start);
+            let last = self
+                .column_times
+                .partition_point(|&middle| middle - half <= times.end);
+            let oldest = self.columns_made - self.column_times.range(..).len();
+
+            // the texture is a ring, so the columns are drawn in runs that don't wrap around it
+            let mut start = first;
+            while start < last {
+                let texture_x = (oldest + start) % MAX_COLUMNS;
+                let end = last.min(start + MAX_COLUMNS - texture_x);
+                let start_time = self.column_times.range(start..).next().copied();
+                let end_time = self.column_times.range(end - 1..).next().copied();
+                if let (Some(start_time), Some(end_time)) = (start_time, end_time) {
+                    heatmap_painter.image(
+                        texture.id(),
+                        Rect::from_x_y_ranges(
+                            x(start_time - half)..=x(end_time + half),
+                            heatmap.y_range(),
+                        ),
+                        Rect::from_min_max(
+                            pos2(texture_x as f32 / MAX_COLUMNS as f32, 0.0),
+                            pos2((texture_x + end - start) as f32 / MAX_COLUMNS as f32, 1.0),
+                        ),
+                        Color32::WHITE,
+                    );
+                }
+                start = end;
+            }
+        }
+
+        painter.text(
+            heatmap.left_top() + egui::vec2(4.0, 2.0),
+            egui::Align2::LEFT_TOP,
+            format!("{:.0} Hz", self.sample_rate / 2.0),
+            font.clone(),
+            Color32::WHITE,
+        );
+        painter.text(
+            heatmap.left_bottom() + egui::vec2(4.0, -2.0),
+            egui::Align2::LEFT_BOTTOM,
+            "0 Hz",
+            font.clone(),
+            Color32::WHITE,
+        );
+
+        // color map legend, loudest at the top
+        let bar = Rect::from_min_max(
+            pos2(heatmap.right() + 8.0, rect.top() + 6.0),
+            pos2(heatmap.right() + 20.0, rect.bottom() - 6.0),
+        );
+        let steps = 32;
+        for step in 0..steps {
+            let top = bar.top() + bar.height() * step as f32 / steps as f32;
+            let bottom = bar.top() + bar.height() * (step + 1) as f32 / steps as f32;
+            let fraction = 1.0 - (step as f32 + 0.5) / steps as f32;
+            painter.rect_filled(
+                Rect::from_min_max(pos2(bar.left(), top), pos2(bar.right(), bottom)),
+                0.0,
+                color_map(fraction),
+            );
+        }
+        painter.text(
+            pos2(bar.right() + 4.0, bar.top()),
+            egui::Align2::LEFT_TOP,
+            format!("{:.0}", self.max_db),
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            pos2(bar.right() + 4.0, bar.bottom()),
+            egui::Align2::LEFT_BOTTOM,
+            format!("{:.0}", self.min_db),
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            pos2(bar.right() + 4.0, bar.center().y),
+            egui::Align2::LEFT_CENTER,
+            "dB",
+            font,
+            text_color,
+        );
+
+        heatmap
+    }
+}
+
+/// One column of the heatmap, highest frequency at the top
+fn column_image(column: &[f32], min_db: f32, max_db: f32) -> ColorImage {
+    let span = (max_db - min_db).max(f32::EPSILON);
+    let bytes: Vec<u8> = column
+        .iter()
+        .rev()
+        .flat_map(|magnitude| color_map((magnitude - min_db) / span).to_array())
+        .collect();
+    ColorImage::from_rgba_unmultiplied([1, column.len()], &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples a second of the test signal
+    const RATE: f32 = 1000.0;
+
+    /// A second of a 125 Hz sine then a second of a 250 Hz one, both on a bin of 256
+    fn two_tones() -> Channels {
+        let mut channels = Channels::new(4000);
+        for i in 0..2000 {
+            let time = i as f64 / RATE as f64;
+            let frequency = if i < 1000 { 125.0 } else { 250.0 };
+            let value = 512.0 + 300.0 * (std::f64::consts::TAU * frequency * time).sin();
+            channels.push("raw", time, value as f32);
+        }
+        channels
+    }
+
+    #[test]
+    fn sine_lands_in_its_row_at_its_time() {
+        let ctx = egui::Context::default();
+        let mut spectrogram = Spectrogram::default();
+        spectrogram.follow(&ctx, &two_tones(), Some(RATE));
+
+        let (low, _) = spectrogram.peak_at(0.4).unwrap();
+        let (high, _) = spectrogram.peak_at(1.6).unwrap();
+        assert_eq!(low, 125.0);
+        assert_eq!(high, 250.0);
+        assert!(spectrogram.peak_at(5.0).is_none());
+
+        // the column under 125 Hz is brightest 32 bins up from the bottom row
+        let index = spectrogram.column_times.partition_point(|&time| time < 0.4);
+        let column = spectrogram.columns.range(index..).next().unwrap();
+        // a range above the loudest bin, so the ones either side of it aren't as bright
+        let loudest = column.iter().copied().fold(f32::MIN, f32::max);
+        let image = column_image(column, spectrogram.min_db, loudest + 10.0);
+        let brightest = |color: &Color32| color.r() as u32 + color.g() as u32 + color.b() as u32;
+        let row = (1..image.pixels.len() - 1)
+            .max_by_key(|&row| brightest(&image.pixels[row]))
+            .unwrap();
+        assert_eq!(row, image.pixels.len() - 1 - 32);
+    }
+
+    #[test]
+    fn columns_are_a_hop_apart() {
+        let ctx = egui::Context::default();
+        let mut spectrogram = Spectrogram::default();
+        spectrogram.follow(&ctx, &two_tones(), Some(RATE));
+
+        // half of 256 samples at 1 kHz, the first centred on its window
+        let times: Vec<f64> = spectrogram.column_times.range(..).copied().collect();
+        assert_eq!(times.len(), (2000 - 256) / 128 + 1);
+        assert_eq!(times[0], 0.128);
+        assert!(
+            times
+                .windows(2)
+                .all(|pair| (pair[1] - pair[0] - 0.128).abs() < 1e-9)
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::channel::{Channel, Channels};
+use crate::plot;
+use crate::theme::ChartStyle;
+use crate::units::ValueAxis;
+
+/// How often the spectrum is recomputed, it doesn't need to keep up with every frame
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// Window lengths offered in the picker, the FFT needs a power of two
+pub const WINDOW_LENGTHS: [usize; 5] = [256, 512, 1024, 2048, 4096];
+/// Magnitudes are clamped to this so silence doesn't become -infinity dB
+const MIN_MAGNITUDE: f32 = 1e-9;
+
+/// Shapes applied to the samples before the FFT, so the cut at the edges of the
+/// window doesn't smear energy across the whole spectrum
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+}
+
+impl WindowFunction {
+    pub const ALL: [Self; 3] = [Self::Rectangular, Self::Hann, Self::Hamming];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rectangular => "Rectangular",
+            Self::Hann => "Hann",
+            Self::Hamming => "Hamming",
+        }
+    }
+
+    /// The weight of sample `i` out of `n`
+    fn weight(self, i: usize, n: usize) -> f32 {
+        let phase = TAU * i as f32 / (n - 1).max(1) as f32;
+        match self {
+            Self::Rectangular => 1.0,
+            Self::Hann => 0.5 - 0.5 * phase.cos(),
+            Self::Hamming => 0.54 - 0.46 * phase.cos(),
+        }
+    }
+}
+
+/// The spectrum of the newest samples of one channel
+pub struct SpectrumView {
+    /// Name of the channel to analyse
+    pub channel: String,
+    pub window: WindowFunction,
+    /// How many samples go into the FFT
+    pub length: usize,
+    /// Samples per second the last spectrum was computed with
+    pub sample_rate: f32,
+    /// (frequency in Hz, magnitude in dB)
+    pub bins: Vec<(f32, f32)>,
+    /// Times of the first and last samples that went into `bins`
+    pub span: Option<(f64, f64)>,
+    last_update: Option<Instant>,
+}
+
+impl Default for SpectrumView {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            window: WindowFunction::Hann,
+            length: 1024,
+            sample_rate: 0.0,
+            bins: Vec::new(),
+            span: None,
+            last_update: None,
+        }
+    }
+}
+
+impl SpectrumView {
+    /// Recompute the spectrum from the samples of `channel` up to `end` seconds,
+    /// if it has been long enough since the last time.
+    /// Without a `sample_rate` it is measured from the sample times.
+    pub fn update(&mut self, channel: &Channel, end: f64, sample_rate: Option<f32>) {
+        if self
+            .last_update
+            .is_some_and(|last| last.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        let last = channel.samples.partition_point(|&(time, _)| time <= end);
+        if last < self.length {
+            self.bins.clear();
+            self.span = None;
+            return;
+        }
+        let samples: Vec<(f64, f32)> = channel
+            .samples
+            .range(last - self.length..last)
+            .copied()
+            .collect();
+
+        self.sample_rate = sample_rate.unwrap_or_else(|| {
+            let seconds = samples[samples.len() - 1].0 - samples[0].0;
+            ((samples.len() - 1) as f64 / seconds.max(f64::EPSILON)) as f32
+        });
+        self.span = Some((samples[0].0, samples[samples.len() - 1].0));
+        let values: Vec<f32> = samples.iter().map(|&(_, value)| value).collect();
+        self.bins = spectrum(&values, self.sample_rate, self.window);
+    }
+
+    /// Compute the spectrum again on the next update, after a setting changed
+    pub fn refresh(&mut self) {
+        self.last_update = None;
+    }
+
+    /// Recompute the spectrum of the chosen channel up to `end`,
+    /// on the first channel when the chosen one isn't there
+    pub fn follow(&mut self, channels: &Channels, end: f64, sample_rate: Option<f32>) {
+        channels.fall_back(&mut self.channel);
+        if let Some(channel) = channels.get(&self.channel) {
+            self.update(channel, end, sample_rate);
+        }
+    }
+
+    /// The channel, window and length, with the magnitude axis, and the bin width
+    /// under them while the spectrum is `shown`
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        channels: &Channels,
+        magnitude_axis: &mut ValueAxis,
+        shown: bool,
+    ) {
+        ui.heading("Spectrum");
+
+        let mut changed =
+            channels.picker(ui, egui::ComboBox::from_label("Channel"), &mut self.channel);
+        egui::ComboBox::from_label("Window")
+            .selected_text(self.window.name())
+            .show_ui(ui, |ui| {
+                for window in WindowFunction::ALL {
+                    changed |= ui
+                        .selectable_value(&mut self.window, window, window.name())
+                        .changed();
+                }
+            });
+        egui::ComboBox::from_label("Length")
+            .selected_text(self.length.to_string())
+            .show_ui(ui, |ui| {
+                for length in WINDOW_LENGTHS {
+                    changed |= ui
+                        .selectable_value(&mut self.length, length, length.to_string())
+                        .changed();
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Magnitude");
+            magnitude_axis.controls(ui, "spectrum axis");
+        });
+
+        if changed {
+            self.refresh();
+        }
+        if shown {
+            if self.bins.is_empty() {
+                ui.label(format!("Waiting for {} samples", self.length));
+            } else {
+                ui.label(format!(
+                    "{:.0} Hz sample rate, {:.2} Hz per bin",
+                    self.sample_rate,
+                    self.sample_rate / self.length as f32
+                ));
+            }
+        }
+    }
+
+    /// Draw the spectrum in the chosen channel's color, with `gap_warning` over it
+    /// if the samples it was taken from have gaps
+    pub fn draw(
+        &self,
+        ui: &egui::Ui,
+        channels: &Channels,
+        magnitude_axis: ValueAxis,
+        style: &ChartStyle,
+        gap_warning: Option<String>,
+    ) {
+        let color = channels
+            .get(&self.channel)
+            .map_or(egui::Color32::RED, |channel| channel.color);
+        plot::draw_spectrum(ui, &self.bins, magnitude_axis, color, style);
+
+        // the FFT runs over the samples either side as if they were next to each other
+        if let Some(warning) = gap_warning {
+            let rect = ui.max_rect();
+            ui.painter().text(
+                rect.center_top() + egui::vec2(0.0, 4.0),
+                egui::Align2::CENTER_TOP,
+                format!("{warning}, the spectrum is smeared"),
+                egui::FontId::proportional(14.0),
+                ui.visuals().warn_fg_color,
+            );
+        }
+    }
+}
+
+/// The one-sided magnitude spectrum of `values` as (Hz, dB).
+/// The number of values has to be a power of two.
+pub fn spectrum(values: &[f32], sample_rate: f32, window: WindowFunction) -> Vec<(f32, f32)> {
+    let n = values.len();
+    // the DC offset of the EMG would otherwise hide everything near 0 Hz
+    let mean = values.iter().sum::<f32>() / n as f32;
+
+    let weights: Vec<f32> = (0..n).map(|i| window.weight(i, n)).collect();
+    let mut re: Vec<f32> = values
+        .iter()
+        .zip(&weights)
+        .map(|(value, weight)| (value - mean) * weight)
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    // scale so a sine of amplitude 1 shows up as 0 dB whatever the window
+    let gain = weights.iter().sum::<f32>() / 2.0;
+    (0..=n / 2)
+        .map(|bin| {
+            let frequency = bin as f32 * sample_rate / n as f32;
+            let magnitude = re[bin].hypot(im[bin]) / gain;
+            (frequency, 20.0 * magnitude.max(MIN_MAGNITUDE).log10())
+        })
+        .collect()
+}
+
+/// In place radix-2 FFT, `re` and `im` have to be the same power of two long
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    // put the samples in bit reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let angle = -TAU / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let even = start + k;
+                let odd = even + size / 2;
+                let odd_re = re[odd] * cos - im[odd] * sin;
+                let odd_im = re[odd] * sin + im[odd] * cos;
+                re[odd] = re[even] - odd_re;
+                im[odd] = im[even] - odd_im;
+                re[even] += odd_re;
+                im[even] += odd_im;
+            }
+        }
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Made up but not periodic, so every bin has something in it
+    fn noise(n: usize) -> Vec<f32> {
+        let mut state = 12_345u32;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as f32 / 32_768.0 - 1.0
+            })
+            .collect()
+    }
+
+    /// `values` through the FFT, as (re, im)
+    fn transform(values: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let mut re = values.to_vec();
+        let mut im = vec![0.0; values.len()];
+        fft(&mut re, &mut im);
+        (re, im)
+    }
+
+    #[test]
+    fn fft_of_dc_is_all_in_the_first_bin() {
+        let (re, im) = transform(&[3.0; 64]);
+
+        assert!((re[0] - 3.0 * 64.0).abs() < 1e-3);
+        assert!(im[0].abs() < 1e-3);
+        for bin in 1..64 {
+            assert!(re[bin].hypot(im[bin]) < 1e-3, "bin {bin}");
+        }
+    }
+
+    #[test]
+    fn fft_of_a_sine_lands_on_its_bin() {
+        let n = 256;
+        let values: Vec<f32> = (0..n)
+            .map(|i| (TAU * 10.0 * i as f32 / n as f32).sin())
+            .collect();
+        let (re, im) = transform(&values);
+
+        for bin in 0..n {
+            let magnitude = re[bin].hypot(im[bin]);
+            // a real sine shows up at its bin and mirrored at n - bin, half the amplitude in each
+            let expected = if bin == 10 || bin == n - 10 {
+                n as f32 / 2.0
+            } else {
+                0.0
+            };
+            assert!(
+                (magnitude - expected).abs() < 1e-2,
+                "bin {bin}: {magnitude}"
+            );
+        }
+    }
+
+    #[test]
+    fn fft_matches_a_slow_dft() {
+        let n = 32;
+        let values = noise(n);
+        let (re, im) = transform(&values);
+
+        for bin in 0..n {
+            let (mut dft_re, mut dft_im) = (0.0f32, 0.0f32);
+            for (i, value) in values.iter().enumerate() {
+                let angle = -TAU * (bin * i) as f32 / n as f32;
+                dft_re += value * angle.cos();
+                dft_im += value * angle.sin();
+            }
+            assert!((re[bin] - dft_re).abs() < 1e-3, "bin {bin}");
+            assert!((im[bin] - dft_im).abs() < 1e-3, "bin {bin}");
+        }
+    }
+
+    #[test]
+    fn fft_keeps_the_energy() {
+        let n = 1024;
+        let values = noise(n);
+        let (re, im) = transform(&values);
+
+        // Parseval: the energy in the samples is the energy in the bins over n
+        let time_energy: f32 = values.iter().map(|value| value * value).sum();
+        let frequency_energy: f32 = re.iter().zip(&im).map(|(re, im)| re * re + im * im).sum();
+        let ratio = frequency_energy / n as f32 / time_energy;
+        assert!((ratio - 1.0).abs() < 1e-4, "{ratio}");
+    }
+
+    #[test]
+    fn unit_sine_is_zero_db_at_its_frequency() {
+        // 1024 samples at 1024 Hz puts each bin on a whole Hz
+        let n = 1024;
+        let values: Vec<f32> = (0..n)
+            .map(|i| 512.0 + (TAU * 60.0 * i as f32 / n as f32).sin())
+            .collect();
+
+        for window in WindowFunction::ALL {
+            let bins = spectrum(&values, n as f32, window);
+            assert_eq!(bins.len(), n / 2 + 1);
+
+            let &(frequency, db) = bins.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+            assert_eq!(frequency, 60.0, "{}", window.name());
+            assert!(db.abs() < 0.1, "{}: {db} dB", window.name());
+        }
+    }
+
+    #[test]
+    fn dc_offset_is_taken_off() {
+        let bins = spectrum(&[512.0; 256], 1000.0, WindowFunction::Hann);
+
+        let floor = 20.0 * MIN_MAGNITUDE.log10();
+        assert!(bins.iter().all(|&(_, db)| db <= floor + 1.0));
+    }
+}
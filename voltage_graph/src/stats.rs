@@ -0,0 +1,251 @@
+use eframe::egui;
+
+use crate::channel::{Channel, Channels};
+
+/// Which samples the statistics are taken over
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StatsWindow {
+    LastSecond,
+    LastFiveSeconds,
+    /// Everything in the history, or the whole loaded file
+    Buffer,
+    /// The region dragged out on the plot
+    Selection,
+}
+
+impl StatsWindow {
+    pub const ALL: [Self; 4] = [
+        Self::LastSecond,
+        Self::LastFiveSeconds,
+        Self::Buffer,
+        Self::Selection,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::LastSecond => "Last 1 s",
+            Self::LastFiveSeconds => "Last 5 s",
+            Self::Buffer => "Entire buffer",
+            Self::Selection => "Selected region",
+        }
+    }
+}
+
+/// Summary of a set of samples
+pub struct Stats {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub rms: f32,
+    pub std_dev: f32,
+    pub p5: f32,
+    pub p95: f32,
+}
+
+impl Stats {
+    /// Summarize `values`, `None` if there aren't any. The values are reordered.
+    pub fn of(values: &mut [f32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let count = values.len();
+
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        // sums in f64 so a long buffer doesn't lose precision
+        let mut sum = 0.0f64;
+        let mut sum_squares = 0.0f64;
+        for &value in values.iter() {
+            min = min.min(value);
+            max = max.max(value);
+            sum += value as f64;
+            sum_squares += value as f64 * value as f64;
+        }
+        let mean = sum / count as f64;
+        let variance = (sum_squares / count as f64 - mean * mean).max(0.0);
+
+        Some(Self {
+            count,
+            min,
+            max,
+            mean: mean as f32,
+            rms: (sum_squares / count as f64).sqrt() as f32,
+            std_dev: variance.sqrt() as f32,
+            p5: percentile(values, 0.05),
+            p95: percentile(values, 0.95),
+        })
+    }
+}
+
+/// The channel and window picked for the statistics in the side panel
+pub struct StatsView {
+    /// Name of the channel the statistics are for
+    pub channel: String,
+    pub window: StatsWindow,
+}
+
+impl Default for StatsView {
+    fn default() -> Self {
+        Self {
+            channel: String::new(),
+            window: StatsWindow::LastSecond,
+        }
+    }
+}
+
+impl StatsView {
+    /// Pick the channel and window. With the selection picked there's a button that
+    /// clears `selection`.
+    pub fn controls(
+        &mut self,
+        ui: &mut egui::Ui,
+        channels: &Channels,
+        selection: &mut Option<(f64, f64)>,
+    ) {
+        ui.heading("Statistics");
+
+        channels.fall_back(&mut self.channel);
+        ui.horizontal(|ui| {
+            channels.picker(
+                ui,
+                egui::ComboBox::from_id_salt("stats channel"),
+                &mut self.channel,
+            );
+            egui::ComboBox::from_id_salt("stats window")
+                .selected_text(self.window.name())
+                .show_ui(ui, |ui| {
+                    for window in StatsWindow::ALL {
+                        ui.selectable_value(&mut self.window, window, window.name());
+                    }
+                });
+        });
+
+        if self.window == StatsWindow::Selection {
+            ui.horizontal(|ui| {
+                ui.label("Shift+drag on the plot to select");
+                if ui
+                    .add_enabled(selection.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    *selection = None;
+                }
+            });
+        }
+    }
+}
+
+/// The statistics of one channel, a line each
+pub fn grid(ui: &mut egui::Ui, stats: &Stats) {
+    egui::Grid::new("statistics").num_columns(2).show(ui, |ui| {
+        for (label, value) in [
+            ("Min", stats.min),
+            ("Max", stats.max),
+            ("Mean", stats.mean),
+            ("RMS", stats.rms),
+            ("Std dev", stats.std_dev),
+            ("5th percentile", stats.p5),
+            ("95th percentile", stats.p95),
+        ] {
+            ui.label(label);
+            ui.monospace(format!("{value:.3}"));
+            ui.end_row();
+        }
+        ui.label("Samples");
+        ui.monospace(stats.count.to_string());
+        ui.end_row();
+    });
+}
+
+/// The statistics of each channel in `rows`, a row each
+pub fn table(ui: &mut egui::Ui, rows: &[(&Channel, Stats)]) {
+    egui::ScrollArea::both().show(ui, |ui| {
+        egui::Grid::new("statistics table")
+            .num_columns(9)
+            .striped(true)
+            .show(ui, |ui| {
+                for header in [
+                    "Channel", "Min", "Max", "Mean", "RMS", "Std dev", "5th", "95th", "Samples",
+                ] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+
+                for (channel, stats) in rows {
+                    ui.colored_label(channel.color, channel.name.as_str());
+                    for value in [
+                        stats.min,
+                        stats.max,
+                        stats.mean,
+                        stats.rms,
+                        stats.std_dev,
+                        stats.p5,
+                        stats.p95,
+                    ] {
+                        ui.monospace(format!("{value:.3}"));
+                    }
+                    ui.monospace(stats.count.to_string());
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// The value `fraction` of the way through `values` once sorted, by nearest rank
+fn percentile(values: &mut [f32], fraction: f32) -> f32 {
+    let index = ((values.len() - 1) as f32 * fraction).round() as usize;
+    let (_, value, _) = values.select_nth_unstable_by(index, f32::total_cmp);
+    *value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_to_summarize() {
+        assert!(Stats::of(&mut []).is_none());
+    }
+
+    #[test]
+    fn summary_of_a_few_values() {
+        let stats = Stats::of(&mut [4.0, -2.0, 2.0, -4.0]).unwrap();
+
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.min, -4.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.mean, 0.0);
+        assert!((stats.rms - 10.0f32.sqrt()).abs() < 1e-6);
+        assert!((stats.std_dev - 10.0f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn std_dev_ignores_the_offset() {
+        let stats = Stats::of(&mut [1001.0, 999.0, 1001.0, 999.0]).unwrap();
+
+        assert_eq!(stats.mean, 1000.0);
+        assert!((stats.std_dev - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn percentiles_by_nearest_rank() {
+        // 0 to 100 shuffled, so the 5th and 95th percentiles are 5 and 95
+        let mut values: Vec<f32> = (0..=100).map(|i| ((i * 37) % 101) as f32).collect();
+        let stats = Stats::of(&mut values).unwrap();
+
+        assert_eq!(stats.p5, 5.0);
+        assert_eq!(stats.p95, 95.0);
+    }
+
+    #[test]
+    fn percentile_rounds_to_the_nearest_sample() {
+        let shuffled = [9.0, 1.0, 5.0, 3.0, 7.0, 2.0, 8.0, 4.0, 6.0, 0.0];
+        // 0.05 of the way through 10 values is index 0.45, so the first
+        assert_eq!(percentile(&mut shuffled.clone(), 0.05), 0.0);
+        // 0.95 is index 8.55, so the last
+        assert_eq!(percentile(&mut shuffled.clone(), 0.95), 9.0);
+        // and halfway is index 4.5, which rounds up
+        assert_eq!(percentile(&mut shuffled.clone(), 0.5), 5.0);
+        assert_eq!(percentile(&mut [42.0], 0.95), 42.0);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+
+use eframe::egui::{self, Id};
+
+/// The most lines kept in the scrollback
+const MAX_LINES: usize = 500;
+
+/// Id of the panel, egui remembers its size between sessions
+pub const TERMINAL_PANEL: &str = "terminal";
+/// Commands with their own button, since they get used all the time
+pub const QUICK_COMMANDS: [&str; 3] = ["VERSION", "CAL START", "SAVE"];
+
+/// A line in the scrollback
+pub enum TerminalLine {
+    /// A command typed in and sent to the firmware
+    Sent(String),
+    /// A line from the firmware that wasn't telemetry, like a reply to a command
+    Received(String),
+}
+
+/// The text side of the serial connection, for sending the firmware commands
+#[derive(Default)]
+pub struct Terminal {
+    pub show: bool,
+    /// The command being typed
+    pub input: String,
+    lines: VecDeque<TerminalLine>,
+    /// Commands sent before, oldest first
+    history: Vec<String>,
+    /// Which command in the history the up and down arrows are on
+    history_position: Option<usize>,
+}
+
+impl Terminal {
+    pub fn lines(&self) -> impl Iterator<Item = &TerminalLine> {
+        self.lines.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    pub fn received(&mut self, line: String) {
+        self.push(TerminalLine::Received(line));
+    }
+
+    /// Note a command as sent and remember it for the up arrow
+    pub fn sent(&mut self, command: &str) {
+        if self.history.last().is_none_or(|last| last != command) {
+            self.history.push(command.to_owned());
+        }
+        self.history_position = None;
+        self.push(TerminalLine::Sent(command.to_owned()));
+    }
+
+    /// Put the command before the one shown in the input
+    pub fn history_back(&mut self) {
+        let position = match self.history_position {
+            Some(position) => position.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.history_position = Some(position);
+        self.input = self.history[position].clone();
+    }
+
+    /// The panel along the bottom, returns a command to send if one was entered
+    pub fn panel(&mut self, ctx: &egui::Context) -> Option<String> {
+        if !self.show {
+            return None;
+        }
+
+        let mut command = None;
+        egui::TopBottomPanel::bottom(Id::new(TERMINAL_PANEL))
+            .resizable(true)
+            .default_height(200.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Terminal");
+                    for quick in QUICK_COMMANDS {
+                        if ui.button(quick).clicked() {
+                            command = Some(quick.to_owned());
+                        }
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.clear();
+                    }
+                });
+
+                // the input goes along the bottom, the scrollback fills the rest
+                egui::TopBottomPanel::bottom(Id::new("terminal input"))
+                    .show_separator_line(false)
+                    .show_inside(ui, |ui| {
+                        let input = ui.add(
+                            egui::TextEdit::singleline(&mut self.input)
+                                .font(egui::TextStyle::Monospace)
+                                .hint_text("Command, up and down for history")
+                                .desired_width(f32::INFINITY),
+                        );
+                        if input.has_focus() {
+                            if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+                                self.history_back();
+                            }
+                            if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                                self.history_forward();
+                            }
+                        }
+                        if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            command = Some(std::mem::take(&mut self.input));
+                            input.request_focus();
+                        }
+                    });
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in self.lines() {
+                            match line {
+                                TerminalLine::Sent(text) => {
+                                    ui.monospace(
+                                        egui::RichText::new(format!("> {text}"))
+                                            .color(ui.visuals().hyperlink_color),
+                                    );
+                                }
+                                TerminalLine::Received(text) => {
+                                    ui.monospace(text.as_str());
+                                }
+                            }
+                        }
+                    });
+            });
+
+        command
+    }
+
+    /// Put the command after the one shown in the input, or clear it at the end of the history
+    pub fn history_forward(&mut self) {
+        let Some(position) = self.history_position else {
+            return;
+        };
+        if position + 1 < self.history.len() {
+            self.history_position = Some(position + 1);
+            self.input = self.history[position + 1].clone();
+        } else {
+            self.history_position = None;
+            self.input.clear();
+        }
+    }
+
+    fn push(&mut self, line: TerminalLine) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
@@ -0,0 +1,107 @@
+use eframe::egui::{Color32, Visuals};
+use plotters::style::RGBColor;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Black on white with thick lines and big text, so plots still read on a projector
+    Presentation,
+}
+
+/// How a chart is drawn apart from the channels, which keep their own colors
+pub struct ChartStyle {
+    pub background: RGBColor,
+    /// Axes, labels and the legend border
+    pub text: RGBColor,
+    pub mesh: RGBColor,
+    pub bold_mesh: RGBColor,
+    /// A span dragged out on the plot
+    pub selection: RGBColor,
+    /// The trigger line and motion artifacts
+    pub trigger: RGBColor,
+    /// Clipped spans and where contractions end
+    pub warning: RGBColor,
+    /// Where contractions start
+    pub onset: RGBColor,
+    /// Width of the channels' lines, in pixels
+    pub line_width: u32,
+    /// Width of markers and thresholds drawn over the data
+    pub marker_width: u32,
+    pub caption_size: u32,
+    /// Tick labels, the legend and notes on the plot
+    pub label_size: u32,
+    /// What each axis is
+    pub axis_size: u32,
+}
+
+impl Theme {
+    pub const ALL: [Self; 3] = [Self::Light, Self::Dark, Self::Presentation];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Light => "Light",
+            Self::Dark => "Dark",
+            Self::Presentation => "Presentation",
+        }
+    }
+
+    pub fn visuals(self) -> Visuals {
+        match self {
+            Self::Light | Self::Presentation => Visuals::light(),
+            Self::Dark => Visuals::dark(),
+        }
+    }
+
+    /// The chart style that goes with the egui visuals, the channel colors are picked to show on both
+    pub fn chart_style(self) -> ChartStyle {
+        let plain = ChartStyle {
+            background: RGBColor(255, 255, 255),
+            text: RGBColor(0, 0, 0),
+            mesh: RGBColor(235, 235, 235),
+            bold_mesh: RGBColor(200, 200, 200),
+            selection: RGBColor(0, 0, 255),
+            trigger: RGBColor(255, 0, 255),
+            warning: RGBColor(255, 0, 0),
+            onset: RGBColor(0, 255, 0),
+            line_width: 1,
+            marker_width: 1,
+            caption_size: 20,
+            label_size: 12,
+            axis_size: 14,
+        };
+        match self {
+            Self::Light => plain,
+            Self::Dark => ChartStyle {
+                background: RGBColor(27, 27, 27),
+                text: RGBColor(210, 210, 210),
+                mesh: RGBColor(45, 45, 45),
+                bold_mesh: RGBColor(75, 75, 75),
+                selection: RGBColor(90, 140, 255),
+                ..plain
+            },
+            Self::Presentation => ChartStyle {
+                mesh: RGBColor(215, 215, 215),
+                bold_mesh: RGBColor(150, 150, 150),
+                trigger: RGBColor(170, 0, 170),
+                warning: RGBColor(200, 0, 0),
+                onset: RGBColor(0, 150, 0),
+                line_width: 3,
+                marker_width: 2,
+                caption_size: 28,
+                label_size: 18,
+                axis_size: 20,
+                ..plain
+            },
+        }
+    }
+}
+
+impl ChartStyle {
+    /// The text color for things drawn with egui next to a chart
+    pub fn text_color(&self) -> Color32 {
+        let RGBColor(r, g, b) = self.text;
+        Color32::from_rgb(r, g, b)
+    }
+}
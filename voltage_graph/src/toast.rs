@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+/// How long a message stays on screen
+const TOAST_LIFETIME: Duration = Duration::from_secs(5);
+
+struct Toast {
+    message: String,
+    is_error: bool,
+    shown: Instant,
+}
+
+/// Short messages in the corner of the window that go away on their own
+#[derive(Default)]
+pub struct Toasts {
+    messages: Vec<Toast>,
+}
+
+impl Toasts {
+    /// Show an error message
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(message.into(), true);
+    }
+
+    /// Show a message that something worked
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(message.into(), false);
+    }
+
+    fn push(&mut self, message: String, is_error: bool) {
+        self.messages.push(Toast {
+            message,
+            is_error,
+            shown: Instant::now(),
+        });
+    }
+
+    /// Draw the messages that have not timed out yet
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.messages
+            .retain(|toast| toast.shown.elapsed() < TOAST_LIFETIME);
+        if self.messages.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .show(ctx, |ui| {
+                let error_color = ui.visuals().error_fg_color;
+                for toast in &self.messages {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        if toast.is_error {
+                            ui.colored_label(error_color, toast.message.as_str());
+                        } else {
+                            ui.label(toast.message.as_str());
+                        }
+                    });
+                }
+            });
+
+        // wake up again to take the message down
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,320 @@
+use std::ops::Range;
+
+use eframe::egui;
+
+use crate::channel::Channels;
+
+/// Which way the signal has to cross the level to trigger
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// What happens after a capture
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Capture once and wait to be armed again
+    Single,
+    /// Arm again straight away, so the display moves on to the next event
+    Auto,
+}
+
+/// Freezes the plot around the moment a channel crosses a level, like an oscilloscope.
+/// Samples are checked as they arrive so a short event between frames isn't missed.
+pub struct Trigger {
+    pub enabled: bool,
+    /// Name of the channel watched for the crossing
+    pub channel: String,
+    pub level: f32,
+    pub edge: Edge,
+    pub mode: TriggerMode,
+    /// Seconds shown before the crossing
+    pub pre: f64,
+    /// Seconds shown after the crossing
+    pub post: f64,
+    armed: bool,
+    /// The last sample of the channel, as (time, value)
+    last: Option<(f64, f32)>,
+    /// A crossing waiting for its post-trigger samples to come in
+    pending: Option<f64>,
+    /// The crossing the plot is frozen on
+    captured: Option<f64>,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: String::new(),
+            level: 512.0,
+            edge: Edge::Rising,
+            mode: TriggerMode::Auto,
+            pre: 0.5,
+            post: 1.5,
+            armed: true,
+            last: None,
+            pending: None,
+            captured: None,
+        }
+    }
+}
+
+impl Trigger {
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    /// The time of the crossing the plot is frozen on
+    pub fn captured(&self) -> Option<f64> {
+        self.captured
+    }
+
+    /// Wait for the next crossing
+    pub fn arm(&mut self) {
+        self.armed = true;
+        self.pending = None;
+    }
+
+    /// Forget the capture and go back to the live plot
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.pending = None;
+        self.captured = None;
+    }
+
+    /// Check a new sample of channel `name` for a crossing
+    pub fn push(&mut self, name: &str, time: f64, value: f32) {
+        if !self.enabled || name != self.channel {
+            return;
+        }
+        // the time went backwards, so a new stream started
+        if self.last.is_some_and(|(last_time, _)| time < last_time) {
+            self.reset();
+        }
+
+        if self.armed
+            && self.pending.is_none()
+            && let Some((last_time, last_value)) = self.last
+        {
+            let crossed = match self.edge {
+                Edge::Rising => last_value < self.level && value >= self.level,
+                Edge::Falling => last_value > self.level && value <= self.level,
+            };
+            if crossed {
+                // the time the line between the two samples passes the level
+                let fraction = (self.level - last_value) / (value - last_value);
+                self.pending = Some(last_time + (time - last_time) * fraction as f64);
+            }
+        }
+        self.last = Some((time, value));
+
+        if let Some(crossing) = self.pending
+            && time >= crossing + self.post
+        {
+            self.captured = Some(crossing);
+            self.pending = None;
+            self.armed = self.mode == TriggerMode::Auto;
+        }
+    }
+
+    /// The times to show on the plot, `None` until something has been captured
+    pub fn window(&self) -> Option<Range<f64>> {
+        self.captured
+            .filter(|_| self.enabled)
+            .map(|crossing| crossing - self.pre..crossing + self.post)
+    }
+
+    /// The settings for the trigger and whether it is waiting or has captured.
+    /// `history_seconds` is the most it can show either side of a crossing.
+    pub fn controls(&mut self, ui: &mut egui::Ui, channels: &Channels, history_seconds: f32) {
+        ui.heading("Trigger");
+
+        if ui
+            .checkbox(&mut self.enabled, "Freeze the plot on a crossing")
+            .changed()
+        {
+            self.reset();
+            self.arm();
+        }
+        if !self.enabled {
+            return;
+        }
+
+        channels.fall_back(&mut self.channel);
+
+        ui.horizontal(|ui| {
+            channels.picker(
+                ui,
+                egui::ComboBox::from_id_salt("trigger channel"),
+                &mut self.channel,
+            );
+            ui.add(egui::DragValue::new(&mut self.level).prefix("Level "));
+        });
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.edge, Edge::Rising, "Rising");
+            ui.selectable_value(&mut self.edge, Edge::Falling, "Falling");
+            ui.separator();
+            ui.selectable_value(&mut self.mode, TriggerMode::Auto, "Auto")
+                .on_hover_text("Arm again after every capture");
+            ui.selectable_value(&mut self.mode, TriggerMode::Single, "Single")
+                .on_hover_text("Capture once, then wait to be armed");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.pre)
+                    .range(0.0..=history_seconds)
+                    .speed(0.01)
+                    .prefix("Before ")
+                    .suffix(" s"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.post)
+                    .range(0.01..=history_seconds)
+                    .speed(0.01)
+                    .prefix("After ")
+                    .suffix(" s"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.is_armed(), egui::Button::new("Arm"))
+                .clicked()
+            {
+                self.arm();
+            }
+            if ui
+                .add_enabled(self.captured().is_some(), egui::Button::new("Live"))
+                .on_hover_text("Drop the capture and show the live plot until the next one")
+                .clicked()
+            {
+                self.reset();
+            }
+        });
+        let status = match (self.is_armed(), self.captured()) {
+            (true, None) => String::from("Waiting for a crossing"),
+            (true, Some(time)) => format!("Showing t = {time:.3} s, waiting for the next"),
+            (false, Some(time)) => format!("Stopped on t = {time:.3} s"),
+            (false, None) => String::from("Not armed"),
+        };
+        ui.label(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(edge: Edge, mode: TriggerMode) -> Trigger {
+        Trigger {
+            enabled: true,
+            channel: String::from("raw"),
+            level: 500.0,
+            edge,
+            mode,
+            pre: 0.5,
+            post: 1.0,
+            ..Default::default()
+        }
+    }
+
+    /// Push `values` one every 0.1 s starting at `start`
+    fn push_all(trigger: &mut Trigger, start: f64, values: &[f32]) {
+        for (i, &value) in values.iter().enumerate() {
+            trigger.push("raw", start + i as f64 * 0.1, value);
+        }
+    }
+
+    #[test]
+    fn rising_edge_waits_for_the_post_trigger_samples() {
+        let mut trigger = trigger(Edge::Rising, TriggerMode::Single);
+
+        // crosses 500 a quarter of the way from 0.1 s to 0.2 s
+        push_all(&mut trigger, 0.0, &[400.0, 400.0, 800.0, 800.0]);
+        assert_eq!(trigger.captured(), None);
+        assert_eq!(trigger.window(), None);
+
+        push_all(&mut trigger, 0.4, &[800.0; 10]);
+        let crossing = trigger.captured().unwrap();
+        assert!((crossing - 0.125).abs() < 1e-4);
+        let window = trigger.window().unwrap();
+        assert!((window.start - (crossing - 0.5)).abs() < 1e-6);
+        assert!((window.end - (crossing + 1.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn falling_edge_ignores_rising_crossings() {
+        let mut trigger = trigger(Edge::Falling, TriggerMode::Single);
+
+        push_all(&mut trigger, 0.0, &[400.0, 600.0, 600.0]);
+        push_all(&mut trigger, 0.3, &[600.0; 20]);
+        assert_eq!(trigger.captured(), None);
+
+        push_all(&mut trigger, 2.3, &[400.0]);
+        push_all(&mut trigger, 2.4, &[400.0; 20]);
+        let crossing = trigger.captured().unwrap();
+        assert!(crossing > 2.2 && crossing <= 2.3);
+    }
+
+    #[test]
+    fn single_waits_to_be_armed_again() {
+        let mut trigger = trigger(Edge::Rising, TriggerMode::Single);
+
+        push_all(&mut trigger, 0.0, &[400.0, 600.0]);
+        push_all(&mut trigger, 0.2, &[600.0; 10]);
+        let first = trigger.captured().unwrap();
+        assert!(!trigger.is_armed());
+
+        // a second crossing leaves the plot on the first
+        push_all(&mut trigger, 1.2, &[400.0, 600.0]);
+        push_all(&mut trigger, 1.4, &[600.0; 20]);
+        assert_eq!(trigger.captured(), Some(first));
+
+        trigger.arm();
+        push_all(&mut trigger, 3.4, &[400.0, 600.0]);
+        push_all(&mut trigger, 3.6, &[600.0; 20]);
+        assert!(trigger.captured().unwrap() > 3.4);
+    }
+
+    #[test]
+    fn auto_moves_on_to_the_next_crossing() {
+        let mut trigger = trigger(Edge::Rising, TriggerMode::Auto);
+
+        push_all(&mut trigger, 0.0, &[400.0, 600.0]);
+        push_all(&mut trigger, 0.2, &[600.0; 10]);
+        assert!(trigger.is_armed());
+
+        push_all(&mut trigger, 1.2, &[400.0, 600.0]);
+        push_all(&mut trigger, 1.4, &[600.0; 20]);
+        assert!(trigger.captured().unwrap() > 1.2);
+    }
+
+    #[test]
+    fn other_channels_and_disabled_are_ignored() {
+        let mut trigger = trigger(Edge::Rising, TriggerMode::Auto);
+        for (i, value) in [400.0, 600.0].into_iter().chain([600.0; 20]).enumerate() {
+            trigger.push("smoothed", i as f64 * 0.1, value);
+        }
+        assert_eq!(trigger.captured(), None);
+
+        push_all(&mut trigger, 0.0, &[400.0, 600.0]);
+        push_all(&mut trigger, 0.2, &[600.0; 20]);
+        assert!(trigger.window().is_some());
+        trigger.enabled = false;
+        assert_eq!(trigger.window(), None);
+    }
+
+    #[test]
+    fn time_going_back_starts_over() {
+        let mut trigger = trigger(Edge::Rising, TriggerMode::Auto);
+
+        push_all(&mut trigger, 0.0, &[400.0, 600.0]);
+        push_all(&mut trigger, 0.2, &[600.0; 20]);
+        assert!(trigger.captured().is_some());
+
+        // a reconnect starts the times from 0 again
+        push_all(&mut trigger, 0.0, &[400.0]);
+        assert_eq!(trigger.captured(), None);
+    }
+}
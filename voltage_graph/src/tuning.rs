@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::channel::Channels;
+use crate::derived::DerivedChannel;
+
+/// How often the filters are measured again
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+/// How many samples either side of a point are averaged when looking for a step
+const STEP_WINDOW: usize = 50;
+/// How many samples the moving average that ripple is measured against spans
+const RIPPLE_WINDOW: usize = 25;
+
+/// The biggest change in level in a channel, used as the step filters are judged by
+pub struct Step {
+    pub time: f64,
+    /// The average level before the step
+    pub before: f32,
+    /// The average level after the step
+    pub after: f32,
+}
+
+/// How one filter did on the data
+pub struct Comparison {
+    /// Seconds from the step until the filter got 90% of the way to the new level,
+    /// `None` if it never got there
+    pub lag: Option<f64>,
+    /// RMS of what is left after taking away a short moving average, how jittery the output is
+    pub ripple: f32,
+}
+
+/// Find where the mean of the samples before and after a point differ the most
+pub fn find_step(samples: &[(f64, f32)]) -> Option<Step> {
+    if samples.len() < STEP_WINDOW * 2 {
+        return None;
+    }
+
+    // running sums so every point is checked in one pass
+    let mut sums = Vec::with_capacity(samples.len() + 1);
+    sums.push(0.0f64);
+    for &(_, value) in samples {
+        sums.push(sums[sums.len() - 1] + value as f64);
+    }
+    let mean = |start: usize, end: usize| ((sums[end] - sums[start]) / (end - start) as f64) as f32;
+
+    (STEP_WINDOW..=samples.len() - STEP_WINDOW)
+        .map(|i| (i, mean(i - STEP_WINDOW, i), mean(i, i + STEP_WINDOW)))
+        .max_by(|a, b| (a.2 - a.1).abs().total_cmp(&(b.2 - b.1).abs()))
+        .map(|(i, before, after)| Step {
+            time: samples[i].0,
+            before,
+            after,
+        })
+}
+
+/// Measure how a filter's output `filtered` responded to `step`
+pub fn compare(step: Option<&Step>, filtered: &[(f64, f32)]) -> Comparison {
+    let lag = step.and_then(|step| {
+        let target = step.before + 0.9 * (step.after - step.before);
+        let rising = step.after > step.before;
+        let start = filtered.partition_point(|&(time, _)| time < step.time);
+        filtered[start..]
+            .iter()
+            .find(|&&(_, value)| {
+                if rising {
+                    value >= target
+                } else {
+                    value <= target
+                }
+            })
+            .map(|&(time, _)| time - step.time)
+    });
+
+    Comparison {
+        lag,
+        ripple: ripple(filtered),
+    }
+}
+
+/// RMS of the difference between each value and the moving average around it
+fn ripple(samples: &[(f64, f32)]) -> f32 {
+    if samples.len() < RIPPLE_WINDOW {
+        return 0.0;
+    }
+
+    let mut sum: f64 = samples[..RIPPLE_WINDOW]
+        .iter()
+        .map(|&(_, value)| value as f64)
+        .sum();
+    let mut squares = 0.0f64;
+    let mut count = 0;
+    let half = RIPPLE_WINDOW / 2;
+    for end in RIPPLE_WINDOW..=samples.len() {
+        let average = sum / RIPPLE_WINDOW as f64;
+        let residual = samples[end - RIPPLE_WINDOW + half].1 as f64 - average;
+        squares += residual * residual;
+        count += 1;
+        if end < samples.len() {
+            sum += samples[end].1 as f64 - samples[end - RIPPLE_WINDOW].1 as f64;
+        }
+    }
+    (squares / count as f64).sqrt() as f32
+}
+
+/// Measure each filter, as (name, source), on the biggest step in its source
+/// from `start` to `end` seconds
+pub fn compare_filters<'a>(
+    filters: impl IntoIterator<Item = (&'a str, &'a str)>,
+    channels: &Channels,
+    start: f64,
+    end: f64,
+) -> Vec<(String, Comparison)> {
+    let samples = |name: &str| -> Vec<(f64, f32)> {
+        channels
+            .get(name)
+            .map(|channel| channel.between(start, end).copied().collect())
+            .unwrap_or_default()
+    };
+    filters
+        .into_iter()
+        .map(|(name, source)| {
+            let step = find_step(&samples(source));
+            (name.to_owned(), compare(step.as_ref(), &samples(name)))
+        })
+        .collect()
+}
+
+/// Write the comparison table to `path` as CSV, one row per filter with the lag in ms,
+/// a filter that never reached 90% has a blank lag
+pub fn write_csv(path: &Path, comparisons: &[(String, Comparison)]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "filter,lag_90_ms,ripple_rms")?;
+    for (name, comparison) in comparisons {
+        let lag = comparison
+            .lag
+            .map_or(String::new(), |lag| (lag * 1000.0).to_string());
+        writeln!(
+            writer,
+            "\"{}\",{lag},{}",
+            name.replace('"', "\"\""),
+            comparison.ripple
+        )?;
+    }
+
+    writer.flush()
+}
+
+/// How each filter did, by name of the derived channel
+#[derive(Default)]
+pub struct FilterComparison {
+    pub rows: Vec<(String, Comparison)>,
+    last: Option<Instant>,
+}
+
+impl FilterComparison {
+    /// Measure how each filter in `derived` responds to the biggest step in its source,
+    /// once in a while. Only `span` is used if there is one.
+    pub fn update(
+        &mut self,
+        derived: &[DerivedChannel],
+        channels: &Channels,
+        span: Option<(f64, f64)>,
+    ) {
+        if self
+            .last
+            .is_some_and(|last| last.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last = Some(Instant::now());
+
+        let (start, end) = span.unwrap_or((f64::NEG_INFINITY, f64::INFINITY));
+        let filters = derived
+            .iter()
+            .map(|derived| (derived.name.as_str(), derived.source.as_str()));
+        self.rows = compare_filters(filters, channels, start, end);
+    }
+
+    /// The lag and ripple of each filter, measured in the selection if `selected`.
+    /// Returns whether the table should be exported.
+    pub fn table(&self, ui: &mut egui::Ui, selected: bool) -> bool {
+        if self.rows.is_empty() {
+            return false;
+        }
+        ui.label(if selected {
+            "Measured on the biggest step in the selection"
+        } else {
+            "Measured on the biggest step in the history"
+        });
+        egui::Grid::new("filter comparison")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Filter");
+                ui.strong("90% lag");
+                ui.strong("Ripple RMS");
+                ui.end_row();
+                for (name, comparison) in &self.rows {
+                    ui.label(name.as_str());
+                    match comparison.lag {
+                        Some(lag) => ui.monospace(format!("{:.0} ms", lag * 1000.0)),
+                        None => ui.monospace("-"),
+                    };
+                    ui.monospace(format!("{:.2}", comparison.ripple));
+                    ui.end_row();
+                }
+            });
+        ui.button("Export table").clicked()
+    }
+}
+
+/// The alphas typed in `text`, split by commas or spaces. Anything that isn't
+/// a number above 0 and up to 1 is left out.
+pub fn parse_alphas(text: &str) -> Vec<f32> {
+    text.split([',', ' '])
+        .filter_map(|field| field.trim().parse().ok())
+        .filter(|&alpha: &f32| alpha > 0.0 && alpha <= 1.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hand_core::{EmgSimulator, EmgState, LcgRng, SimProfile};
+
+    use super::*;
+    use crate::derived::FilterKind;
+
+    /// Samples a second of the capture
+    const RATE: f64 = 1000.0;
+
+    /// Two seconds relaxed then two clenched, the way the firmware makes them
+    fn capture() -> Vec<(f64, f32)> {
+        let mut simulator = EmgSimulator::new();
+        let mut rng = LcgRng::new(7);
+        [EmgState::Relaxed, EmgState::Clenched]
+            .into_iter()
+            .flat_map(|state| std::iter::repeat_n(state, 2000))
+            .enumerate()
+            .map(|(index, state)| {
+                simulator.profile = SimProfile::Hold(state);
+                let value = simulator.next(rng.rand_bounded_u32(1024) as u16);
+                (index as f64 / RATE, value as f32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kalman_against_ema_on_a_capture() {
+        let mut channels = Channels::new(0);
+        channels.load(vec![(String::from("raw"), capture())]);
+        let filters = [
+            ("ema", FilterKind::Ema { alpha: 0.15 }),
+            (
+                "kalman",
+                FilterKind::Kalman {
+                    process_noise: 0.05,
+                    measurement_noise: 100.0,
+                },
+            ),
+        ];
+        for (name, kind) in filters {
+            DerivedChannel::new(name.to_owned(), "raw", kind).update(&mut channels);
+        }
+
+        let rows = compare_filters(
+            [("raw", "raw"), ("ema", "raw"), ("kalman", "raw")],
+            &channels,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+        );
+        let [(_, raw), (_, ema), (_, kalman)] = &rows[..] else {
+            panic!("{} rows", rows.len());
+        };
+        let (Some(ema_lag), Some(kalman_lag)) = (ema.lag, kalman.lag) else {
+            panic!("a filter never got to the clenched level");
+        };
+        // the Kalman filter follows the contraction sooner, at the cost of more ripple
+        // than the EMA, both smoothing the raw signal
+        assert!(
+            kalman_lag < ema_lag,
+            "kalman lag {kalman_lag} s, ema lag {ema_lag} s"
+        );
+        assert!(
+            ema.ripple < kalman.ripple && kalman.ripple < raw.ripple,
+            "ripple: ema {}, kalman {}, raw {}",
+            ema.ripple,
+            kalman.ripple,
+            raw.ripple
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::{Axis, Channel};
+
+/// A straight line from the values a channel holds to the values shown
+#[derive(Clone, Copy)]
+pub struct Scale {
+    pub factor: f32,
+    pub offset: f32,
+}
+
+impl Scale {
+    /// Leaves values as they are
+    pub const IDENTITY: Self = Self {
+        factor: 1.0,
+        offset: 0.0,
+    };
+
+    pub fn apply(self, value: f32) -> f32 {
+        value * self.factor + self.offset
+    }
+
+    /// Turn a shown value back into the channel's own units
+    pub fn invert(self, value: f32) -> f32 {
+        (value - self.offset) / self.factor
+    }
+
+    /// The shown range of values from `low` to `high`, still lowest first if the factor is negative
+    pub fn range(self, low: f32, high: f32) -> (f32, f32) {
+        let (low, high) = (self.apply(low), self.apply(high));
+        (low.min(high), low.max(high))
+    }
+}
+
+/// How ADC counts are turned into millivolts.
+/// Everything that shows a value goes through here so they all agree.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Units {
+    /// Show values in millivolts instead of ADC counts
+    pub millivolts: bool,
+    /// The ADC's reference voltage
+    pub reference_volts: f32,
+    /// The ADC's resolution
+    pub bits: u32,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            millivolts: false,
+            reference_volts: 5.0,
+            bits: 10,
+        }
+    }
+}
+
+impl Units {
+    pub fn label(&self) -> &'static str {
+        if self.millivolts { "mV" } else { "ADC counts" }
+    }
+
+    /// How `channel` is shown. Channels on the right axis, like servo angles,
+    /// aren't ADC counts so they are left alone.
+    pub fn scale(&self, channel: &Channel) -> Scale {
+        if self.millivolts && channel.axis == Axis::Left {
+            self.millivolt_scale(channel)
+        } else {
+            Scale::IDENTITY
+        }
+    }
+
+    /// Counts to millivolts at the electrodes, taking out the channel's amplifier
+    pub fn millivolt_scale(&self, channel: &Channel) -> Scale {
+        let max_count = ((1u64 << self.bits.clamp(1, 24)) - 1) as f32;
+        let millivolts_per_count = self.reference_volts * 1000.0 / max_count;
+        Scale {
+            factor: millivolts_per_count / channel.gain,
+            offset: -channel.offset / channel.gain,
+        }
+    }
+
+    /// Counts or millivolts, and the ADC that turns one into the other
+    pub fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.millivolts, false, "Counts");
+            ui.selectable_value(&mut self.millivolts, true, "Millivolts");
+        });
+        if self.millivolts {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.reference_volts)
+                        .range(0.1..=20.0)
+                        .speed(0.01)
+                        .prefix("Reference ")
+                        .suffix(" V"),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.bits)
+                        .range(8..=16)
+                        .suffix(" bit"),
+                );
+            });
+        }
+    }
+}
+
+/// Values are clamped up to this before a log is taken, so zero and negative samples
+/// sit at the bottom of the plot instead of turning into NaN
+const LOG_FLOOR: f32 = 1e-6;
+
+/// How values are spread up a y-axis
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AxisScale {
+    Linear,
+    /// Each step up the axis is ten times bigger
+    Log10,
+    /// Decibels relative to a reference value, 20 dB for every ten times bigger
+    Decibels,
+}
+
+impl AxisScale {
+    pub const ALL: [Self; 3] = [Self::Linear, Self::Log10, Self::Decibels];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Log10 => "Log10",
+            Self::Decibels => "dB",
+        }
+    }
+}
+
+/// The scale of one view's y-axis. Values are put through it after `Units`.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ValueAxis {
+    pub scale: AxisScale,
+    /// The value that is 0 dB, in the units shown
+    pub reference: f32,
+}
+
+impl Default for ValueAxis {
+    fn default() -> Self {
+        Self {
+            scale: AxisScale::Linear,
+            reference: 1.0,
+        }
+    }
+}
+
+impl ValueAxis {
+    pub const DECIBELS: Self = Self {
+        scale: AxisScale::Decibels,
+        reference: 1.0,
+    };
+
+    /// Where `value` goes up the axis
+    pub fn apply(self, value: f32) -> f32 {
+        match self.scale {
+            AxisScale::Linear => value,
+            AxisScale::Log10 => value.max(LOG_FLOOR).log10(),
+            AxisScale::Decibels => {
+                20.0 * (value.max(LOG_FLOOR) / self.reference.max(LOG_FLOOR)).log10()
+            }
+        }
+    }
+
+    /// Turn a position up the axis back into a value
+    pub fn invert(self, shown: f32) -> f32 {
+        match self.scale {
+            AxisScale::Linear => shown,
+            AxisScale::Log10 => 10f32.powf(shown),
+            AxisScale::Decibels => self.reference.max(LOG_FLOOR) * 10f32.powf(shown / 20.0),
+        }
+    }
+
+    /// What the axis is in, for values in `unit`
+    pub fn label(self, unit: &str) -> String {
+        match self.scale {
+            AxisScale::Linear => unit.to_owned(),
+            AxisScale::Log10 => format!("log {unit}"),
+            AxisScale::Decibels => format!("dB re {} {unit}", self.reference),
+        }
+    }
+
+    /// Pick linear, log or dB, and the reference for dB
+    pub fn controls(&mut self, ui: &mut egui::Ui, id: &str) {
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(self.scale.name())
+            .show_ui(ui, |ui| {
+                for scale in AxisScale::ALL {
+                    ui.selectable_value(&mut self.scale, scale, scale.name());
+                }
+            });
+        if self.scale == AxisScale::Decibels {
+            ui.add(
+                egui::DragValue::new(&mut self.reference)
+                    .range(1e-6..=f32::MAX)
+                    .speed(0.1)
+                    .prefix("re "),
+            )
+            .on_hover_text("The value that is 0 dB");
+        }
+    }
+
+    /// The label for a tick at `shown` up the axis. A log axis is labelled with the
+    /// values themselves, not their logs.
+    pub fn tick(self, shown: f32) -> String {
+        match self.scale {
+            AxisScale::Log10 => short_number(self.invert(shown)),
+            AxisScale::Linear | AxisScale::Decibels => short_number(shown),
+        }
+    }
+}
+
+/// `value` with at most three decimals, or in scientific notation if it is very big or small
+fn short_number(value: f32) -> String {
+    if value == 0.0 || (0.01..100_000.0).contains(&value.abs()) {
+        format!("{value:.3}")
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned()
+    } else {
+        format!("{value:.1e}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::Channels;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn counts_to_millivolts_and_back() {
+        let mut channels = Channels::new(10);
+        channels.push("raw", 0.0, 0.0);
+        channels.push("motor", 0.0, 0.0);
+        let mut raw = channels.get("raw").unwrap();
+        let motor = channels.get("motor").unwrap();
+        let mut units = Units {
+            millivolts: true,
+            ..Units::default()
+        };
+
+        // 5 V over 10 bits, with no amplifier
+        let scale = units.scale(raw);
+        assert_close(scale.apply(1023.0), 5000.0);
+        assert_close(scale.apply(0.0), 0.0);
+        for counts in [0.0, 1.0, 512.0, 1023.0] {
+            assert_close(scale.invert(scale.apply(counts)), counts);
+        }
+
+        // angles are left alone
+        assert_eq!(units.scale(motor).apply(45.0), 45.0);
+        units.millivolts = false;
+        assert_eq!(units.scale(raw).apply(512.0), 512.0);
+
+        // the amplifier's gain and offset come back out
+        channels.iter_mut().for_each(|channel| {
+            channel.gain = 1000.0;
+            channel.offset = 1500.0;
+        });
+        raw = channels.get("raw").unwrap();
+        let scale = Units::default().millivolt_scale(raw);
+        assert_close(scale.apply(306.9), 0.0);
+        for counts in [0.0, 306.9, 1023.0] {
+            assert_close(scale.invert(scale.apply(counts)), counts);
+        }
+    }
+
+    #[test]
+    fn scales_round_trip() {
+        let axes = [
+            ValueAxis::default(),
+            ValueAxis {
+                scale: AxisScale::Log10,
+                reference: 1.0,
+            },
+            ValueAxis {
+                scale: AxisScale::Decibels,
+                reference: 2.5,
+            },
+        ];
+        for axis in axes {
+            for value in [0.01, 1.0, 2.5, 700.0] {
+                let back = axis.invert(axis.apply(value));
+                assert!(
+                    (back - value).abs() <= value * 1e-4,
+                    "{value} came back {back}"
+                );
+            }
+        }
+        assert_eq!(ValueAxis::DECIBELS.apply(10.0), 20.0);
+    }
+
+    #[test]
+    fn labels_and_ticks() {
+        assert_eq!(Units::default().label(), "ADC counts");
+        let log = ValueAxis {
+            scale: AxisScale::Log10,
+            reference: 1.0,
+        };
+        let decibels = ValueAxis {
+            scale: AxisScale::Decibels,
+            reference: 0.5,
+        };
+        assert_eq!(ValueAxis::default().label("mV"), "mV");
+        assert_eq!(log.label("mV"), "log mV");
+        assert_eq!(decibels.label("mV"), "dB re 0.5 mV");
+
+        assert_eq!(ValueAxis::default().tick(512.0), "512");
+        assert_eq!(ValueAxis::default().tick(2.25), "2.25");
+        assert_eq!(ValueAxis::default().tick(0.0), "0");
+        assert_eq!(ValueAxis::default().tick(-1.5), "-1.5");
+        assert_eq!(log.tick(2.0), "100");
+        assert_eq!(decibels.tick(-6.0), "-6");
+        assert_eq!(short_number(1_500_000.0), "1.5e6");
+        assert_eq!(short_number(0.0005), "5.0e-4");
+    }
+}
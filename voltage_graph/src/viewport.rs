@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+/// The narrowest the plot can be zoomed in to, in seconds
+const MIN_WIDTH: f64 = 0.05;
+/// How many panes the main area can be split into
+pub const PANES: usize = 2;
+
+/// Which part of the time axis is shown on the plot
+#[derive(Clone, Copy)]
+pub struct TimeViewport {
+    /// Seconds of data across the plot
+    pub width: f64,
+    /// The right edge of the plot, only used when not following the newest data
+    end: f64,
+    live: bool,
+}
+
+impl TimeViewport {
+    pub fn new(width: f64) -> Self {
+        Self {
+            width,
+            end: width,
+            live: true,
+        }
+    }
+
+    /// If the view is following the newest data
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// The times shown on the plot, `newest` is the time of the newest sample
+    pub fn range(&self, newest: f64) -> Range<f64> {
+        let end = if self.live {
+            newest.max(self.width)
+        } else {
+            self.end
+        };
+        (end - self.width)..end
+    }
+
+    /// Freeze the view where it is, new data will keep coming in off screen
+    pub fn pause(&mut self, newest: f64) {
+        if self.live {
+            self.end = self.range(newest).end;
+            self.live = false;
+        }
+    }
+
+    /// Snap back to following the newest data
+    pub fn go_live(&mut self) {
+        self.live = true;
+    }
+
+    pub fn toggle_pause(&mut self, newest: f64) {
+        if self.live {
+            self.pause(newest);
+        } else {
+            self.go_live();
+        }
+    }
+
+    /// Stop following the newest data and put `time` in the middle of the plot
+    pub fn center_on(&mut self, time: f64) {
+        self.end = time + self.width / 2.0;
+        self.live = false;
+    }
+
+    /// Scale the width by `factor`, keeping the time `anchor` at the same place on the plot.
+    /// When live the right edge stays on the newest data instead.
+    pub fn zoom(&mut self, factor: f32, anchor: f64, newest: f64, max_width: f64) {
+        let range = self.range(newest);
+        let width = (self.width * factor as f64).clamp(MIN_WIDTH, max_width.max(MIN_WIDTH));
+
+        if !self.live {
+            let anchor_fraction = (anchor - range.start) / self.width;
+            self.end = anchor + (1.0 - anchor_fraction) * width;
+        }
+        self.width = width;
+    }
+
+    /// Move the view by `seconds`, staying within the data from `oldest` to `newest`
+    pub fn pan(&mut self, seconds: f64, oldest: f64, newest: f64) {
+        self.pause(newest);
+
+        let latest_end = newest.max(self.width);
+        let earliest_end = (oldest + self.width).min(latest_end);
+        self.end = (self.end + seconds).max(earliest_end).min(latest_end);
+    }
+}
+
+/// Keeps the panes of the main area on one time axis. Linked panes all use the shared
+/// viewport, so zooming or panning one moves the others, and they share one cursor line.
+/// An unlinked pane keeps a viewport of its own.
+#[derive(Default)]
+pub struct LinkedPanes {
+    /// The viewport of each pane that is unlinked
+    own: [Option<TimeViewport>; PANES],
+    /// The pane being drawn
+    current: usize,
+    /// The time under the pointer last frame, if it was over a linked pane
+    cursor: Option<f64>,
+    /// The time under the pointer so far this frame
+    hovered: Option<f64>,
+}
+
+impl LinkedPanes {
+    pub fn is_linked(&self, pane: usize) -> bool {
+        self.own[pane].is_none()
+    }
+
+    /// Link or unlink `pane`, where the first `shown` panes are on screen.
+    /// Unlinking starts the pane off where the shared viewport is. Linking it back moves
+    /// the shared viewport to where the pane is, unless another linked pane is on screen,
+    /// then the pane lines up with that one instead so only the pane that was clicked moves.
+    pub fn set_linked(
+        &mut self,
+        pane: usize,
+        linked: bool,
+        shown: usize,
+        shared: &mut TimeViewport,
+    ) {
+        if !linked {
+            self.own[pane].get_or_insert(*shared);
+            return;
+        }
+        let Some(own) = self.own[pane].take() else {
+            return;
+        };
+        let others_linked = (0..shown).any(|other| other != pane && self.is_linked(other));
+        if !others_linked {
+            *shared = own;
+        }
+    }
+
+    /// Start a new frame, the cursor drawn is where the pointer was last frame
+    pub fn begin_frame(&mut self) {
+        self.cursor = self.hovered.take();
+    }
+
+    /// Put the viewport of `pane` in `shared` while the pane is drawn, if it is unlinked.
+    /// Call `leave` once it is drawn to put them back.
+    pub fn enter(&mut self, pane: usize, shared: &mut TimeViewport) {
+        self.current = pane;
+        if let Some(own) = &mut self.own[pane] {
+            std::mem::swap(own, shared);
+        }
+    }
+
+    pub fn leave(&mut self, shared: &mut TimeViewport) {
+        if let Some(own) = &mut self.own[self.current] {
+            std::mem::swap(own, shared);
+        }
+    }
+
+    /// The other linked panes out of the first `shown`, none if the pane being drawn is unlinked
+    pub fn linked_others(&self, shown: usize) -> Vec<usize> {
+        if !self.is_linked(self.current) {
+            return Vec::new();
+        }
+        (0..shown)
+            .filter(|&pane| pane != self.current && self.is_linked(pane))
+            .collect()
+    }
+
+    /// The time of the cursor line to draw in the pane being drawn, `None` if it is unlinked
+    pub fn cursor(&self) -> Option<f64> {
+        self.cursor.filter(|_| self.is_linked(self.current))
+    }
+
+    /// The pointer is over `time` in the pane being drawn, shared if the pane is linked
+    pub fn hover(&mut self, time: f64) {
+        if self.is_linked(self.current) {
+            self.hovered = Some(time);
+        }
+    }
+}